@@ -0,0 +1,153 @@
+//! Hosts downloads may be fetched from.
+//!
+//! URLs come from the webview, so the desktop side decides where they may point: the mod CDNs,
+//! the configured HygherTales proxy if it runs on this machine, and hosts the user confirmed in
+//! a native dialog (see the `trust_download_host` and `set_proxy_base_url` commands). The initial
+//! URL and every redirect hop are checked.
+
+use std::net::IpAddr;
+
+use reqwest::Url;
+
+use crate::settings::Settings;
+
+/// Prefix of the error returned for a URL outside the allowlist.
+pub const HOST_NOT_ALLOWED: &str = "HOST_NOT_ALLOWED";
+
+/// Domains mods are served from. Subdomains (e.g. `edge.forgecdn.net`) are included.
+pub const TRUSTED_DOMAINS: [&str; 3] = ["forgecdn.net", "curseforge.com", "orbis.place"];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HostAllowlist {
+    /// Allowed in addition to [`TRUSTED_DOMAINS`]; exact host names, no subdomains.
+    hosts: Vec<String>,
+}
+
+impl HostAllowlist {
+    pub fn new(hosts: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            hosts: hosts.into_iter().map(|h| h.to_ascii_lowercase()).collect(),
+        }
+    }
+
+    /// The proxy's host if it is on this machine, plus the hosts the user trusted. A proxy
+    /// elsewhere is added to the trusted hosts when the user confirms it.
+    pub fn from_settings(settings: &Settings) -> Self {
+        let proxy_host = Url::parse(&settings.proxy_base_url)
+            .ok()
+            .and_then(|u| u.host_str().map(str::to_string))
+            .filter(|h| is_local_host(h));
+        Self::new(
+            proxy_host
+                .into_iter()
+                .chain(settings.trusted_download_hosts.iter().cloned()),
+        )
+    }
+
+    pub fn allows_host(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        TRUSTED_DOMAINS
+            .iter()
+            .any(|d| host == *d || host.strip_suffix(d).is_some_and(|rest| rest.ends_with('.')))
+            || self.hosts.contains(&host)
+    }
+
+    /// Fails with [`HOST_NOT_ALLOWED`] unless `url` is http(s) on an allowed host.
+    pub fn check(&self, url: &Url) -> Result<(), String> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!(
+                "{HOST_NOT_ALLOWED}: {}: URLs cannot be downloaded",
+                url.scheme()
+            ));
+        }
+        match url.host_str() {
+            Some(host) if self.allows_host(host) => Ok(()),
+            Some(host) => Err(format!(
+                "{HOST_NOT_ALLOWED}: {host} is not a trusted download host"
+            )),
+            None => Err(format!("{HOST_NOT_ALLOWED}: {url} has no host")),
+        }
+    }
+
+    pub fn check_str(&self, url: &str) -> Result<(), String> {
+        let parsed = Url::parse(url).map_err(|e| format!("Invalid URL \"{url}\": {e}"))?;
+        self.check(&parsed)
+    }
+}
+
+/// Lowercased host of an http(s) URL, for trusting it.
+pub fn host_of(url: &str) -> Result<String, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Invalid URL \"{url}\": {e}"))?;
+    match (parsed.scheme(), parsed.host_str()) {
+        ("http" | "https", Some(host)) => Ok(host.to_ascii_lowercase()),
+        _ => Err(format!("Only http(s) hosts can be trusted, not \"{url}\"")),
+    }
+}
+
+/// `localhost` or a loopback address, e.g. the bundled proxy.
+pub fn is_local_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_cdn_subdomains_and_configured_hosts() {
+        let list = HostAllowlist::from_settings(&Settings {
+            trusted_download_hosts: vec!["mods.example.org".to_string()],
+            ..Settings::default()
+        });
+        for ok in [
+            "https://edge.forgecdn.net/files/1/2/a.jar",
+            "https://mediafilez.forgecdn.net/a.jar",
+            "https://cdn.orbis.place/a.jar",
+            "http://localhost:8787/v1/file",
+            "https://MODS.example.org/a.jar",
+        ] {
+            assert!(list.check_str(ok).is_ok(), "{ok}");
+        }
+        for bad in [
+            "https://evilforgecdn.net/a.jar",
+            "https://forgecdn.net.evil.com/a.jar",
+            "https://sub.mods.example.org/a.jar",
+            "http://127.0.0.1/a.jar",
+            "http://192.168.1.1/a.jar",
+            "file:///etc/passwd",
+        ] {
+            let err = list.check_str(bad).unwrap_err();
+            assert!(err.starts_with(HOST_NOT_ALLOWED), "{bad}: {err}");
+        }
+    }
+
+    #[test]
+    fn only_a_local_proxy_is_allowed_without_asking() {
+        let with_proxy = |url: &str| {
+            HostAllowlist::from_settings(&Settings {
+                proxy_base_url: url.to_string(),
+                ..Settings::default()
+            })
+        };
+        assert!(with_proxy("http://127.0.0.1:8787").allows_host("127.0.0.1"));
+        assert!(with_proxy("http://[::1]:8787").allows_host("[::1]"));
+        assert!(!with_proxy("https://proxy.example.org").allows_host("proxy.example.org"));
+        assert!(!with_proxy("http://192.168.1.1").allows_host("192.168.1.1"));
+        assert!(is_local_host("LOCALHOST") && !is_local_host("localhost.example.org"));
+    }
+
+    #[test]
+    fn host_of_requires_http() {
+        assert_eq!(
+            host_of(" https://Mods.Example.org/x ").unwrap(),
+            "mods.example.org"
+        );
+        assert!(host_of("file:///tmp/x").is_err());
+        assert!(host_of("nonsense").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Checks that a download is a mod archive (ZIP/JAR) before it is installed.
+//!
+//! Mirrors and expired signed URLs sometimes answer with an HTML or JSON error page and a 200
+//! status. Such responses are caught by their Content-Type, and every finished download must
+//! start with a ZIP local file header and have a readable central directory.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Error code prefix of a download that is not a ZIP/JAR archive.
+pub const NOT_A_MOD_ARCHIVE: &str = "NOT_A_MOD_ARCHIVE";
+
+const LOCAL_HEADER_SIG: [u8; 4] = *b"PK\x03\x04";
+const CENTRAL_HEADER_SIG: [u8; 4] = *b"PK\x01\x02";
+const EOCD_SIG: [u8; 4] = *b"PK\x05\x06";
+const ZIP64_LOCATOR_SIG: [u8; 4] = *b"PK\x06\x07";
+const ZIP64_EOCD_SIG: [u8; 4] = *b"PK\x06\x06";
+const EOCD_LEN: u64 = 22;
+const ZIP64_LOCATOR_LEN: u64 = 20;
+const CENTRAL_HEADER_LEN: usize = 46;
+/// The end of central directory record is followed by a comment of at most this many bytes.
+const MAX_COMMENT_LEN: u64 = 0xFFFF;
+/// Central directories larger than this are not read (no mod has millions of entries).
+const MAX_CENTRAL_DIR_LEN: u64 = 64 * 1024 * 1024;
+
+fn not_archive(reason: impl std::fmt::Display) -> String {
+    format!("{NOT_A_MOD_ARCHIVE}: not a mod archive ({reason})")
+}
+
+/// Reject responses whose Content-Type says they are a page or a document, not a file.
+/// A missing or generic type (e.g. `application/octet-stream`) passes.
+pub fn check_content_type(content_type: Option<&str>) -> Result<(), String> {
+    let Some(value) = content_type else {
+        return Ok(());
+    };
+    let mime = value
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let is_document = mime.starts_with("text/")
+        || mime.ends_with("/json")
+        || mime.ends_with("+json")
+        || mime.ends_with("/xml")
+        || mime.ends_with("+xml");
+    if is_document {
+        return Err(not_archive(format!("the server sent {mime}")));
+    }
+    Ok(())
+}
+
+fn u16_at(b: &[u8], at: usize) -> u64 {
+    u16::from_le_bytes([b[at], b[at + 1]]) as u64
+}
+
+fn u32_at(b: &[u8], at: usize) -> u64 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap()) as u64
+}
+
+fn u64_at(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn read_at(f: &mut File, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+    let mut buf = vec![0u8; len];
+    f.seek(SeekFrom::Start(offset))
+        .and_then(|_| f.read_exact(&mut buf))
+        .map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+/// Location of the central directory: `(offset, size, entries)`.
+fn central_directory(f: &mut File, file_len: u64) -> Result<(u64, u64, u64), String> {
+    let tail_len = file_len.min(EOCD_LEN + MAX_COMMENT_LEN);
+    let tail_start = file_len - tail_len;
+    let tail = read_at(f, tail_start, tail_len as usize)?;
+    let eocd = (0..=tail.len().saturating_sub(EOCD_LEN as usize))
+        .rev()
+        .find(|&i| tail[i..i + 4] == EOCD_SIG)
+        .ok_or_else(|| not_archive("no end of central directory"))?;
+    let record = &tail[eocd..];
+    let eocd_offset = tail_start + eocd as u64;
+    let (entries, size, offset) = (u16_at(record, 10), u32_at(record, 12), u32_at(record, 16));
+    if entries != 0xFFFF && size != 0xFFFF_FFFF && offset != 0xFFFF_FFFF {
+        return Ok((offset, size, entries));
+    }
+
+    // ZIP64: the real values are in a record the locator right before the EOCD points to.
+    let locator_offset = eocd_offset
+        .checked_sub(ZIP64_LOCATOR_LEN)
+        .ok_or_else(|| not_archive("truncated ZIP64 locator"))?;
+    let locator = read_at(f, locator_offset, ZIP64_LOCATOR_LEN as usize)?;
+    if locator[..4] != ZIP64_LOCATOR_SIG {
+        return Err(not_archive("missing ZIP64 locator"));
+    }
+    let record_offset = u64_at(&locator, 8);
+    if record_offset >= locator_offset {
+        return Err(not_archive("bad ZIP64 record offset"));
+    }
+    let record = read_at(f, record_offset, 56)?;
+    if record[..4] != ZIP64_EOCD_SIG {
+        return Err(not_archive("missing ZIP64 end of central directory"));
+    }
+    Ok((
+        u64_at(&record, 48),
+        u64_at(&record, 40),
+        u64_at(&record, 32),
+    ))
+}
+
+/// Check that `path` is a ZIP archive with at least one entry and a central directory whose
+/// headers can all be read. Errors start with [`NOT_A_MOD_ARCHIVE`].
+pub fn validate(path: &Path) -> Result<(), String> {
+    let mut f = File::open(path).map_err(|e| e.to_string())?;
+    let file_len = f.metadata().map_err(|e| e.to_string())?.len();
+    let mut magic = [0u8; 4];
+    if file_len < EOCD_LEN || f.read_exact(&mut magic).is_err() || magic != LOCAL_HEADER_SIG {
+        return Err(not_archive("no ZIP header"));
+    }
+
+    let (offset, size, entries) = central_directory(&mut f, file_len)?;
+    if entries == 0 {
+        return Err(not_archive("the archive is empty"));
+    }
+    if size > MAX_CENTRAL_DIR_LEN || offset.checked_add(size).map_or(true, |end| end > file_len) {
+        return Err(not_archive("central directory out of bounds"));
+    }
+    let dir = read_at(&mut f, offset, size as usize)?;
+    let mut at = 0usize;
+    for n in 0..entries {
+        let header = dir
+            .get(at..at + CENTRAL_HEADER_LEN)
+            .filter(|h| h[..4] == CENTRAL_HEADER_SIG)
+            .ok_or_else(|| not_archive(format!("central directory entry {n} is damaged")))?;
+        let local_offset = u32_at(header, 42);
+        if local_offset != 0xFFFF_FFFF && local_offset >= offset {
+            return Err(not_archive(format!("entry {n} points past its data")));
+        }
+        let variable = u16_at(header, 28) + u16_at(header, 30) + u16_at(header, 32);
+        at += CENTRAL_HEADER_LEN + variable as usize;
+        if at > dir.len() {
+            return Err(not_archive(format!(
+                "central directory entry {n} is cut off"
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::body;
+    use std::fs;
+
+    #[test]
+    fn content_type() {
+        for ok in [
+            None,
+            Some("application/java-archive"),
+            Some("application/zip"),
+            Some("application/octet-stream"),
+        ] {
+            assert!(check_content_type(ok).is_ok(), "{ok:?}");
+        }
+        for bad in [
+            "text/html; charset=utf-8",
+            "application/json",
+            "application/problem+json",
+            "application/xml",
+        ] {
+            let err = check_content_type(Some(bad)).unwrap_err();
+            assert!(err.starts_with(NOT_A_MOD_ARCHIVE), "{err}");
+        }
+    }
+
+    #[test]
+    fn accepts_zips_and_rejects_pages_and_damaged_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mod.jar");
+        let check = |bytes: &[u8]| {
+            fs::write(&path, bytes).unwrap();
+            validate(&path)
+        };
+        let zip = body(1000);
+        assert!(check(&zip).is_ok());
+
+        let mut commented = zip.clone();
+        let len = commented.len();
+        commented[len - 2] = 3;
+        commented.extend(b"abc");
+        assert!(check(&commented).is_ok());
+
+        for bad in [
+            b"<!DOCTYPE html><html><body>Link expired</body></html>".to_vec(),
+            b"{\"error\":\"not found\"}".to_vec(),
+            Vec::new(),
+            // Cut off halfway: the central directory is gone.
+            zip[..500].to_vec(),
+            // Central directory header overwritten.
+            {
+                let mut z = zip.clone();
+                let cd = z.len() - 22 - 51;
+                z[cd..cd + 4].copy_from_slice(b"XXXX");
+                z
+            },
+        ] {
+            let err = check(&bad).unwrap_err();
+            assert!(err.starts_with(NOT_A_MOD_ARCHIVE), "{err}");
+        }
+    }
+}
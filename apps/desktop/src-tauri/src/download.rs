@@ -0,0 +1,1278 @@
+//! Streaming HTTP downloads with progress reporting.
+//!
+//! The body is copied in fixed-size chunks to a temp file in a staging folder under app data, so
+//! memory use does not depend on the size of the mod and the Mods folder only ever sees complete
+//! files. Progress is reported through a callback (the
+//! command turns it into `download-progress` events keyed by the frontend's download id).
+//! Interrupted downloads are kept and resumed with HTTP range requests. Transient failures are
+//! retried according to the client's [`RetryPolicy`](crate::retry::RetryPolicy).
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::header::{
+    HeaderName, CONTENT_DISPOSITION, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE, LAST_MODIFIED,
+    RANGE,
+};
+use reqwest::StatusCode;
+use sha1::Digest;
+
+use crate::archive;
+use crate::file_name;
+use crate::http::HttpClient;
+use crate::persist;
+use crate::retry::{self, Failure};
+
+/// Tauri event carrying a [`DownloadProgress`] payload.
+pub const PROGRESS_EVENT: &str = "download-progress";
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Minimum time between two progress events for the same download.
+const EMIT_INTERVAL: Duration = Duration::from_millis(200);
+/// Weight of the newest sample in the smoothed speed.
+const SPEED_SMOOTHING: f64 = 0.3;
+
+/// Payload of the `download-progress` event.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub download_id: String,
+    pub received: u64,
+    /// From Content-Length; `None` if the server did not send one.
+    pub total: Option<u64>,
+    pub bytes_per_sec: f64,
+    /// Seconds left at the current speed; `None` if the total or speed is unknown.
+    pub eta_secs: Option<f64>,
+    /// Set on the last event of a successful download.
+    pub done: bool,
+    /// 1 for the first attempt, counting up with every retry.
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// Set while waiting to retry after `last_error`.
+    pub retry_in_secs: Option<f64>,
+    pub last_error: Option<String>,
+}
+
+/// Counts bytes for one download and decides when a progress event is due.
+pub struct ProgressTracker {
+    download_id: String,
+    total: Option<u64>,
+    received: u64,
+    last_emit: Instant,
+    bytes_since_emit: u64,
+    bytes_per_sec: f64,
+    attempt: u32,
+    max_attempts: u32,
+}
+
+impl ProgressTracker {
+    pub fn new(download_id: String, total: Option<u64>, now: Instant) -> Self {
+        Self {
+            download_id,
+            total,
+            received: 0,
+            last_emit: now,
+            bytes_since_emit: 0,
+            bytes_per_sec: 0.0,
+            attempt: 1,
+            max_attempts: 1,
+        }
+    }
+
+    /// Tag events with the attempt number.
+    pub fn attempt(mut self, attempt: u32, max_attempts: u32) -> Self {
+        self.attempt = attempt;
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Start counting from `offset` bytes already on disk (a resumed download).
+    pub fn starting_at(mut self, offset: u64) -> Self {
+        self.received = offset;
+        self
+    }
+
+    /// Count `n` more bytes. Returns an event if at least [`EMIT_INTERVAL`] passed since the last.
+    pub fn advance(&mut self, n: u64, now: Instant) -> Option<DownloadProgress> {
+        self.received += n;
+        self.bytes_since_emit += n;
+        let elapsed = now.saturating_duration_since(self.last_emit);
+        if elapsed < EMIT_INTERVAL {
+            return None;
+        }
+        self.sample(elapsed, now);
+        Some(self.snapshot(false))
+    }
+
+    /// Final event for a completed download.
+    pub fn finish(&mut self, now: Instant) -> DownloadProgress {
+        let elapsed = now.saturating_duration_since(self.last_emit);
+        if self.bytes_since_emit > 0 && !elapsed.is_zero() {
+            self.sample(elapsed, now);
+        }
+        self.snapshot(true)
+    }
+
+    fn sample(&mut self, elapsed: Duration, now: Instant) {
+        let current = self.bytes_since_emit as f64 / elapsed.as_secs_f64();
+        self.bytes_per_sec = if self.bytes_per_sec == 0.0 {
+            current
+        } else {
+            SPEED_SMOOTHING * current + (1.0 - SPEED_SMOOTHING) * self.bytes_per_sec
+        };
+        self.bytes_since_emit = 0;
+        self.last_emit = now;
+    }
+
+    fn snapshot(&self, done: bool) -> DownloadProgress {
+        let eta_secs = match self.total {
+            _ if done => Some(0.0),
+            Some(total) if self.bytes_per_sec > 0.0 => {
+                Some(total.saturating_sub(self.received) as f64 / self.bytes_per_sec)
+            }
+            _ => None,
+        };
+        DownloadProgress {
+            download_id: self.download_id.clone(),
+            received: self.received,
+            total: self.total,
+            bytes_per_sec: self.bytes_per_sec,
+            eta_secs,
+            done,
+            attempt: self.attempt,
+            max_attempts: self.max_attempts,
+            retry_in_secs: None,
+            last_error: None,
+        }
+    }
+}
+
+/// One file to download, as sent by the frontend.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadRequest {
+    pub url: String,
+    pub dest_path: String,
+    /// Tags the progress events of this download.
+    #[serde(default)]
+    pub download_id: Option<String>,
+    /// Hex digests and length published by the provider, checked before the file is kept.
+    #[serde(default)]
+    pub expected_sha1: Option<String>,
+    #[serde(default)]
+    pub expected_md5: Option<String>,
+    #[serde(default)]
+    pub expected_size: Option<u64>,
+    /// Provider file key (`curseforge:<fileId>`, `orbis:<versionId>:<fileIndex>`) used to find
+    /// the file in the download cache.
+    #[serde(default)]
+    pub cache_key: Option<String>,
+    /// Name the file after the server's `Content-Disposition` or the final URL (see
+    /// [`file_name::from_response`]) and only keep the directory of `dest_path`. Its file name is
+    /// the fallback when the server does not send a usable one.
+    #[serde(default)]
+    pub use_server_file_name: bool,
+    /// Set by the backend: the staging folder (see [`DownloadRequest::partial_dir`]). `None`
+    /// keeps the unfinished download next to the destination.
+    #[serde(skip)]
+    pub staging_dir: Option<PathBuf>,
+}
+
+/// Where a download ended up, as returned to the frontend.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadedFile {
+    pub path: String,
+    pub file_name: String,
+}
+
+impl From<&Path> for DownloadedFile {
+    fn from(path: &Path) -> Self {
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            file_name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Error code prefix of a download whose length differs from `expected_size`.
+pub const SIZE_MISMATCH: &str = "SIZE_MISMATCH";
+/// Error code prefix of a download whose SHA-1 or MD5 differs from the expected one.
+pub const CHECKSUM_MISMATCH: &str = "CHECKSUM_MISMATCH";
+
+fn expected_hex(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(|v| v.trim().to_ascii_lowercase())
+        .filter(|v| !v.is_empty())
+}
+
+/// Check a downloaded file against the expected length and digests. Errors start with
+/// [`SIZE_MISMATCH`] or [`CHECKSUM_MISMATCH`].
+pub fn verify(path: &Path, req: &DownloadRequest) -> Result<(), String> {
+    let len = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    if let Some(expected) = req.expected_size {
+        if len != expected {
+            return Err(format!(
+                "{SIZE_MISMATCH}: downloaded {len} bytes, expected {expected}"
+            ));
+        }
+    }
+    let sha1 = expected_hex(&req.expected_sha1);
+    let md5 = expected_hex(&req.expected_md5);
+    if sha1.is_none() && md5.is_none() {
+        return Ok(());
+    }
+
+    let mut sha1_hasher = sha1.as_ref().map(|_| sha1::Sha1::new());
+    let mut md5_hasher = md5.as_ref().map(|_| md5::Md5::new());
+    let mut f = fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = f.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        if let Some(h) = sha1_hasher.as_mut() {
+            h.update(&buf[..n]);
+        }
+        if let Some(h) = md5_hasher.as_mut() {
+            h.update(&buf[..n]);
+        }
+    }
+    let checks = [
+        (
+            "SHA-1",
+            sha1,
+            sha1_hasher.map(|h| format!("{:x}", h.finalize())),
+        ),
+        (
+            "MD5",
+            md5,
+            md5_hasher.map(|h| format!("{:x}", h.finalize())),
+        ),
+    ];
+    for (name, expected, actual) in checks {
+        if let (Some(expected), Some(actual)) = (expected, actual) {
+            if expected != actual {
+                return Err(format!(
+                    "{CHECKSUM_MISMATCH}: {name} is {actual}, expected {expected}"
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Error message of a transfer stopped through the `stop` callback.
+pub const STOPPED: &str = "Download stopped";
+
+/// Copy `reader` into `writer` in chunks, reporting progress through `emit`.
+/// `stop` is polled between chunks; when it returns true the copy ends with [`STOPPED`].
+pub fn copy_with_progress<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    tracker: &mut ProgressTracker,
+    stop: &dyn Fn() -> bool,
+    emit: &mut dyn FnMut(DownloadProgress),
+) -> io::Result<u64> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut written = 0u64;
+    loop {
+        if stop() {
+            return Err(io::Error::new(io::ErrorKind::Other, STOPPED));
+        }
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..n])?;
+        written += n as u64;
+        if let Some(progress) = tracker.advance(n as u64, Instant::now()) {
+            emit(progress);
+        }
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+/// Sidecar saved next to a partial download (`<file>.tmp.meta`) so it can be resumed.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialMeta {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub bytes_written: u64,
+    pub total: Option<u64>,
+    /// Name the server gave the file, when the request asked for it.
+    #[serde(default)]
+    pub file_name: Option<String>,
+}
+
+impl PartialMeta {
+    /// Value for `If-Range`. Weak ETags are not allowed there, so Last-Modified is used instead.
+    fn validator(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|e| !e.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Folder under app data where unfinished downloads are kept.
+pub const STAGING_DIRNAME: &str = "download-staging";
+/// Staged downloads untouched for this long are deleted at startup.
+pub const STAGING_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// Prefix of finished updates the frontend parks in the Mods folder until `apply_mod_update`.
+pub const UPDATE_TEMP_PREFIX: &str = ".ht-update-";
+/// Prefix of the error for a download to a destination another download is writing.
+pub const DOWNLOAD_IN_PROGRESS: &str = "DOWNLOAD_IN_PROGRESS";
+
+/// Temp files of the downloads running now. Two downloads to one destination would share the
+/// temp file, so the second is refused while the first runs.
+static ACTIVE: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// A download's claim on its temp file, released when dropped.
+struct ActiveDownload(PathBuf);
+
+impl ActiveDownload {
+    fn claim(req: &DownloadRequest) -> Result<Self, String> {
+        let temp_path = req.temp_path();
+        let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+        if active.contains(&temp_path) {
+            return Err(format!(
+                "{DOWNLOAD_IN_PROGRESS}: {} is already being downloaded",
+                req.dest().display()
+            ));
+        }
+        active.push(temp_path.clone());
+        Ok(Self(temp_path))
+    }
+}
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        let mut active = ACTIVE.lock().unwrap_or_else(|e| e.into_inner());
+        active.retain(|p| *p != self.0);
+    }
+}
+
+impl DownloadRequest {
+    fn dest(&self) -> PathBuf {
+        PathBuf::from(self.dest_path.trim())
+    }
+
+    /// Folder of the unfinished download: in the staging folder, a subfolder named after a hash
+    /// of the destination, so downloads never share temp files; else the destination's folder.
+    pub fn partial_dir(&self) -> PathBuf {
+        let dest = self.dest();
+        match self.staging_dir {
+            Some(ref staging) => {
+                let hash = sha1::Sha1::digest(dest.to_string_lossy().as_bytes());
+                staging.join(&format!("{hash:x}")[..16])
+            }
+            None => dest.parent().unwrap_or(Path::new("")).to_path_buf(),
+        }
+    }
+
+    /// `foo.jar` -> `<partial dir>/foo.jar.tmp`
+    pub fn temp_path(&self) -> PathBuf {
+        self.partial_dir().join(with_suffix(
+            Path::new(self.dest().file_name().unwrap_or_default()),
+            ".tmp",
+        ))
+    }
+
+    /// `foo.jar` -> `<partial dir>/foo.jar.tmp.meta`
+    pub fn meta_path(&self) -> PathBuf {
+        with_suffix(&self.temp_path(), ".meta")
+    }
+
+    /// Delete the temp file and sidecar of the unfinished download, and its staging subfolder.
+    pub fn discard_partial(&self) {
+        discard_partial(&self.temp_path(), &self.meta_path());
+        if self.staging_dir.is_some() {
+            let _ = fs::remove_dir(self.partial_dir());
+        }
+    }
+}
+
+/// True for the temp file or sidecar of an unfinished download.
+pub fn is_partial_download(name: &str) -> bool {
+    name.ends_with(".tmp") || name.ends_with(".tmp.meta")
+}
+
+/// Delete what interrupted downloads left behind: updates parked in `mod_folders` (only files
+/// named with [`UPDATE_TEMP_PREFIX`], so nothing of the user's is touched), and staged downloads
+/// that cannot be resumed or are older than `max_age`. Only call this while no download runs.
+/// Returns the number of files and folders removed.
+pub fn sweep_leftovers(staging: &Path, mod_folders: &[PathBuf], max_age: Duration) -> usize {
+    let mut removed = 0;
+    for folder in mod_folders {
+        let Ok(entries) = fs::read_dir(folder) else {
+            continue;
+        };
+        for e in entries.filter_map(|e| e.ok()) {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if name.starts_with(UPDATE_TEMP_PREFIX)
+                && e.path().is_file()
+                && fs::remove_file(e.path()).is_ok()
+            {
+                eprintln!("[download] Removed leftover {}", e.path().display());
+                removed += 1;
+            }
+        }
+    }
+
+    let Ok(entries) = fs::read_dir(staging) else {
+        return removed;
+    };
+    for e in entries.filter_map(|e| e.ok()) {
+        let path = e.path();
+        if !path.is_dir() {
+            removed += usize::from(fs::remove_file(&path).is_ok());
+            continue;
+        }
+        let files: Vec<_> = fs::read_dir(&path)
+            .map(|d| d.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+            .unwrap_or_default();
+        let resumable = files
+            .iter()
+            .any(|f| f.to_string_lossy().ends_with(".tmp.meta") && read_meta(f).is_some());
+        let stale = files.iter().all(|f| {
+            fs::metadata(f)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.elapsed().ok())
+                .map_or(true, |age| age > max_age)
+        });
+        if (!resumable || stale) && fs::remove_dir_all(&path).is_ok() {
+            eprintln!("[download] Removed staged download {}", path.display());
+            removed += 1;
+        }
+    }
+    removed
+}
+
+fn read_meta(path: &Path) -> Option<PartialMeta> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+fn write_meta(path: &Path, meta: &PartialMeta) -> Result<(), String> {
+    let json = serde_json::to_vec_pretty(meta).map_err(|e| e.to_string())?;
+    persist::write_atomic(path, &json, 0).map_err(|e| e.to_string())
+}
+
+fn discard_partial(temp_path: &Path, meta_path: &Path) {
+    let _ = fs::remove_file(temp_path);
+    let _ = fs::remove_file(meta_path);
+}
+
+/// Offset and metadata of a partial download of `url` that can be resumed, if any.
+fn resume_point(url: &str, temp_path: &Path, meta_path: &Path) -> Option<(u64, PartialMeta)> {
+    let meta = read_meta(meta_path)?;
+    let len = fs::metadata(temp_path).ok()?.len();
+    if meta.url != url || meta.validator().is_none() || len == 0 {
+        return None;
+    }
+    Some((len, meta))
+}
+
+fn header(resp: &reqwest::blocking::Response, name: HeaderName) -> Option<String> {
+    resp.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// `bytes 100-999/1000` -> `(100, Some(1000))`. The total may be `*` (unknown).
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+    let rest = value.trim().strip_prefix("bytes ")?;
+    let (range, total) = rest.split_once('/')?;
+    let (start, _) = range.split_once('-')?;
+    Some((start.trim().parse().ok()?, total.trim().parse().ok()))
+}
+
+fn http_error(status: StatusCode) -> String {
+    format!("Download failed: HTTP {status}")
+}
+
+/// A finished [`download_to_file`].
+#[derive(Debug)]
+pub struct SavedFile {
+    pub path: PathBuf,
+    /// With `use_server_file_name`, the server's name for the file, before a taken name got a
+    /// ` (1)` suffix.
+    pub server_file_name: Option<String>,
+}
+
+/// Download `req.url` to `req.dest_path` (or a free variant of it if taken).
+/// With `use_server_file_name`, the file is named as the server says, in the same directory.
+///
+/// An interrupted or stopped download leaves `<file>.tmp` and `<file>.tmp.meta` in
+/// [`DownloadRequest::partial_dir`]; the next call for the same destination and URL resumes with `Range`/`If-Range`. If the server ignores
+/// the range or the file changed in the meantime, it starts over. Progress is only reported when
+/// a `download_id` is given. An error page served with a 200 (by Content-Type), or a finished
+/// file that fails [`verify`] or [`archive::validate`], is deleted, not resumed.
+///
+/// Transient failures are retried per `http.retry`; a retry of a cut-off transfer resumes it. While
+/// waiting, a progress event with `retry_in_secs` is reported.
+pub fn download_to_file(
+    req: &DownloadRequest,
+    http: &HttpClient,
+    stop: &dyn Fn() -> bool,
+    mut emit: impl FnMut(DownloadProgress),
+) -> Result<SavedFile, String> {
+    let url = req.url.trim();
+    let dest = req.dest();
+    if url.is_empty() {
+        return Err("URL is empty".to_string());
+    }
+    http.allowlist.check_str(url)?;
+    let _claim = ActiveDownload::claim(req)?;
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::create_dir_all(req.partial_dir()).map_err(|e| e.to_string())?;
+    let retry = &http.retry;
+    let max_attempts = retry.max_attempts();
+    let mut attempt = 1;
+    loop {
+        if attempt > 1 {
+            eprintln!("[download] {url}: attempt {attempt}/{max_attempts}");
+        }
+        let (error, retry_after) =
+            match download_attempt(req, http.client(), (attempt, max_attempts), stop, &mut emit) {
+                Ok(path) => return Ok(path),
+                Err(Failure::Fatal(e)) => return Err(e),
+                Err(Failure::Transient { error, retry_after }) => (error, retry_after),
+            };
+        if attempt >= max_attempts {
+            if max_attempts == 1 {
+                return Err(error);
+            }
+            eprintln!("[download] {url}: giving up after {max_attempts} attempts: {error}");
+            return Err(format!("{error} (gave up after {max_attempts} attempts)"));
+        }
+        let Some(delay) = retry.delay(attempt, retry_after) else {
+            let secs = retry_after.unwrap_or_default().as_secs();
+            return Err(format!("{error}; the server asked to retry after {secs}s"));
+        };
+        eprintln!(
+            "[download] {url}: attempt {attempt}/{max_attempts} failed ({error}); retrying in {:.1}s",
+            delay.as_secs_f64()
+        );
+        if let Some(ref id) = req.download_id {
+            let total = read_meta(&req.meta_path()).and_then(|m| m.total);
+            let received = fs::metadata(req.temp_path()).map_or(0, |m| m.len());
+            let mut progress = ProgressTracker::new(id.clone(), total, Instant::now())
+                .starting_at(received)
+                .attempt(attempt, max_attempts)
+                .snapshot(false);
+            progress.retry_in_secs = Some(delay.as_secs_f64());
+            progress.last_error = Some(error);
+            emit(progress);
+        }
+        if !retry::wait(delay, stop) {
+            return Err(STOPPED.to_string());
+        }
+        attempt += 1;
+    }
+}
+
+/// One attempt of [`download_to_file`]. `attempt` is `(number, max)`, for progress events.
+fn download_attempt(
+    req: &DownloadRequest,
+    client: &reqwest::blocking::Client,
+    attempt: (u32, u32),
+    stop: &dyn Fn() -> bool,
+    emit: &mut dyn FnMut(DownloadProgress),
+) -> Result<SavedFile, Failure> {
+    let url = req.url.trim();
+    let dest = req.dest();
+    let temp_path = req.temp_path();
+    let meta_path = req.meta_path();
+    let resume = resume_point(url, &temp_path, &meta_path);
+    if resume.is_none() {
+        discard_partial(&temp_path, &meta_path);
+    }
+
+    let full = || -> Result<(reqwest::blocking::Response, u64, Option<u64>), Failure> {
+        let resp = client.get(url).send().map_err(Failure::from_reqwest)?;
+        if !resp.status().is_success() {
+            return Err(Failure::from_response(&resp, http_error(resp.status())));
+        }
+        let total = resp.content_length();
+        Ok((resp, 0, total))
+    };
+    let (mut resp, offset, total) = match resume {
+        None => full()?,
+        Some((offset, ref meta)) => {
+            let resp = client
+                .get(url)
+                .header(RANGE, format!("bytes={offset}-"))
+                .header(IF_RANGE, meta.validator().unwrap_or_default())
+                .send()
+                .map_err(Failure::from_reqwest)?;
+            match resp.status() {
+                StatusCode::PARTIAL_CONTENT => {
+                    match header(&resp, CONTENT_RANGE).and_then(|v| parse_content_range(&v)) {
+                        Some((start, total)) if start == offset => {
+                            eprintln!("[download] Resuming {url} at {offset} bytes");
+                            (resp, offset, total.or(meta.total))
+                        }
+                        _ => {
+                            discard_partial(&temp_path, &meta_path);
+                            full()?
+                        }
+                    }
+                }
+                StatusCode::RANGE_NOT_SATISFIABLE => {
+                    discard_partial(&temp_path, &meta_path);
+                    full()?
+                }
+                // Range ignored, or the file changed since the partial download: full body follows.
+                s if s.is_success() => {
+                    discard_partial(&temp_path, &meta_path);
+                    let total = resp.content_length();
+                    (resp, 0, total)
+                }
+                s => return Err(Failure::from_response(&resp, http_error(s))),
+            }
+        }
+    };
+
+    if let Err(e) = archive::check_content_type(header(&resp, CONTENT_TYPE).as_deref()) {
+        eprintln!("[download] {url}: {e}");
+        discard_partial(&temp_path, &meta_path);
+        return Err(Failure::Fatal(e));
+    }
+
+    let previous = resume.map(|(_, meta)| meta);
+    let file_name = if req.use_server_file_name {
+        file_name::from_response(header(&resp, CONTENT_DISPOSITION).as_deref(), resp.url())
+            .or_else(|| previous.as_ref().and_then(|m| m.file_name.clone()))
+    } else {
+        None
+    };
+    let mut meta = PartialMeta {
+        url: url.to_string(),
+        etag: header(&resp, ETAG).or_else(|| previous.as_ref().and_then(|m| m.etag.clone())),
+        last_modified: header(&resp, LAST_MODIFIED)
+            .or_else(|| previous.as_ref().and_then(|m| m.last_modified.clone())),
+        bytes_written: offset,
+        total,
+        file_name,
+    };
+    write_meta(&meta_path, &meta)?;
+
+    let download_id = req.download_id.clone();
+    let has_id = download_id.is_some();
+    let mut tracker = ProgressTracker::new(download_id.unwrap_or_default(), total, Instant::now())
+        .starting_at(offset)
+        .attempt(attempt.0, attempt.1);
+    let mut report = |p: DownloadProgress| {
+        if has_id {
+            emit(p);
+        }
+    };
+    report(tracker.snapshot(false));
+
+    let result = (|| {
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&temp_path)
+            .map_err(|e| e.to_string())?;
+        let copied = copy_with_progress(&mut resp, &mut f, &mut tracker, stop, &mut report);
+        f.sync_all().map_err(|e| e.to_string())?;
+        copied.map_err(|e| e.to_string())
+    })();
+    if let Err(e) = result {
+        let written = fs::metadata(&temp_path).map(|m| m.len()).unwrap_or(0);
+        meta.bytes_written = written;
+        if written > 0 && meta.validator().is_some() && write_meta(&meta_path, &meta).is_ok() {
+            if e == STOPPED {
+                return Err(Failure::Fatal(e));
+            }
+            return Err(Failure::transient(format!(
+                "Download interrupted after {written} bytes: {e}. It will resume on the next attempt."
+            )));
+        }
+        discard_partial(&temp_path, &meta_path);
+        if e == STOPPED {
+            return Err(Failure::Fatal(e));
+        }
+        return Err(Failure::transient(format!("Download interrupted: {e}")));
+    }
+
+    if let Err(e) = verify(&temp_path, req).and_then(|()| archive::validate(&temp_path)) {
+        eprintln!("[download] {url}: {e}");
+        discard_partial(&temp_path, &meta_path);
+        return Err(Failure::Fatal(e));
+    }
+
+    let target = match meta.file_name {
+        Some(ref name) => dest.with_file_name(name),
+        None => dest,
+    };
+    let final_path = crate::unique_file_path(&target);
+    persist::move_file(&temp_path, &final_path).map_err(|e| e.to_string())?;
+    req.discard_partial();
+    report(tracker.finish(Instant::now()));
+    Ok(SavedFile {
+        path: final_path,
+        server_file_name: meta.file_name,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allowlist::HOST_NOT_ALLOWED;
+    use crate::archive::NOT_A_MOD_ARCHIVE;
+    use crate::retry::RetryPolicy;
+    use crate::test_server::{body, ServeOptions, TestResponse, TestServer};
+    use std::io::Cursor;
+    use std::sync::{Arc, OnceLock};
+
+    /// Request staged in a `download-staging` folder next to the destination's folder.
+    fn request(url: &str, dest: &Path, id: Option<&str>) -> DownloadRequest {
+        DownloadRequest {
+            url: url.to_string(),
+            dest_path: dest.to_string_lossy().into_owned(),
+            download_id: id.map(str::to_string),
+            expected_sha1: None,
+            expected_md5: None,
+            expected_size: None,
+            cache_key: None,
+            use_server_file_name: false,
+            staging_dir: dest.parent().map(|p| p.join(STAGING_DIRNAME)),
+        }
+    }
+
+    fn temp_of(dest: &Path) -> PathBuf {
+        request("", dest, None).temp_path()
+    }
+
+    fn meta_of(dest: &Path) -> PathBuf {
+        request("", dest, None).meta_path()
+    }
+
+    fn serve(data: &[u8], ranges: bool, cut_first_at: Option<usize>) -> TestServer {
+        TestServer::serving(
+            data.to_vec(),
+            ServeOptions {
+                ranges,
+                cut_first_at,
+                ..ServeOptions::default()
+            },
+        )
+    }
+
+    #[test]
+    fn tracker_throttles_and_estimates() {
+        let start = Instant::now();
+        let mut t = ProgressTracker::new("d1".to_string(), Some(1000), start);
+        assert!(t.advance(100, start + Duration::from_millis(50)).is_none());
+        let p = t.advance(100, start + Duration::from_millis(500)).unwrap();
+        assert_eq!(p.received, 200);
+        assert!((p.bytes_per_sec - 400.0).abs() < 1e-6);
+        assert!((p.eta_secs.unwrap() - 2.0).abs() < 1e-6);
+        assert!(!p.done);
+
+        let done = t.finish(start + Duration::from_millis(600));
+        assert!(done.done);
+        assert_eq!(done.eta_secs, Some(0.0));
+    }
+
+    #[test]
+    fn unknown_total_has_no_eta() {
+        let start = Instant::now();
+        let mut t = ProgressTracker::new("d2".to_string(), None, start);
+        let p = t.advance(10, start + Duration::from_secs(1)).unwrap();
+        assert_eq!(p.total, None);
+        assert_eq!(p.eta_secs, None);
+    }
+
+    #[test]
+    fn copies_in_chunks() {
+        let data = vec![7u8; CHUNK_SIZE * 3 + 5];
+        let mut out = Vec::new();
+        let mut t = ProgressTracker::new("d3".to_string(), Some(data.len() as u64), Instant::now());
+        let mut events = Vec::new();
+        let n = copy_with_progress(
+            &mut Cursor::new(&data),
+            &mut out,
+            &mut t,
+            &|| false,
+            &mut |p| events.push(p),
+        )
+        .unwrap();
+        assert_eq!(n, data.len() as u64);
+        assert_eq!(out, data);
+        assert_eq!(t.finish(Instant::now()).received, data.len() as u64);
+    }
+
+    #[test]
+    fn resumes_interrupted_download() {
+        let data = body(300_000);
+        let server = serve(&data, true, Some(100_000));
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+
+        let err = download_to_file(
+            &request(&server.url, &dest, None),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.contains("resume"), "{err}");
+        let meta = read_meta(&meta_of(&dest)).unwrap();
+        assert_eq!(meta.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(meta.bytes_written, 100_000);
+
+        let mut events = Vec::new();
+        let path = download_to_file(
+            &request(&server.url, &dest, Some("d")),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |p| events.push(p),
+        )
+        .unwrap()
+        .path;
+        assert_eq!(fs::read(&path).unwrap(), data);
+        assert_eq!(server.header(1, "range").as_deref(), Some("bytes=100000-"));
+        assert_eq!(server.header(1, "if-range").as_deref(), Some("\"v1\""));
+        assert_eq!(events.first().unwrap().received, 100_000);
+        assert_eq!(events.last().unwrap().received, 300_000);
+        assert!(!temp_of(&dest).exists());
+        assert!(!meta_of(&dest).exists());
+    }
+
+    #[test]
+    fn restarts_when_server_ignores_range() {
+        let data = body(200_000);
+        let server = serve(&data, false, Some(50_000));
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+
+        assert!(download_to_file(
+            &request(&server.url, &dest, None),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |_| {}
+        )
+        .is_err());
+        let path = download_to_file(
+            &request(&server.url, &dest, None),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |_| {},
+        )
+        .unwrap()
+        .path;
+        assert!(server.header(1, "range").is_some());
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn every_destination_gets_its_own_staging_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let jar = request("", &dir.path().join("Mods/foo.jar"), None);
+        let zip = request("", &dir.path().join("Mods/foo.zip"), None);
+        let staging = dir.path().join("Mods").join(STAGING_DIRNAME);
+        assert!(jar.temp_path().starts_with(&staging));
+        assert_ne!(jar.partial_dir(), zip.partial_dir());
+        assert_eq!(jar.temp_path().file_name().unwrap(), "foo.jar.tmp");
+        assert_eq!(jar.meta_path().file_name().unwrap(), "foo.jar.tmp.meta");
+    }
+
+    #[test]
+    fn sweeps_leftovers_but_keeps_resumable_downloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("Mods");
+        let staging = mods.join(STAGING_DIRNAME);
+        fs::create_dir_all(&mods).unwrap();
+        // `.tmp` files in Mods may be the user's; only the app's own prefix is swept there.
+        for name in [
+            "keep.jar",
+            "notes.tmp",
+            "other.jar.tmp.meta",
+            ".ht-update-3-new.jar",
+        ] {
+            fs::write(mods.join(name), b"x").unwrap();
+        }
+        let staged = |name: &str, meta: bool| {
+            let req = request("http://example.invalid/a.jar", &mods.join(name), None);
+            fs::create_dir_all(req.partial_dir()).unwrap();
+            fs::write(req.temp_path(), b"part").unwrap();
+            if meta {
+                let meta = PartialMeta {
+                    url: req.url.clone(),
+                    etag: Some("\"v1\"".to_string()),
+                    last_modified: None,
+                    bytes_written: 4,
+                    total: None,
+                    file_name: None,
+                };
+                write_meta(&req.meta_path(), &meta).unwrap();
+            }
+            req.partial_dir()
+        };
+        let resumable = staged("a.jar", true);
+        let orphan = staged("b.jar", false);
+
+        let removed = sweep_leftovers(&staging, std::slice::from_ref(&mods), STAGING_MAX_AGE);
+        assert_eq!(removed, 2);
+        let mut left: Vec<_> = fs::read_dir(&mods)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            [
+                STAGING_DIRNAME,
+                "keep.jar",
+                "notes.tmp",
+                "other.jar.tmp.meta"
+            ]
+        );
+        assert!(resumable.exists());
+        assert!(!orphan.exists());
+
+        // Too old to be worth resuming.
+        assert_eq!(sweep_leftovers(&staging, &[], Duration::ZERO), 1);
+        assert!(!resumable.exists());
+    }
+
+    #[test]
+    fn refuses_a_second_download_to_the_same_destination() {
+        let data = body(1000);
+        let server = serve(&data, true, None);
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+        let req = request(&server.url, &dest, None);
+        let http = HttpClient::with_retry(RetryPolicy::NONE);
+
+        let running = ActiveDownload::claim(&req).unwrap();
+        let err = download_to_file(&req, &http, &|| false, |_| {}).unwrap_err();
+        assert!(err.starts_with(DOWNLOAD_IN_PROGRESS), "{err}");
+        assert_eq!(server.request_count(), 0);
+        // Another destination is not held up.
+        let other = request(&server.url, &dir.path().join("other.jar"), None);
+        download_to_file(&other, &http, &|| false, |_| {}).unwrap();
+
+        drop(running);
+        let saved = download_to_file(&req, &http, &|| false, |_| {}).unwrap();
+        assert_eq!(fs::read(saved.path).unwrap(), data);
+    }
+
+    #[test]
+    fn partial_for_another_url_is_discarded() {
+        let data = body(1000);
+        let server = serve(&data, true, None);
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+        fs::create_dir_all(temp_of(&dest).parent().unwrap()).unwrap();
+        fs::write(temp_of(&dest), b"stale").unwrap();
+        let meta = PartialMeta {
+            url: "http://example.invalid/other.jar".to_string(),
+            etag: Some("\"v0\"".to_string()),
+            last_modified: None,
+            bytes_written: 5,
+            total: None,
+            file_name: None,
+        };
+        write_meta(&meta_of(&dest), &meta).unwrap();
+
+        let path = download_to_file(
+            &request(&server.url, &dest, None),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |_| {},
+        )
+        .unwrap()
+        .path;
+        assert!(server.header(0, "range").is_none());
+        assert_eq!(fs::read(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn verifies_size_and_checksums_before_keeping_the_file() {
+        // echo -n "hello" | sha1sum / md5sum
+        let server = serve(b"hello", false, None);
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+        let with = |sha1: Option<&str>, md5: Option<&str>, size: Option<u64>| DownloadRequest {
+            expected_sha1: sha1.map(str::to_string),
+            expected_md5: md5.map(str::to_string),
+            expected_size: size,
+            ..request(&server.url, &dest, None)
+        };
+
+        let err = download_to_file(
+            &with(None, None, Some(6)),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.starts_with(SIZE_MISMATCH), "{err}");
+        let err = download_to_file(
+            &with(None, Some("00000000000000000000000000000000"), Some(5)),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.starts_with(CHECKSUM_MISMATCH), "{err}");
+        assert!(!dest.exists());
+        assert!(!temp_of(&dest).exists());
+        assert!(!meta_of(&dest).exists());
+
+        // Checksums match, but it is still not an archive.
+        let err = download_to_file(
+            &with(
+                Some(" AAF4C61DDCC5E8A2DABEDE0F3B482CD9AEA9434D "),
+                Some("5d41402abc4b2a76b9719d911017c592"),
+                Some(5),
+            ),
+            &HttpClient::with_retry(RetryPolicy::NONE),
+            &|| false,
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.starts_with(NOT_A_MOD_ARCHIVE), "{err}");
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn rejects_error_pages_served_as_200() {
+        let html = b"<html><body>This link has expired</body></html>".to_vec();
+        let page = html.clone();
+        let server = TestServer::start(move |_, path, _| match path {
+            "/typed.jar" => TestResponse::new(200, body(1000))
+                .header("Content-Type", "text/html; charset=utf-8"),
+            "/untyped.jar" => TestResponse::new(200, page.clone()),
+            _ => TestResponse::new(200, body(1000))
+                .header("Content-Type", "application/java-archive"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+        let get = |name: &str| {
+            download_to_file(
+                &request(&format!("{}/{name}", server.base), &dest, None),
+                &HttpClient::with_retry(RetryPolicy::NONE),
+                &|| false,
+                |_| {},
+            )
+        };
+
+        for name in ["typed.jar", "untyped.jar"] {
+            let err = get(name).unwrap_err();
+            assert!(err.starts_with(NOT_A_MOD_ARCHIVE), "{name}: {err}");
+            assert!(!dest.exists() && !temp_of(&dest).exists());
+        }
+        assert_eq!(fs::read(get("mod.jar").unwrap().path).unwrap(), body(1000));
+    }
+
+    #[test]
+    fn names_the_file_as_the_server_does_when_asked() {
+        let server = TestServer::start(|_, path, _| match path {
+            "/get" => TestResponse::new(200, body(1000)).header(
+                "Content-Disposition",
+                "attachment; filename=\"../Cool Mod-1.2.jar\"",
+            ),
+            "/jump" => {
+                TestResponse::new(302, Vec::new()).header("Location", "/files/Other-3.0.jar")
+            }
+            _ => TestResponse::new(200, body(1000)),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let fallback = dir.path().join("cool.jar");
+        let get = |name: &str, use_server_file_name: bool| {
+            let req = DownloadRequest {
+                use_server_file_name,
+                ..request(&format!("{}/{name}", server.base), &fallback, None)
+            };
+            download_to_file(
+                &req,
+                &HttpClient::with_retry(RetryPolicy::NONE),
+                &|| false,
+                |_| {},
+            )
+            .unwrap()
+        };
+
+        let saved = get("get", true);
+        assert_eq!(saved.path, dir.path().join("Cool Mod-1.2.jar"));
+        assert_eq!(saved.server_file_name.as_deref(), Some("Cool Mod-1.2.jar"));
+        // The name is taken now; the server's name is still reported without the suffix.
+        let again = get("get", true);
+        assert_eq!(again.path, dir.path().join("Cool Mod-1.2 (1).jar"));
+        assert_eq!(again.server_file_name, saved.server_file_name);
+        assert_eq!(get("jump", true).path, dir.path().join("Other-3.0.jar"));
+        // No usable name from the server: the requested one is kept.
+        let kept = get("api", true);
+        assert_eq!((kept.path, kept.server_file_name), (fallback.clone(), None));
+        let unnamed = get("get", false);
+        assert_eq!(unnamed.path, dir.path().join("cool (1).jar"));
+        assert_eq!(unnamed.server_file_name, None);
+    }
+
+    fn quick_retries(max_retries: u32) -> HttpClient {
+        HttpClient::with_retry(RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+        })
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let data = body(1000);
+        let ok = data.clone();
+        let server = TestServer::start(move |i, _, _| match i {
+            0 => TestResponse::new(503, Vec::new()),
+            1 => TestResponse::new(429, Vec::new()).header("Retry-After", "0"),
+            _ => TestResponse::new(200, ok.clone()),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+
+        let mut events = Vec::new();
+        let path = download_to_file(
+            &request(&server.url, &dest, Some("d")),
+            &quick_retries(3),
+            &|| false,
+            |p| events.push(p),
+        )
+        .unwrap()
+        .path;
+        assert_eq!(fs::read(path).unwrap(), data);
+        assert_eq!(server.request_count(), 3);
+        let retries: Vec<_> = events
+            .iter()
+            .filter(|p| p.retry_in_secs.is_some())
+            .collect();
+        assert_eq!(retries.len(), 2);
+        assert!(retries[0].last_error.as_deref().unwrap().contains("503"));
+        assert_eq!((retries[1].attempt, retries[1].max_attempts), (2, 4));
+        assert_eq!(events.last().unwrap().attempt, 3);
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts_and_fails_fast_on_4xx() {
+        let server = TestServer::start(|_, path, _| match path {
+            "/missing.jar" => TestResponse::new(404, Vec::new()),
+            _ => TestResponse::new(500, Vec::new()),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+
+        let err = download_to_file(
+            &request(&server.url, &dest, None),
+            &quick_retries(2),
+            &|| false,
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.contains("gave up after 3 attempts"), "{err}");
+        assert_eq!(server.request_count(), 3);
+
+        let missing = format!("{}/missing.jar", server.base);
+        let err = download_to_file(
+            &request(&missing, &dest, None),
+            &quick_retries(2),
+            &|| false,
+            |_| {},
+        )
+        .unwrap_err();
+        assert!(err.contains("404"), "{err}");
+        assert_eq!(server.request_count(), 4);
+    }
+
+    #[test]
+    fn retry_resumes_a_cut_off_transfer() {
+        let data = body(300_000);
+        let server = serve(&data, true, Some(100_000));
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+
+        let path = download_to_file(
+            &request(&server.url, &dest, None),
+            &quick_retries(1),
+            &|| false,
+            |_| {},
+        )
+        .unwrap()
+        .path;
+        assert_eq!(fs::read(path).unwrap(), data);
+        assert_eq!(server.header(1, "range").as_deref(), Some("bytes=100000-"));
+    }
+
+    #[test]
+    fn rejects_hosts_outside_the_allowlist_including_redirects() {
+        // The test server is trusted as 127.0.0.1 but not as localhost.
+        let target = Arc::new(OnceLock::<String>::new());
+        let location = target.clone();
+        let data = body(1000);
+        let server = TestServer::start(move |_, path, _| match path {
+            "/jump.jar" => {
+                TestResponse::new(302, Vec::new()).header("Location", location.get().unwrap())
+            }
+            _ => TestResponse::new(200, data.clone()),
+        });
+        let localhost = server.url.replace("127.0.0.1", "localhost");
+        target.set(localhost.clone()).unwrap();
+        let settings = crate::settings::Settings {
+            proxy_base_url: "http://proxy.invalid".to_string(),
+            trusted_download_hosts: vec!["127.0.0.1".to_string()],
+            ..Default::default()
+        };
+        let http = HttpClient::from_settings(&settings).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("mod.jar");
+        let get =
+            |url: &str| download_to_file(&request(url, &dest, None), &http, &|| false, |_| {});
+
+        let err = get(&localhost).unwrap_err();
+        assert!(err.starts_with(HOST_NOT_ALLOWED), "{err}");
+        assert_eq!(server.request_count(), 0);
+
+        let err = get(&format!("{}/jump.jar", server.base)).unwrap_err();
+        assert!(err.starts_with(HOST_NOT_ALLOWED), "{err}");
+        assert_eq!(server.request_count(), 1);
+        assert!(!dest.exists());
+
+        get(&server.url).unwrap();
+    }
+
+    #[test]
+    fn parses_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-999/1000"),
+            Some((100, Some(1000)))
+        );
+        assert_eq!(parse_content_range("bytes 5-9/*"), Some((5, None)));
+        assert_eq!(parse_content_range("items 1-2/3"), None);
+    }
+}
@@ -0,0 +1,599 @@
+//! Content-addressed cache of downloaded mod files, shared by every profile.
+//!
+//! Files are stored once under `download-cache/objects/<sha1>` in the app data dir. The index
+//! (`download-cache/index.json`) records each file's size, when it was last used and the
+//! provider keys it was downloaded for (`curseforge:<fileId>`, `orbis:<versionId>:<fileIndex>`).
+//! A download is served from the cache when its key or expected SHA-1 matches; every successful
+//! network download is added. Least recently used files are evicted above the size limit.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+
+use sha1::{Digest, Sha1};
+
+use crate::download::{self, DownloadProgress, DownloadRequest, ProgressTracker};
+use crate::http::HttpClient;
+use crate::{persist, recovery, schema};
+
+pub const CACHE_DIRNAME: &str = "download-cache";
+const OBJECTS_DIRNAME: &str = "objects";
+const INDEX_FILENAME: &str = "index.json";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    size: u64,
+    /// Unix time in milliseconds.
+    last_used: i64,
+    #[serde(default)]
+    keys: Vec<String>,
+    /// Name the server gave the file, for requests with `use_server_file_name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file_name: Option<String>,
+}
+
+/// Cached files by SHA-1.
+#[derive(serde::Serialize, serde::Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CacheIndex {
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+impl CacheIndex {
+    fn total_bytes(&self) -> u64 {
+        self.entries.values().map(|e| e.size).sum()
+    }
+
+    fn find_key(&self, key: &str) -> Option<String> {
+        self.entries
+            .iter()
+            .find(|(_, e)| e.keys.iter().any(|k| k == key))
+            .map(|(sha1, _)| sha1.clone())
+    }
+}
+
+/// Returned by `get_download_cache_usage` and `clear_download_cache`.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheUsage {
+    pub path: String,
+    pub files: usize,
+    pub total_bytes: u64,
+    pub limit_bytes: u64,
+}
+
+struct Inner {
+    dir: PathBuf,
+    /// 0 disables the cache.
+    limit: u64,
+    index: CacheIndex,
+}
+
+impl Inner {
+    fn object_path(&self, sha1: &str) -> PathBuf {
+        self.dir.join(OBJECTS_DIRNAME).join(sha1)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        schema::write(
+            &schema::DOWNLOAD_CACHE,
+            &self.dir.join(INDEX_FILENAME),
+            &self.index,
+        )
+    }
+
+    fn remove(&mut self, sha1: &str) {
+        self.index.entries.remove(sha1);
+        let _ = fs::remove_file(self.object_path(sha1));
+    }
+
+    /// Drop least recently used files until the total fits the limit.
+    fn evict(&mut self) {
+        while self.index.total_bytes() > self.limit {
+            let Some(oldest) = self
+                .index
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(sha1, _)| sha1.clone())
+            else {
+                break;
+            };
+            eprintln!("[cache] Evicting {oldest}");
+            self.remove(&oldest);
+        }
+    }
+}
+
+/// Kept in Tauri managed state; cheap to clone into download threads.
+#[derive(Clone)]
+pub struct DownloadCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+fn now_millis() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn sha1_file(path: &Path) -> io::Result<String> {
+    let mut f = fs::File::open(path)?;
+    let mut hasher = Sha1::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Index entries for the object files in `objects`, without keys.
+fn rebuild_index(objects: &Path) -> CacheIndex {
+    let mut index = CacheIndex::default();
+    let Ok(entries) = fs::read_dir(objects) else {
+        return index;
+    };
+    for e in entries.filter_map(|e| e.ok()) {
+        let Ok(meta) = e.metadata() else { continue };
+        let Some(name) = e.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if meta.is_file() && name.len() == 40 && name.bytes().all(|b| b.is_ascii_hexdigit()) {
+            let last_used = meta
+                .modified()
+                .ok()
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).timestamp_millis())
+                .unwrap_or(0);
+            index.entries.insert(
+                name,
+                CacheEntry {
+                    size: meta.len(),
+                    last_used,
+                    keys: Vec::new(),
+                    file_name: None,
+                },
+            );
+        }
+    }
+    index
+}
+
+impl DownloadCache {
+    /// Open the cache in `dir` (created if missing). Problems with the index are logged and the
+    /// cache starts over, since everything in it can be downloaded again.
+    pub fn open(dir: &Path, limit_bytes: u64) -> Self {
+        let objects = dir.join(OBJECTS_DIRNAME);
+        let index_path = dir.join(INDEX_FILENAME);
+        let _ = fs::create_dir_all(&objects);
+        let index = match recovery::read_or_recover(&schema::DOWNLOAD_CACHE, &index_path, || {
+            Some(rebuild_index(&objects))
+        }) {
+            Ok(res) => res.data.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("[cache] {e}; starting with an empty cache");
+                CacheIndex::default()
+            }
+        };
+        let mut inner = Inner {
+            dir: dir.to_path_buf(),
+            limit: limit_bytes,
+            index,
+        };
+
+        // Forget entries whose file is gone, adopt object files the index does not know (it may
+        // have been restored from an older backup) and delete anything else.
+        let missing: Vec<String> = inner
+            .index
+            .entries
+            .keys()
+            .filter(|sha1| !inner.object_path(sha1).is_file())
+            .cloned()
+            .collect();
+        for sha1 in &missing {
+            inner.index.entries.remove(sha1);
+        }
+        for (sha1, entry) in rebuild_index(&objects).entries {
+            inner.index.entries.entry(sha1).or_insert(entry);
+        }
+        if let Ok(entries) = fs::read_dir(&objects) {
+            for e in entries.filter_map(|e| e.ok()) {
+                let name = e.file_name().to_string_lossy().into_owned();
+                if !inner.index.entries.contains_key(&name) {
+                    let _ = fs::remove_file(e.path());
+                }
+            }
+        }
+        inner.evict();
+        if let Err(e) = inner.save() {
+            eprintln!("[cache] Failed to save index: {e}");
+        }
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Change the size limit, evicting right away if the cache is now too big. 0 disables it.
+    pub fn set_limit(&self, limit_bytes: u64) -> Result<(), String> {
+        let mut inner = self.lock();
+        if inner.limit == limit_bytes {
+            return Ok(());
+        }
+        inner.limit = limit_bytes;
+        inner.evict();
+        inner.save()
+    }
+
+    pub fn usage(&self) -> CacheUsage {
+        let inner = self.lock();
+        CacheUsage {
+            path: inner.dir.to_string_lossy().into_owned(),
+            files: inner.index.entries.len(),
+            total_bytes: inner.index.total_bytes(),
+            limit_bytes: inner.limit,
+        }
+    }
+
+    /// Delete every cached file.
+    pub fn clear(&self) -> Result<CacheUsage, String> {
+        {
+            let mut inner = self.lock();
+            let all: Vec<String> = inner.index.entries.keys().cloned().collect();
+            for sha1 in &all {
+                inner.remove(sha1);
+            }
+            inner.save()?;
+        }
+        Ok(self.usage())
+    }
+
+    /// Copy the cached file for `req` to its destination (or a free variant of it), if there is
+    /// one that passes the request's size and hash checks. The lock is only held to look the entry
+    /// up and to update it afterwards, so other downloads are not held up by the copy and hash.
+    fn restore(&self, req: &DownloadRequest) -> Result<Option<PathBuf>, String> {
+        let (sha1, object, file_name) = {
+            let inner = self.lock();
+            if inner.limit == 0 {
+                return Ok(None);
+            }
+            let by_key = req
+                .cache_key
+                .as_deref()
+                .and_then(|k| inner.index.find_key(k));
+            let by_hash = req
+                .expected_sha1
+                .as_deref()
+                .map(|h| h.trim().to_ascii_lowercase())
+                .filter(|h| inner.index.entries.contains_key(h));
+            let Some(sha1) = by_key.or(by_hash) else {
+                return Ok(None);
+            };
+            let file_name = inner
+                .index
+                .entries
+                .get(&sha1)
+                .and_then(|e| e.file_name.clone());
+            let object = inner.object_path(&sha1);
+            (sha1, object, file_name)
+        };
+
+        let mut dest = PathBuf::from(req.dest_path.trim());
+        if req.use_server_file_name {
+            if let Some(name) = file_name {
+                dest.set_file_name(name);
+            }
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        // Copy to the staging folder first so a crash never leaves a half file in Mods. The copy
+        // is what gets checked, so an object evicted or replaced meanwhile cannot slip through.
+        req.discard_partial();
+        let temp = req.temp_path();
+        fs::create_dir_all(req.partial_dir()).map_err(|e| e.to_string())?;
+        if let Err(e) = fs::copy(&object, &temp) {
+            req.discard_partial();
+            return Err(e.to_string());
+        }
+        if let Err(e) = download::verify(&temp, req) {
+            req.discard_partial();
+            eprintln!("[cache] Dropping {sha1}: {e}");
+            let mut inner = self.lock();
+            inner.remove(&sha1);
+            inner.save()?;
+            return Ok(None);
+        }
+        let final_path = crate::unique_file_path(&dest);
+        let moved = persist::move_file(&temp, &final_path);
+        req.discard_partial();
+        moved.map_err(|e| e.to_string())?;
+
+        let now = now_millis();
+        let mut inner = self.lock();
+        if let Some(entry) = inner.index.entries.get_mut(&sha1) {
+            entry.last_used = now;
+            if let Some(key) = req.cache_key.as_deref() {
+                if !entry.keys.iter().any(|k| k == key) {
+                    entry.keys.push(key.to_string());
+                }
+            }
+        }
+        inner.save()?;
+        Ok(Some(final_path))
+    }
+
+    /// Add a downloaded file under its SHA-1, tagged with `key`. `file_name` is remembered for
+    /// requests that want the server's name for the file.
+    pub fn insert(
+        &self,
+        path: &Path,
+        key: Option<&str>,
+        file_name: Option<&str>,
+    ) -> Result<(), String> {
+        self.insert_at(path, key, file_name, now_millis())
+    }
+
+    fn insert_at(
+        &self,
+        path: &Path,
+        key: Option<&str>,
+        file_name: Option<&str>,
+        now: i64,
+    ) -> Result<(), String> {
+        let mut inner = self.lock();
+        let size = fs::metadata(path).map_err(|e| e.to_string())?.len();
+        if inner.limit == 0 || size > inner.limit {
+            return Ok(());
+        }
+        let sha1 = sha1_file(path).map_err(|e| e.to_string())?;
+        if let Some(key) = key {
+            // A provider file id maps to one content; drop it from any older entry.
+            for entry in inner.index.entries.values_mut() {
+                entry.keys.retain(|k| k != key);
+            }
+        }
+        if !inner.index.entries.contains_key(&sha1) {
+            let object = inner.object_path(&sha1);
+            let mut temp = object.clone().into_os_string();
+            temp.push(".tmp");
+            let temp = PathBuf::from(temp);
+            fs::create_dir_all(object.parent().unwrap_or(&inner.dir)).map_err(|e| e.to_string())?;
+            let copied = fs::copy(path, &temp).and_then(|_| fs::rename(&temp, &object));
+            if let Err(e) = copied {
+                let _ = fs::remove_file(&temp);
+                return Err(e.to_string());
+            }
+            inner.index.entries.insert(
+                sha1.clone(),
+                CacheEntry {
+                    size,
+                    last_used: now,
+                    keys: Vec::new(),
+                    file_name: None,
+                },
+            );
+        }
+        if let Some(entry) = inner.index.entries.get_mut(&sha1) {
+            entry.last_used = now;
+            if let Some(key) = key {
+                entry.keys.push(key.to_string());
+            }
+            if let Some(name) = file_name {
+                entry.file_name = Some(name.to_string());
+            }
+        }
+        inner.evict();
+        inner.save()
+    }
+
+    /// Serve `req` from the cache if possible, otherwise download it and add the result.
+    pub fn fetch(
+        &self,
+        req: &DownloadRequest,
+        http: &HttpClient,
+        stop: &dyn Fn() -> bool,
+        mut emit: impl FnMut(DownloadProgress),
+    ) -> Result<PathBuf, String> {
+        match self.restore(req) {
+            Ok(Some(path)) => {
+                eprintln!("[cache] {} served from cache", req.url);
+                if let Some(id) = req.download_id.clone() {
+                    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                    let now = Instant::now();
+                    emit(
+                        ProgressTracker::new(id, Some(size), now)
+                            .starting_at(size)
+                            .finish(now),
+                    );
+                }
+                return Ok(path);
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("[cache] Lookup failed: {e}"),
+        }
+        let saved = download::download_to_file(req, http, stop, emit)?;
+        let file_name = saved.server_file_name.as_deref();
+        if let Err(e) = self.insert(&saved.path, req.cache_key.as_deref(), file_name) {
+            eprintln!("[cache] Failed to add {}: {e}", saved.path.display());
+        }
+        Ok(saved.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::RetryPolicy;
+    use crate::test_server::{body, ServeOptions, TestServer};
+
+    fn request(url: &str, dest: &Path, key: Option<&str>) -> DownloadRequest {
+        DownloadRequest {
+            url: url.to_string(),
+            dest_path: dest.to_string_lossy().into_owned(),
+            download_id: None,
+            expected_sha1: None,
+            expected_md5: None,
+            expected_size: None,
+            cache_key: key.map(str::to_string),
+            use_server_file_name: false,
+            staging_dir: None,
+        }
+    }
+
+    fn file(dir: &Path, name: &str, len: usize) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, body(len)).unwrap();
+        path
+    }
+
+    #[test]
+    fn second_download_is_served_from_cache() {
+        let data = body(10_000);
+        let server = TestServer::serving(data.clone(), ServeOptions::default());
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::open(&dir.path().join(CACHE_DIRNAME), 1 << 20);
+        let mods = dir.path().join("Mods");
+
+        let first = cache
+            .fetch(
+                &request(&server.url, &mods.join("a.jar"), Some("curseforge:1")),
+                &HttpClient::with_retry(RetryPolicy::NONE),
+                &|| false,
+                |_| {},
+            )
+            .unwrap();
+        let second = cache
+            .fetch(
+                &request(&server.url, &mods.join("b.jar"), Some("curseforge:1")),
+                &HttpClient::with_retry(RetryPolicy::NONE),
+                &|| false,
+                |_| {},
+            )
+            .unwrap();
+        assert_eq!(server.request_count(), 1);
+        assert_eq!(fs::read(&first).unwrap(), data);
+        assert_eq!(fs::read(&second).unwrap(), data);
+
+        // Found by content hash too, and the index survives a restart.
+        let cache = DownloadCache::open(&dir.path().join(CACHE_DIRNAME), 1 << 20);
+        let mut by_hash = request(&server.url, &mods.join("c.jar"), None);
+        by_hash.expected_sha1 = Some(sha1_file(&first).unwrap());
+        cache
+            .fetch(
+                &by_hash,
+                &HttpClient::with_retry(RetryPolicy::NONE),
+                &|| false,
+                |_| {},
+            )
+            .unwrap();
+        assert_eq!(server.request_count(), 1);
+        assert_eq!(cache.usage().files, 1);
+        assert_eq!(cache.usage().total_bytes, 10_000);
+
+        // A cached file that fails the request's checks is dropped and downloaded again.
+        let mut wrong = request(&server.url, &mods.join("d.jar"), Some("curseforge:1"));
+        wrong.expected_size = Some(1);
+        assert!(cache
+            .fetch(
+                &wrong,
+                &HttpClient::with_retry(RetryPolicy::NONE),
+                &|| false,
+                |_| {}
+            )
+            .is_err());
+        assert_eq!(server.request_count(), 2);
+        assert_eq!(cache.usage().files, 0);
+    }
+
+    #[test]
+    fn remembers_the_server_name_without_the_collision_suffix() {
+        let server = TestServer::serving(body(1000), ServeOptions::default());
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::open(&dir.path().join(CACHE_DIRNAME), 1 << 20);
+        let mods = dir.path().join("Mods");
+        fs::create_dir_all(&mods).unwrap();
+        fs::write(mods.join("mod.jar"), "another mod").unwrap();
+        let named = |dest: &Path| DownloadRequest {
+            use_server_file_name: true,
+            ..request(&server.url, dest, Some("curseforge:1"))
+        };
+        let fetch = |req: &DownloadRequest| {
+            cache
+                .fetch(
+                    req,
+                    &HttpClient::with_retry(RetryPolicy::NONE),
+                    &|| false,
+                    |_| {},
+                )
+                .unwrap()
+        };
+
+        assert_eq!(fetch(&named(&mods.join("x.jar"))), mods.join("mod (1).jar"));
+        let other = dir.path().join("Other");
+        assert_eq!(fetch(&named(&other.join("x.jar"))), other.join("mod.jar"));
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::open(&dir.path().join(CACHE_DIRNAME), 2500);
+        let a = file(dir.path(), "a.jar", 1000);
+        let b = file(dir.path(), "b.jar", 1010);
+        let c = file(dir.path(), "c.jar", 1020);
+        cache.insert_at(&a, Some("k:a"), None, 1).unwrap();
+        cache.insert_at(&b, Some("k:b"), None, 2).unwrap();
+        // Using `a` again makes `b` the oldest.
+        cache.insert_at(&a, None, None, 3).unwrap();
+        cache.insert_at(&c, Some("k:c"), None, 4).unwrap();
+
+        let inner = cache.lock();
+        assert_eq!(inner.index.entries.len(), 2);
+        assert!(inner.index.find_key("k:a").is_some());
+        assert!(inner.index.find_key("k:b").is_none());
+        assert!(inner.index.find_key("k:c").is_some());
+        drop(inner);
+
+        cache
+            .insert_at(&file(dir.path(), "big.jar", 3000), None, None, 5)
+            .unwrap();
+        assert_eq!(cache.usage().files, 2);
+        cache.set_limit(0).unwrap();
+        assert_eq!(cache.usage().files, 0);
+    }
+
+    #[test]
+    fn clear_and_recover_from_corrupt_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join(CACHE_DIRNAME);
+        let cache = DownloadCache::open(&cache_dir, 1 << 20);
+        cache
+            .insert(&file(dir.path(), "a.jar", 1000), Some("k:a"), None)
+            .unwrap();
+        fs::write(cache_dir.join(OBJECTS_DIRNAME).join("stray.tmp"), b"x").unwrap();
+        fs::write(cache_dir.join(INDEX_FILENAME), b"{\"schemaVersion\": 1, ").unwrap();
+
+        let cache = DownloadCache::open(&cache_dir, 1 << 20);
+        let usage = cache.usage();
+        assert_eq!((usage.files, usage.total_bytes), (1, 1000));
+        assert!(!cache_dir.join(OBJECTS_DIRNAME).join("stray.tmp").exists());
+
+        let usage = cache.clear().unwrap();
+        assert_eq!((usage.files, usage.total_bytes), (0, 0));
+        assert_eq!(
+            fs::read_dir(cache_dir.join(OBJECTS_DIRNAME))
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+}
@@ -0,0 +1,539 @@
+//! Batched downloads with a concurrency limit, pause and cancel.
+//!
+//! A batch is a list of [`DownloadRequest`]s run by a small pool of worker threads. Every item
+//! has a status the frontend can change while the batch runs: pausing stops the transfer after
+//! the current chunk and keeps the partial file for a later resume, cancelling stops it and
+//! deletes the partial. The batch call returns once every item is done, failed or cancelled.
+//!
+//! Worker threads only live while there is something to download. Once every unfinished item is
+//! paused they exit, and the batch waits for [`DownloadManager::resume`] (or `cancel`) on the
+//! receiver returned by [`DownloadManager::start_batch`] without holding a thread.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use tauri::async_runtime::{channel, Receiver, Sender};
+
+use crate::download::{DownloadProgress, DownloadRequest, DownloadedFile};
+use crate::download_cache::DownloadCache;
+use crate::http::HttpClient;
+
+/// Tauri event carrying an [`ItemUpdate`] payload.
+pub const STATUS_EVENT: &str = "download-status";
+
+pub const DEFAULT_CONCURRENCY: usize = 3;
+pub const MAX_CONCURRENCY: usize = 8;
+
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum ItemStatus {
+    Queued,
+    Running,
+    /// Stopped by the user; the partial file is kept.
+    Paused,
+    Done(DownloadedFile),
+    Failed {
+        error: String,
+    },
+    Cancelled,
+}
+
+impl ItemStatus {
+    fn is_final(&self) -> bool {
+        matches!(
+            self,
+            ItemStatus::Done(_) | ItemStatus::Failed { .. } | ItemStatus::Cancelled
+        )
+    }
+}
+
+/// Payload of the `download-status` event, and one entry of [`BatchSummary`].
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemUpdate {
+    pub batch_id: String,
+    pub item_id: String,
+    pub status: ItemStatus,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchSummary {
+    pub batch_id: String,
+    pub items: Vec<ItemUpdate>,
+}
+
+pub enum DownloadEvent {
+    Progress(DownloadProgress),
+    Status(ItemUpdate),
+}
+
+struct Item {
+    /// `download_id` of the request, or its index in the batch.
+    id: String,
+    request: DownloadRequest,
+    status: ItemStatus,
+}
+
+struct Batch {
+    id: String,
+    items: Mutex<Vec<Item>>,
+    /// Signalled whenever an item leaves `Running` or goes back to `Queued`.
+    changed: Condvar,
+    /// Worker threads per round.
+    workers: usize,
+    /// Wakes the caller of [`DownloadManager::start_batch`] when paused items are resumed or
+    /// cancelled.
+    wake: Sender<()>,
+}
+
+impl Batch {
+    fn lock(&self) -> MutexGuard<'_, Vec<Item>> {
+        self.items.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, item: &Item) -> ItemUpdate {
+        ItemUpdate {
+            batch_id: self.id.clone(),
+            item_id: item.id.clone(),
+            status: item.status.clone(),
+        }
+    }
+}
+
+/// Kept in Tauri managed state; cheap to clone into worker threads.
+#[derive(Clone, Default)]
+pub struct DownloadManager {
+    batches: Arc<Mutex<HashMap<String, Arc<Batch>>>>,
+}
+
+impl DownloadManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn batch(&self, batch_id: &str) -> Result<Arc<Batch>, String> {
+        self.batches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(batch_id)
+            .cloned()
+            .ok_or_else(|| format!("No running download batch \"{batch_id}\""))
+    }
+
+    /// Register a batch whose items are downloaded by [`Self::work_batch`], at most `concurrency`
+    /// at a time (default [`DEFAULT_CONCURRENCY`], capped at [`MAX_CONCURRENCY`]). Items without
+    /// a `download_id` get their index as id. The receiver gets a message whenever items are
+    /// resumed or cancelled; wait on it when `work_batch` returns `None`.
+    pub fn start_batch(
+        &self,
+        batch_id: &str,
+        requests: Vec<DownloadRequest>,
+        concurrency: Option<usize>,
+    ) -> Result<Receiver<()>, String> {
+        let items = requests
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut request)| {
+                let id = request
+                    .download_id
+                    .get_or_insert_with(|| i.to_string())
+                    .clone();
+                Item {
+                    id,
+                    request,
+                    status: ItemStatus::Queued,
+                }
+            })
+            .collect::<Vec<_>>();
+        let workers = concurrency
+            .unwrap_or(DEFAULT_CONCURRENCY)
+            .clamp(1, MAX_CONCURRENCY)
+            .min(items.len().max(1));
+        // One pending wake-up is enough; the next round sees every change.
+        let (wake, woken) = channel(1);
+        let batch = Arc::new(Batch {
+            id: batch_id.to_string(),
+            items: Mutex::new(items),
+            changed: Condvar::new(),
+            workers,
+            wake,
+        });
+        let mut batches = self.batches.lock().unwrap_or_else(|e| e.into_inner());
+        if batches.contains_key(batch_id) {
+            return Err(format!("Download batch \"{batch_id}\" is already running"));
+        }
+        batches.insert(batch_id.to_string(), batch);
+        Ok(woken)
+    }
+
+    /// Download the queued items of a started batch until nothing is queued or running. Returns
+    /// the summary once every item reached a final status, and `None` while paused items are
+    /// left; call it again after the next wake-up. Files already in `cache` are not downloaded
+    /// again.
+    pub fn work_batch(
+        &self,
+        batch_id: &str,
+        cache: &DownloadCache,
+        http: &HttpClient,
+        emit: impl Fn(DownloadEvent) + Send + Sync,
+    ) -> Result<Option<BatchSummary>, String> {
+        let batch = self.batch(batch_id)?;
+        thread::scope(|s| {
+            for _ in 0..batch.workers {
+                s.spawn(|| work(&batch, cache, http, &emit));
+            }
+        });
+
+        let items = batch.lock();
+        if !items.iter().all(|item| item.status.is_final()) {
+            return Ok(None);
+        }
+        self.batches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(batch_id);
+        Ok(Some(BatchSummary {
+            batch_id: batch_id.to_string(),
+            items: items.iter().map(|item| batch.update(item)).collect(),
+        }))
+    }
+
+    /// Pause one item, or every unfinished item if `item_id` is `None`.
+    pub fn pause(&self, batch_id: &str, item_id: Option<&str>) -> Result<Vec<ItemUpdate>, String> {
+        self.set_status(batch_id, item_id, |status| {
+            matches!(status, ItemStatus::Queued | ItemStatus::Running).then_some(ItemStatus::Paused)
+        })
+    }
+
+    /// Queue paused items again. Their partial files are resumed.
+    pub fn resume(&self, batch_id: &str, item_id: Option<&str>) -> Result<Vec<ItemUpdate>, String> {
+        self.set_status(batch_id, item_id, |status| {
+            (*status == ItemStatus::Paused).then_some(ItemStatus::Queued)
+        })
+    }
+
+    /// Cancel one item, or every unfinished item if `item_id` is `None`, and delete partial files.
+    pub fn cancel(&self, batch_id: &str, item_id: Option<&str>) -> Result<Vec<ItemUpdate>, String> {
+        self.set_status(batch_id, item_id, |status| {
+            (!status.is_final()).then_some(ItemStatus::Cancelled)
+        })
+    }
+
+    /// Apply `next` to the matching items and return the ones that changed. A running item
+    /// notices the change at its next chunk and is finished off by its worker.
+    fn set_status(
+        &self,
+        batch_id: &str,
+        item_id: Option<&str>,
+        next: impl Fn(&ItemStatus) -> Option<ItemStatus>,
+    ) -> Result<Vec<ItemUpdate>, String> {
+        let batch = self.batch(batch_id)?;
+        let mut items = batch.lock();
+        if let Some(id) = item_id {
+            if !items.iter().any(|item| item.id == id) {
+                return Err(format!("No download \"{id}\" in batch \"{batch_id}\""));
+            }
+        }
+        let mut changed = Vec::new();
+        for item in items.iter_mut() {
+            if item_id.is_some_and(|id| id != item.id) {
+                continue;
+            }
+            let Some(status) = next(&item.status) else {
+                continue;
+            };
+            let was_running = item.status == ItemStatus::Running;
+            item.status = status;
+            if item.status == ItemStatus::Cancelled && !was_running {
+                item.request.discard_partial();
+            }
+            changed.push(batch.update(item));
+        }
+        batch.changed.notify_all();
+        // Full means a wake-up is already pending.
+        let _ = batch.wake.try_send(());
+        Ok(changed)
+    }
+}
+
+/// Worker loop: take the next queued item until none is queued or running.
+fn work(
+    batch: &Batch,
+    cache: &DownloadCache,
+    http: &HttpClient,
+    emit: &(impl Fn(DownloadEvent) + Sync),
+) {
+    loop {
+        let (index, request) = {
+            let mut items = batch.lock();
+            loop {
+                if let Some(i) = items.iter().position(|i| i.status == ItemStatus::Queued) {
+                    items[i].status = ItemStatus::Running;
+                    emit(DownloadEvent::Status(batch.update(&items[i])));
+                    break (i, items[i].request.clone());
+                }
+                if !items.iter().any(|i| i.status == ItemStatus::Running) {
+                    // Everything left is final or paused; paused items wait without a thread.
+                    return;
+                }
+                // Another worker is busy; a resume may queue more while it runs.
+                items = batch.changed.wait(items).unwrap_or_else(|e| e.into_inner());
+            }
+        };
+
+        let stop = || batch.lock()[index].status != ItemStatus::Running;
+        let result = cache.fetch(&request, http, &stop, |p| emit(DownloadEvent::Progress(p)));
+
+        let mut items = batch.lock();
+        let item = &mut items[index];
+        match (result, &item.status) {
+            (Ok(path), _) => item.status = ItemStatus::Done(DownloadedFile::from(path.as_path())),
+            (Err(_), ItemStatus::Cancelled) => {
+                request.discard_partial();
+            }
+            (Err(_), ItemStatus::Paused) => {}
+            (Err(error), _) => {
+                eprintln!("[download] {} failed: {error}", request.url);
+                item.status = ItemStatus::Failed { error };
+            }
+        }
+        emit(DownloadEvent::Status(batch.update(item)));
+        batch.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::STAGING_DIRNAME;
+    use crate::retry::RetryPolicy;
+    use crate::test_server::{body, ServeOptions, TestServer};
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    fn requests(server: &TestServer, dir: &Path, n: usize) -> Vec<DownloadRequest> {
+        (0..n)
+            .map(|i| DownloadRequest {
+                url: format!("{}/mod{i}.jar", server.base),
+                dest_path: dir
+                    .join(format!("mod{i}.jar"))
+                    .to_string_lossy()
+                    .into_owned(),
+                download_id: Some(format!("m{i}")),
+                expected_sha1: None,
+                expected_md5: None,
+                expected_size: None,
+                cache_key: None,
+                use_server_file_name: false,
+                staging_dir: Some(dir.join(STAGING_DIRNAME)),
+            })
+            .collect()
+    }
+
+    /// Run a batch to the end the way the `run_download_batch` command does, waiting on the
+    /// wake-up receiver while every unfinished item is paused.
+    fn run_batch(
+        manager: &DownloadManager,
+        batch_id: &str,
+        reqs: Vec<DownloadRequest>,
+        concurrency: Option<usize>,
+        cache: &DownloadCache,
+        emit: impl Fn(DownloadEvent) + Send + Sync,
+    ) -> BatchSummary {
+        let http = HttpClient::with_retry(RetryPolicy::NONE);
+        let mut woken = manager.start_batch(batch_id, reqs, concurrency).unwrap();
+        loop {
+            if let Some(summary) = manager.work_batch(batch_id, cache, &http, &emit).unwrap() {
+                return summary;
+            }
+            woken.blocking_recv().unwrap();
+        }
+    }
+
+    /// A cache with a zero limit, which never serves or keeps anything.
+    fn no_cache() -> (tempfile::TempDir, DownloadCache) {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::open(dir.path(), 0);
+        (dir, cache)
+    }
+
+    /// Wait until `cond` holds for the batch, polling its status.
+    fn wait_for(manager: &DownloadManager, batch_id: &str, cond: impl Fn(&[Item]) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            if let Ok(batch) = manager.batch(batch_id) {
+                if cond(&batch.lock()) {
+                    return;
+                }
+            }
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn reports_success_and_failure_per_item() {
+        let data = body(50_000);
+        let server = TestServer::serving(data.clone(), ServeOptions::default());
+        let dir = tempfile::tempdir().unwrap();
+        let (_cache_dir, cache) = no_cache();
+        let mut reqs = requests(&server, dir.path(), 4);
+        reqs[2].url = "http://127.0.0.1:1/unreachable.jar".to_string();
+        reqs[3].download_id = None;
+
+        let events = Mutex::new(0);
+        let summary = run_batch(&DownloadManager::new(), "b", reqs, Some(2), &cache, |e| {
+            if matches!(e, DownloadEvent::Status(_)) {
+                *events.lock().unwrap() += 1;
+            }
+        });
+
+        assert_eq!(summary.items.len(), 4);
+        assert_eq!(summary.items[3].item_id, "3");
+        for (i, item) in summary.items.iter().enumerate() {
+            match &item.status {
+                ItemStatus::Done(file) if i != 2 => assert_eq!(fs::read(&file.path).unwrap(), data),
+                ItemStatus::Failed { .. } if i == 2 => {}
+                other => panic!("item {i}: {other:?}"),
+            }
+        }
+        // Running + final status for every item.
+        assert_eq!(*events.lock().unwrap(), 8);
+    }
+
+    #[test]
+    fn pause_resume_and_cancel() {
+        let data = body(400_000);
+        let server = TestServer::serving(
+            data.clone(),
+            ServeOptions {
+                ranges: true,
+                throttle: Some(Duration::from_millis(10)),
+                ..ServeOptions::default()
+            },
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let (_cache_dir, cache) = no_cache();
+        let reqs = requests(&server, dir.path(), 3);
+        let dest = |i: usize| dir.path().join(format!("mod{i}.jar"));
+        let temps: Vec<_> = reqs.iter().map(DownloadRequest::temp_path).collect();
+        let manager = DownloadManager::new();
+
+        let summary = thread::scope(|s| {
+            let run = s.spawn(|| run_batch(&manager, "b", reqs, Some(1), &cache, |_| {}));
+
+            wait_for(&manager, "b", |items| {
+                items[0].status == ItemStatus::Running
+                    && fs::metadata(&temps[0]).is_ok_and(|m| m.len() > 0)
+            });
+            manager.pause("b", Some("m0")).unwrap();
+            manager.cancel("b", Some("m1")).unwrap();
+            // The worker moves on to m2 once m0 has stopped.
+            wait_for(&manager, "b", |items| {
+                items[2].status == ItemStatus::Running
+            });
+            assert!(temps[0].exists());
+            assert!(manager.resume("b", Some("missing")).is_err());
+            manager.resume("b", None).unwrap();
+            run.join().unwrap()
+        });
+
+        assert!(matches!(summary.items[0].status, ItemStatus::Done(_)));
+        assert_eq!(summary.items[1].status, ItemStatus::Cancelled);
+        assert!(matches!(summary.items[2].status, ItemStatus::Done(_)));
+        assert_eq!(fs::read(dest(0)).unwrap(), data);
+        assert!(!dest(1).exists());
+        // Nothing is left in the staging folder.
+        assert!(temps.iter().all(|t| !t.parent().unwrap().exists()));
+        // m0 was resumed with a range request rather than downloaded again.
+        assert!((0..server.request_count()).any(|i| server.header(i, "range").is_some()));
+    }
+
+    #[test]
+    fn cancelling_a_running_batch_removes_partials() {
+        let server = TestServer::serving(
+            body(1_000_000),
+            ServeOptions {
+                ranges: true,
+                throttle: Some(Duration::from_millis(10)),
+                ..ServeOptions::default()
+            },
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let (_cache_dir, cache) = no_cache();
+        let reqs = requests(&server, dir.path(), 2);
+        let manager = DownloadManager::new();
+
+        let summary = thread::scope(|s| {
+            let run = s.spawn(|| run_batch(&manager, "b", reqs, Some(2), &cache, |_| {}));
+            wait_for(&manager, "b", |items| {
+                items.iter().all(|i| i.status == ItemStatus::Running)
+            });
+            manager.cancel("b", None).unwrap();
+            run.join().unwrap()
+        });
+
+        assert!(summary
+            .items
+            .iter()
+            .all(|i| i.status == ItemStatus::Cancelled));
+        // Only the emptied staging folder is left.
+        let left: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(left, [STAGING_DIRNAME]);
+        assert_eq!(
+            fs::read_dir(dir.path().join(STAGING_DIRNAME))
+                .unwrap()
+                .count(),
+            0
+        );
+        assert!(manager.cancel("b", None).is_err());
+    }
+
+    #[test]
+    fn a_paused_batch_holds_no_workers() {
+        let server = TestServer::serving(
+            body(400_000),
+            ServeOptions {
+                ranges: true,
+                throttle: Some(Duration::from_millis(10)),
+                ..ServeOptions::default()
+            },
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let (_cache_dir, cache) = no_cache();
+        let http = HttpClient::with_retry(RetryPolicy::NONE);
+        let manager = DownloadManager::new();
+        let mut woken = manager
+            .start_batch("b", requests(&server, dir.path(), 2), Some(2))
+            .unwrap();
+
+        let round = thread::scope(|s| {
+            let run = s.spawn(|| manager.work_batch("b", &cache, &http, |_| {}).unwrap());
+            wait_for(&manager, "b", |items| {
+                items.iter().all(|i| i.status == ItemStatus::Running)
+            });
+            manager.pause("b", None).unwrap();
+            run.join().unwrap()
+        });
+        // The workers returned although nothing is finished.
+        assert!(round.is_none());
+        woken.try_recv().unwrap();
+
+        manager.resume("b", Some("m0")).unwrap();
+        manager.cancel("b", Some("m1")).unwrap();
+        woken.try_recv().unwrap();
+        let summary = manager
+            .work_batch("b", &cache, &http, |_| {})
+            .unwrap()
+            .unwrap();
+        assert!(matches!(summary.items[0].status, ItemStatus::Done(_)));
+        assert_eq!(summary.items[1].status, ItemStatus::Cancelled);
+        assert!(manager.work_batch("b", &cache, &http, |_| {}).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! File names of mods: [`sanitize`] cleans every name that comes from the webview or a provider
+//! before a file is written under it, and [`from_response`] reads the name a server gave a file.
+//!
+//! Callers often only know a placeholder such as `<slug>.jar`, while the CDN names the file after
+//! the mod and its version. The name comes from `Content-Disposition` or else the last segment of
+//! the final (redirected) URL, and is cleaned so it is a single, portable path component.
+
+use reqwest::Url;
+
+/// Longest file name most file systems allow, in bytes.
+const FS_MAX_LEN: usize = 255;
+/// Room kept for what is added to a mod's name later: the `.ht-update-<id>-` prefix of an
+/// update, ` (12)` for a name that is taken, and `.bak` or `.tmp.meta`.
+const SUFFIX_ROOM: usize = 40;
+/// Longest name kept, in bytes; longer names are shortened before the extension.
+const MAX_LEN: usize = FS_MAX_LEN - SUFFIX_ROOM;
+/// Extensions a downloaded mod may have (everything else is checked to be a ZIP anyway).
+const MOD_EXTENSIONS: [&str; 2] = ["jar", "zip"];
+/// Names Windows reserves for devices, with or without an extension.
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Make `name` safe to use as one path component on every platform: anything before the last
+/// `/` or `\` is dropped, characters Windows forbids and control characters become `_`,
+/// leading and trailing dots and spaces are trimmed, device names get a `_` prefix and the
+/// length is capped. `None` if nothing usable is left.
+pub fn sanitize(name: &str) -> Option<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let mut name = cleaned
+        .trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string();
+    if name.is_empty() {
+        return None;
+    }
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(stem.trim_end()))
+    {
+        name.insert(0, '_');
+    }
+    if name.len() > MAX_LEN {
+        let ext = name
+            .rfind('.')
+            .map(|i| name[i..].to_string())
+            .filter(|e| e.len() < 16)
+            .unwrap_or_default();
+        let mut end = MAX_LEN - ext.len();
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name = format!("{}{ext}", name[..end].trim_end());
+    }
+    Some(name)
+}
+
+/// Sanitized name with a mod extension, or `None`.
+fn mod_file_name(raw: &str) -> Option<String> {
+    let name = sanitize(raw)?;
+    let (_, ext) = name.rsplit_once('.')?;
+    MOD_EXTENSIONS
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(ext))
+        .then_some(name)
+}
+
+/// Name of the file the server sent: `Content-Disposition` first, else the final URL. Only names
+/// ending in `.jar` or `.zip` are used.
+pub fn from_response(content_disposition: Option<&str>, url: &Url) -> Option<String> {
+    content_disposition
+        .and_then(from_content_disposition)
+        .and_then(|n| mod_file_name(&n))
+        .or_else(|| from_url(url).and_then(|n| mod_file_name(&n)))
+}
+
+/// `filename*` (RFC 6266 / 5987, UTF-8 or Latin-1) is preferred over the plain `filename`.
+fn from_content_disposition(value: &str) -> Option<String> {
+    let mut plain = None;
+    for (key, val) in params(value) {
+        match key.to_ascii_lowercase().as_str() {
+            "filename*" => {
+                if let Some(name) = decode_ext_value(&val) {
+                    return Some(name);
+                }
+            }
+            "filename" if plain.is_none() => plain = Some(val),
+            _ => {}
+        }
+    }
+    plain.filter(|n| !n.is_empty())
+}
+
+/// `attachment; filename="a;b.jar"; x=y` -> `[("filename", "a;b.jar"), ("x", "y")]`.
+fn params(value: &str) -> Vec<(String, String)> {
+    let mut out = Vec::new();
+    let mut chars = value.chars().peekable();
+    // Skip the disposition type.
+    for c in chars.by_ref() {
+        if c == ';' {
+            break;
+        }
+    }
+    loop {
+        let key: String = chars
+            .by_ref()
+            .take_while(|&c| c != '=')
+            .collect::<String>()
+            .trim()
+            .to_string();
+        if key.is_empty() {
+            return out;
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut val = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => val.extend(chars.next()),
+                    '"' => break,
+                    c => val.push(c),
+                }
+            }
+            for c in chars.by_ref() {
+                if c == ';' {
+                    break;
+                }
+            }
+        } else {
+            val = chars.by_ref().take_while(|&c| c != ';').collect();
+            val = val.trim().to_string();
+        }
+        out.push((key, val));
+    }
+}
+
+/// `UTF-8''na%C3%AFve.jar` -> `naïve.jar`.
+fn decode_ext_value(value: &str) -> Option<String> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let bytes = percent_decode(parts.next()?);
+    if charset.eq_ignore_ascii_case("utf-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+fn from_url(url: &Url) -> Option<String> {
+    let segment = url.path_segments()?.next_back()?;
+    String::from_utf8(percent_decode(segment)).ok()
+}
+
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(
+            sanitize("Cool Mod 1.2.jar").as_deref(),
+            Some("Cool Mod 1.2.jar")
+        );
+        assert_eq!(sanitize("../../evil.jar").as_deref(), Some("evil.jar"));
+        assert_eq!(sanitize("C:\\Windows\\x.jar").as_deref(), Some("x.jar"));
+        assert_eq!(sanitize("a<b>:c|d?.jar").as_deref(), Some("a_b__c_d_.jar"));
+        assert_eq!(
+            sanitize("line\nbreak.jar").as_deref(),
+            Some("line_break.jar")
+        );
+        assert_eq!(sanitize(" .hidden.jar. ").as_deref(), Some("hidden.jar"));
+        assert_eq!(sanitize("con.jar").as_deref(), Some("_con.jar"));
+        assert_eq!(sanitize("console.jar").as_deref(), Some("console.jar"));
+        assert_eq!(sanitize(".."), None);
+        assert_eq!(sanitize("dir/"), None);
+
+        let long = format!("{}.jar", "é".repeat(200));
+        let short = sanitize(&long).unwrap();
+        assert!(
+            short.len() <= MAX_LEN && short.ends_with("é.jar"),
+            "{short}"
+        );
+        for decorated in [
+            format!(".ht-update-123456-{short} (999).tmp.meta"),
+            format!("{short} (999).bak (999).bak"),
+        ] {
+            assert!(decorated.len() <= FS_MAX_LEN, "{decorated}");
+        }
+    }
+
+    #[test]
+    fn reads_content_disposition_and_url() {
+        let url = Url::parse("https://edge.forgecdn.net/files/1/2/My%20Mod-1.0.jar?x=1").unwrap();
+        let cd = |v: &str| from_response(Some(v), &url);
+        assert_eq!(
+            cd("attachment; filename=\"server; name.jar\"").as_deref(),
+            Some("server; name.jar")
+        );
+        assert_eq!(
+            cd("attachment; filename=plain.jar; filename*=UTF-8''na%C3%AFve%201.jar").as_deref(),
+            Some("naïve 1.jar")
+        );
+        assert_eq!(
+            cd("attachment; filename*=iso-8859-1'en'caf%E9.zip").as_deref(),
+            Some("café.zip")
+        );
+        assert_eq!(
+            cd("attachment; filename=\"..\\\\..\\\\up.jar\"").as_deref(),
+            Some("up.jar")
+        );
+        // Not a mod name, or no name at all: the URL is used.
+        assert_eq!(
+            cd("attachment; filename=page.html").as_deref(),
+            Some("My Mod-1.0.jar")
+        );
+        assert_eq!(cd("inline").as_deref(), Some("My Mod-1.0.jar"));
+        assert_eq!(from_response(None, &url).as_deref(), Some("My Mod-1.0.jar"));
+
+        let api = Url::parse("http://localhost:8787/v1/curseforge/download?file=1").unwrap();
+        assert_eq!(from_response(None, &api), None);
+    }
+}
@@ -0,0 +1,175 @@
+//! The HTTP client shared by all downloads, built from the settings.
+//!
+//! It lives in managed state and is rebuilt whenever the settings are saved, so every download
+//! uses the same connection pool, timeouts, proxy and trusted certificates.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use reqwest::{Certificate, Proxy};
+
+use crate::allowlist::HostAllowlist;
+use crate::retry::RetryPolicy;
+use crate::settings::Settings;
+
+pub const USER_AGENT: &str = concat!("HygherTales/", env!("CARGO_PKG_VERSION"));
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 15;
+/// Longest a response may stall before the download fails (and is retried).
+pub const DEFAULT_READ_TIMEOUT_SECS: u64 = 60;
+const MAX_REDIRECTS: usize = 10;
+const PROXY_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+/// Client plus the retry policy and host allowlist downloads made with it follow.
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: Client,
+    pub retry: RetryPolicy,
+    pub allowlist: HostAllowlist,
+}
+
+impl HttpClient {
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let secs = |s: u64| (s > 0).then(|| Duration::from_secs(s));
+        let allowlist = HostAllowlist::from_settings(settings);
+        let mut builder = Client::builder()
+            .user_agent(USER_AGENT)
+            .redirect(redirect_policy(allowlist.clone()))
+            .connect_timeout(secs(settings.http_connect_timeout_secs))
+            // The blocking client applies this to sending the request and to every body read.
+            .timeout(secs(settings.http_read_timeout_secs));
+        if let Some(ref url) = settings.http_proxy_url {
+            builder = builder.proxy(proxy(url)?);
+        }
+        for path in &settings.extra_ca_certs {
+            for cert in load_certificates(Path::new(path))? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Ok(Self {
+            client: builder.build().map_err(|e| e.to_string())?,
+            retry: settings.retry_policy(),
+            allowlist,
+        })
+    }
+
+    /// Default settings with `retry` that trust the local test server, for tests.
+    #[cfg(test)]
+    pub fn with_retry(retry: RetryPolicy) -> Self {
+        let settings = Settings {
+            trusted_download_hosts: vec!["127.0.0.1".to_string()],
+            ..Settings::default()
+        };
+        Self {
+            retry,
+            ..Self::from_settings(&settings).unwrap()
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+/// Follow up to [`MAX_REDIRECTS`] redirects, each to an allowed host.
+fn redirect_policy(allowlist: HostAllowlist) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        match allowlist.check(attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(e) => attempt.error(e),
+        }
+    })
+}
+
+/// Proxy for all requests. `socks5h` resolves host names through the proxy.
+pub fn proxy(url: &str) -> Result<Proxy, String> {
+    let parsed =
+        reqwest::Url::parse(url).map_err(|e| format!("HTTP proxy \"{url}\" is invalid: {e}"))?;
+    if !PROXY_SCHEMES.contains(&parsed.scheme()) || !parsed.has_host() {
+        return Err(format!(
+            "HTTP proxy \"{url}\" must be an http, https, socks5 or socks5h URL"
+        ));
+    }
+    Proxy::all(url).map_err(|e| format!("HTTP proxy \"{url}\" is invalid: {e}"))
+}
+
+/// Certificates from a PEM file (one or more) or a single DER certificate.
+pub fn load_certificates(path: &Path) -> Result<Vec<Certificate>, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("Cannot read certificate {}: {e}", path.display()))?;
+    let certs = if bytes.windows(10).any(|w| w == b"-----BEGIN") {
+        Certificate::from_pem_bundle(&bytes).map_err(|e| e.to_string())
+    } else {
+        Certificate::from_der(&bytes)
+            .map(|c| vec![c])
+            .map_err(|e| e.to_string())
+    }
+    .map_err(|e| format!("Invalid certificate {}: {e}", path.display()))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{TestResponse, TestServer};
+
+    #[test]
+    fn sends_user_agent() {
+        let server = TestServer::start(|_, _, _| TestResponse::new(200, Vec::new()));
+        let http = HttpClient::from_settings(&Settings::default()).unwrap();
+        http.client().get(&server.url).send().unwrap();
+        assert_eq!(server.header(0, "user-agent").as_deref(), Some(USER_AGENT));
+    }
+
+    #[test]
+    fn rejects_bad_proxies_and_certificates() {
+        assert!(proxy("socks5h://127.0.0.1:1080").is_ok());
+        assert!(proxy("http://proxy.example:3128").is_ok());
+        assert!(proxy("ftp://proxy.example").is_err());
+        assert!(proxy("not a url").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let bad = dir.path().join("bad.pem");
+        fs::write(
+            &bad,
+            "-----BEGIN CERTIFICATE-----\nnope\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        assert!(load_certificates(&bad).is_err());
+        assert!(load_certificates(&dir.path().join("missing.pem")).is_err());
+
+        let settings = Settings {
+            extra_ca_certs: vec![bad.to_string_lossy().into_owned()],
+            ..Settings::default()
+        };
+        assert!(HttpClient::from_settings(&settings).is_err());
+    }
+
+    #[test]
+    fn read_timeout_fails_a_stalled_response() {
+        let server = TestServer::start(|_, _, _| {
+            let mut resp = TestResponse::new(200, vec![0; 64 * 1024]);
+            resp.throttle = Some(Duration::from_secs(3));
+            resp
+        });
+        let settings = Settings {
+            http_read_timeout_secs: 1,
+            ..Settings::default()
+        };
+        let http = HttpClient::from_settings(&settings).unwrap();
+        let err = http
+            .client()
+            .get(&server.url)
+            .send()
+            .and_then(|r| r.bytes())
+            .unwrap_err();
+        assert!(err.is_timeout(), "{err}");
+    }
+}
@@ -0,0 +1,66 @@
+//! Process-level lock on the app data dir.
+//!
+//! The single-instance plugin normally stops a second window before `setup` runs and forwards
+//! its arguments to the first one. This lock is the backstop for launches the plugin cannot see
+//! (another session, a different install of the app): whoever holds it owns the data files and
+//! the proxy sidecar.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use fs4::fs_std::FileExt;
+
+pub const LOCK_FILENAME: &str = "hyghertales.lock";
+
+/// Held for the lifetime of the app. The OS releases the lock when the process exits,
+/// so a crash never leaves a stale lock behind.
+pub struct AppDataLock {
+    _file: File,
+}
+
+/// Take the exclusive lock in `dir`. Fails if another process already holds it.
+pub fn acquire(dir: &Path) -> Result<AppDataLock, String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let path = dir.join(LOCK_FILENAME);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    if !file.try_lock_exclusive().map_err(|e| e.to_string())? {
+        let mut owner = String::new();
+        let _ = file.read_to_string(&mut owner);
+        let owner = owner.trim();
+        return Err(if owner.is_empty() {
+            format!("{} is locked by another HygherTales instance", dir.display())
+        } else {
+            format!(
+                "{} is locked by another HygherTales instance (pid {owner})",
+                dir.display()
+            )
+        });
+    }
+    // Record our pid for the error message above; purely informational.
+    file.set_len(0).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+    write!(file, "{}", std::process::id()).map_err(|e| e.to_string())?;
+    Ok(AppDataLock { _file: file })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_acquire_fails_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = acquire(dir.path()).unwrap();
+        let err = acquire(dir.path()).err().unwrap();
+        assert!(err.contains(&format!("pid {}", std::process::id())), "{err}");
+        drop(lock);
+        assert!(acquire(dir.path()).is_ok());
+    }
+}
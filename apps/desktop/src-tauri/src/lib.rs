@@ -1,13 +1,47 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::io::Write;
+mod allowlist;
+mod archive;
+mod download;
+mod download_cache;
+mod download_manager;
+mod file_name;
+mod http;
+mod instance;
+mod mod_backups;
+mod mod_paths;
+mod persist;
+mod profile_switch;
+mod profiles;
+mod recovery;
+mod retry;
+mod sandbox;
+mod schema;
+mod settings;
+mod store;
+mod transaction;
+#[cfg(test)]
+mod test_server;
+
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard};
 use std::{env, fs};
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 use tauri_plugin_shell::ShellExt;
 
+use download_cache::{CacheUsage, DownloadCache};
+use download_manager::{DownloadEvent, DownloadManager};
+use recovery::LoadResult;
+use sandbox::{PickedPaths, Sandbox};
+use http::HttpClient;
+use mod_backups::ModBackup;
+use mod_paths::ModRoots;
+use profile_switch::ApplyProfileSummary;
+use settings::Settings;
+use store::Store;
+use transaction::{FileOp, Plan, RecordChange};
+
 #[derive(serde::Serialize)]
 struct EnsureModsDirResult {
     ok: bool,
@@ -26,8 +60,15 @@ struct LaunchGameResult {
     ok: bool,
 }
 
+/// File type filter of a file dialog, e.g. `{ name: "JSON", extensions: ["json"] }`.
+#[derive(serde::Deserialize)]
+struct FileFilter {
+    name: String,
+    extensions: Vec<String>,
+}
+
 /// Installed mod record (matches TS InstalledMod schema).
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct InstalledModRecord {
     pub id: Option<i64>,
@@ -43,6 +84,102 @@ pub struct InstalledModRecord {
     pub enabled: bool,
     #[serde(default)]
     pub pinned: bool,
+    /// Hash the installed file was verified against at download time, as `sha1:<hex>` or
+    /// `md5:<hex>`.
+    #[serde(default)]
+    pub file_hash: Option<String>,
+}
+
+#[cfg(test)]
+impl InstalledModRecord {
+    /// A CurseForge record for tests, with project id `100 + id`.
+    pub(crate) fn for_test(id: i64, filename: &str, enabled: bool) -> Self {
+        InstalledModRecord {
+            id: Some(id),
+            provider: "curseforge".to_string(),
+            project_id: Some(100 + id),
+            resource_id: None,
+            slug: format!("mod-{id}"),
+            name: format!("Mod {id}"),
+            installed_file_id: None,
+            installed_filename: filename.to_string(),
+            installed_at: "2024-01-01T00:00:00Z".to_string(),
+            source_url: None,
+            enabled,
+            pinned: false,
+            file_hash: None,
+        }
+    }
+}
+
+/// Partial update for an installed mod record; fields left out are unchanged. `null` clears the
+/// optional fields (`projectId`, `resourceId`, `installedFileId`, `sourceUrl`, `fileHash`).
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledModPatch {
+    #[serde(default, deserialize_with = "double_option")]
+    pub project_id: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub resource_id: Option<Option<String>>,
+    pub slug: Option<String>,
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub installed_file_id: Option<Option<serde_json::Value>>,
+    pub installed_filename: Option<String>,
+    pub installed_at: Option<String>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub source_url: Option<Option<String>>,
+    pub enabled: Option<bool>,
+    pub pinned: Option<bool>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub file_hash: Option<Option<String>>,
+}
+
+/// Tells a `null` field (`Some(None)`) apart from a missing one (`None`, via `serde(default)`).
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+impl InstalledModPatch {
+    pub fn apply(self, m: &mut InstalledModRecord) {
+        if let Some(v) = self.project_id {
+            m.project_id = v;
+        }
+        if let Some(v) = self.resource_id {
+            m.resource_id = v;
+        }
+        if let Some(v) = self.slug {
+            m.slug = v;
+        }
+        if let Some(v) = self.name {
+            m.name = v;
+        }
+        if let Some(v) = self.installed_file_id {
+            m.installed_file_id = v;
+        }
+        if let Some(v) = self.installed_filename {
+            m.installed_filename = v;
+        }
+        if let Some(v) = self.installed_at {
+            m.installed_at = v;
+        }
+        if let Some(v) = self.source_url {
+            m.source_url = v;
+        }
+        if let Some(v) = self.enabled {
+            m.enabled = v;
+        }
+        if let Some(v) = self.pinned {
+            m.pinned = v;
+        }
+        if let Some(v) = self.file_hash {
+            m.file_hash = v;
+        }
+    }
 }
 
 const INSTALLED_MODS_FILENAME: &str = "installed_mods.json";
@@ -78,63 +215,333 @@ pub struct ProfilesData {
     pub profiles: Vec<ProfileRecord>,
 }
 
-fn app_installed_mods_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+fn app_data_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
     let dir = app
         .path()
         .app_data_dir()
         .map_err(|e| format!("{e}"))?;
     fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
-    Ok(dir.join(INSTALLED_MODS_FILENAME))
+    Ok(dir)
+}
+
+fn app_installed_mods_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(app_data_dir(app)?.join(INSTALLED_MODS_FILENAME))
 }
 
 fn app_profiles_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
-    let dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("{e}"))?;
-    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
-    Ok(dir.join(PROFILES_FILENAME))
+    Ok(app_data_dir(app)?.join(PROFILES_FILENAME))
+}
+
+fn app_settings_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(app_data_dir(app)?.join(settings::SETTINGS_FILENAME))
+}
+
+fn app_journal_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(app_data_dir(app)?.join(transaction::JOURNAL_FILENAME))
+}
+
+type StoreState<'a> = tauri::State<'a, Mutex<Store>>;
+
+/// The shared download client, rebuilt from the settings whenever they are loaded or saved.
+fn lock_http_client(app: &tauri::AppHandle) -> MutexGuard<'_, HttpClient> {
+    app.state::<Mutex<HttpClient>>()
+        .inner()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+fn http_client(app: &tauri::AppHandle) -> HttpClient {
+    lock_http_client(app).clone()
 }
 
+/// Folders the mod file commands may write to, from the Mods folder in the saved settings.
+fn mod_roots(app: &tauri::AppHandle) -> Result<ModRoots, String> {
+    ModRoots::from_settings(&settings::current(&app_settings_path(app)?))
+}
+
+/// Where `read_text_file`, `write_text_file` and `move_file_to_trash` may go: the `files` folder
+/// under app data, the mod folders (if configured) and paths picked in a backend file dialog
+/// this session.
+fn sandbox(app: &tauri::AppHandle) -> Result<Sandbox, String> {
+    let mut roots = vec![app_data_dir(app)?.join(sandbox::FILES_DIRNAME)];
+    if let Ok(mods) = mod_roots(app) {
+        roots.extend([mods.mods, mods.disabled, mods.backup]);
+    }
+    Ok(Sandbox::new(roots, app.state::<PickedPaths>().list()))
+}
+
+/// Point `request` at a cleaned file name, make sure it stays inside the mod folders, and stage
+/// it under app data.
+fn prepare_download(
+    app: &tauri::AppHandle,
+    roots: &ModRoots,
+    request: &mut download::DownloadRequest,
+) -> Result<(), String> {
+    let dest = roots.check_file(Path::new(request.dest_path.trim()))?;
+    request.dest_path = dest.to_string_lossy().into_owned();
+    request.staging_dir = Some(app_data_dir(app)?.join(download::STAGING_DIRNAME));
+    Ok(())
+}
+
+/// Locks the store, importing the legacy installed_mods.json first if that has not happened yet.
+/// If the file is corrupt, the records are rebuilt from `mods_dir`, or else from the Mods folder
+/// in the settings, whichever command happens to run the import.
+fn installed_mods_store<'a>(
+    app: &tauri::AppHandle,
+    store: &'a Mutex<Store>,
+    mods_dir: Option<&Path>,
+) -> Result<MutexGuard<'a, Store>, String> {
+    let mut guard = store.lock().map_err(|e| e.to_string())?;
+    if !guard.installed_mods_imported()? {
+        let configured = mod_roots(app).ok().map(|r| r.mods);
+        let mods_dir = mods_dir.or(configured.as_deref());
+        guard.import_installed_mods_json(&app_installed_mods_path(app)?, mods_dir)?;
+    }
+    Ok(guard)
+}
+
+/// Locks the store, importing the legacy profiles.json (and installed_mods.json, which profile
+/// validation depends on) first if that has not happened yet.
+fn profiles_store<'a>(
+    app: &tauri::AppHandle,
+    store: &'a Mutex<Store>,
+) -> Result<MutexGuard<'a, Store>, String> {
+    let mut guard = installed_mods_store(app, store, None)?;
+    guard.import_profiles_json(&app_profiles_path(app)?)?;
+    Ok(guard)
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadProfilesResult {
+    #[serde(flatten)]
+    load: LoadResult<ProfilesData>,
+    validation: profiles::ProfilesValidation,
+}
+
+/// Reads profiles, repairing inconsistencies (ids, active profile, mods that are no longer
+/// installed) and saving the repaired data. `recovered` is set once if the legacy file was
+/// corrupt when imported.
 #[tauri::command]
-fn read_profiles(app: tauri::AppHandle) -> Result<ProfilesData, String> {
-    let path = app_profiles_path(&app)?;
-    if !path.exists() {
-        return Ok(ProfilesData {
-            next_id: 1,
-            active_profile_id: None,
-            profiles: Vec::new(),
-        });
+fn read_profiles(app: tauri::AppHandle, store: StoreState) -> Result<ReadProfilesResult, String> {
+    let mut store = profiles_store(&app, &store)?;
+    let mut data = store.read_profiles()?;
+    let installed = store.installed_mod_ids()?;
+    let validation = profiles::repair(&mut data, Some(&installed));
+    if !validation.is_clean() {
+        store.replace_profiles(&data)?;
     }
-    let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    let parsed: ProfilesData = serde_json::from_str(&data).map_err(|e| e.to_string())?;
-    Ok(parsed)
+    Ok(ReadProfilesResult {
+        load: LoadResult {
+            data,
+            recovered: store.take_recovery(schema::PROFILES.name),
+        },
+        validation,
+    })
 }
 
+/// Replaces all profiles. Duplicate ids or an unknown active profile are rejected; a stale
+/// `nextId` is fixed and mods that are no longer installed are dropped (and reported).
 #[tauri::command]
-fn write_profiles(app: tauri::AppHandle, data: ProfilesData) -> Result<(), String> {
-    let path = app_profiles_path(&app)?;
-    let json = serde_json::to_string_pretty(&data).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| e.to_string())?;
-    Ok(())
+fn write_profiles(
+    app: tauri::AppHandle,
+    store: StoreState,
+    mut data: ProfilesData,
+) -> Result<profiles::ProfilesValidation, String> {
+    profiles::check(&data)?;
+    let mut store = profiles_store(&app, &store)?;
+    let installed = store.installed_mod_ids()?;
+    let validation = profiles::repair(&mut data, Some(&installed));
+    store.replace_profiles(&data)?;
+    Ok(validation)
 }
 
+/// Creates a profile with the next free id. Returns the new record.
 #[tauri::command]
-fn read_installed_mods(app: tauri::AppHandle) -> Result<Vec<InstalledModRecord>, String> {
-    let path = app_installed_mods_path(&app)?;
-    if !path.exists() {
-        return Ok(Vec::new());
+fn create_profile(
+    app: tauri::AppHandle,
+    store: StoreState,
+    name: String,
+    enabled_mod_ids: Vec<i64>,
+    set_active: bool,
+) -> Result<ProfileRecord, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Profile name is empty".to_string());
+    }
+    let created_at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    profiles_store(&app, &store)?.create_profile(name, &created_at, &enabled_mod_ids, set_active)
+}
+
+#[tauri::command]
+fn rename_profile(
+    app: tauri::AppHandle,
+    store: StoreState,
+    profile_id: i64,
+    name: String,
+) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Profile name is empty".to_string());
     }
-    let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-    let mods: Vec<InstalledModRecord> = serde_json::from_str(&data).map_err(|e| e.to_string())?;
-    Ok(mods)
+    profiles_store(&app, &store)?.rename_profile(profile_id, name)
+}
+
+/// Deletes a profile. If it was active, the first remaining profile becomes active.
+#[tauri::command]
+fn delete_profile(app: tauri::AppHandle, store: StoreState, profile_id: i64) -> Result<(), String> {
+    profiles_store(&app, &store)?.delete_profile(profile_id)
+}
+
+#[tauri::command]
+fn set_active_profile(
+    app: tauri::AppHandle,
+    store: StoreState,
+    profile_id: Option<i64>,
+) -> Result<(), String> {
+    profiles_store(&app, &store)?.set_active_profile(profile_id)
+}
+
+#[tauri::command]
+fn set_profile_mods(
+    app: tauri::AppHandle,
+    store: StoreState,
+    profile_id: i64,
+    mod_ids: Vec<i64>,
+) -> Result<(), String> {
+    profiles_store(&app, &store)?.set_profile_mods(profile_id, &mod_ids)
+}
+
+/// Switch to profile `profile_id`: move every mod whose state differs between the Mods and
+/// Mods.disabled folders and update the records and the active profile, all in one transaction.
+/// Nothing changes if a move fails (`TRANSACTION_FAILED:`).
+#[tauri::command]
+fn apply_profile(
+    app: tauri::AppHandle,
+    store: StoreState,
+    profile_id: i64,
+) -> Result<ApplyProfileSummary, String> {
+    let roots = mod_roots(&app)?;
+    let mut store = profiles_store(&app, &store)?;
+    let data = store.read_profiles()?;
+    let profile = data
+        .profiles
+        .iter()
+        .find(|p| p.id == profile_id)
+        .ok_or_else(|| format!("Profile {profile_id} not found"))?;
+    let (plan, summary) = profile_switch::plan(profile, &store.list_installed_mods()?, &roots);
+    transaction::run(&app_journal_path(&app)?, &mut store, plan)?;
+    Ok(summary)
+}
+
+/// Enable or disable the mods `ids` in one transaction: their files move between the Mods and
+/// Mods.disabled folders (a taken name gets a ` (1)` suffix) and their records are saved
+/// together. Nothing changes if a mod or its file is missing, or a move fails
+/// (`TRANSACTION_FAILED:`). Returns the updated records in the order of `ids`.
+#[tauri::command]
+fn set_mods_enabled_with_files(
+    app: tauri::AppHandle,
+    store: StoreState,
+    ids: Vec<i64>,
+    enabled: bool,
+) -> Result<Vec<InstalledModRecord>, String> {
+    let roots = mod_roots(&app)?;
+    let mut store = installed_mods_store(&app, &store, None)?;
+    let mods = store.list_installed_mods()?;
+    let (plan, updated) = profile_switch::set_enabled_plan(&ids, enabled, &mods, &roots)?;
+    transaction::run(&app_journal_path(&app)?, &mut store, plan)?;
+    Ok(updated)
+}
+
+/// Reads installed mods. `recovered` is set once if the legacy file was corrupt when imported;
+/// `mods_dir` lets the import rebuild the list from the Mods folders as a last resort.
+#[tauri::command]
+fn read_installed_mods(
+    app: tauri::AppHandle,
+    store: StoreState,
+    mods_dir: Option<String>,
+) -> Result<LoadResult<Vec<InstalledModRecord>>, String> {
+    let mods_dir = mods_dir
+        .map(|d| PathBuf::from(d.trim()))
+        .filter(|d| !d.as_os_str().is_empty());
+    let mut store = installed_mods_store(&app, &store, mods_dir.as_deref())?;
+    Ok(LoadResult {
+        data: store.list_installed_mods()?,
+        recovered: store
+            .take_recovery(schema::INSTALLED_MODS.name)
+            .or_else(|| store.take_recovery(store::DB_RECOVERY_NAME)),
+    })
+}
+
+#[tauri::command]
+fn get_installed_mod(
+    app: tauri::AppHandle,
+    store: StoreState,
+    id: i64,
+) -> Result<Option<InstalledModRecord>, String> {
+    installed_mods_store(&app, &store, None)?.get_installed_mod(id)
 }
 
+/// Adds a record with a server-assigned id. Fails if the same provider project/resource is
+/// already installed. Returns the stored record.
 #[tauri::command]
-fn write_installed_mods(app: tauri::AppHandle, mods: Vec<InstalledModRecord>) -> Result<(), String> {
-    let path = app_installed_mods_path(&app)?;
-    let data = serde_json::to_string_pretty(&mods).map_err(|e| e.to_string())?;
-    fs::write(&path, data).map_err(|e| e.to_string())?;
+fn add_installed_mod(
+    app: tauri::AppHandle,
+    store: StoreState,
+    record: InstalledModRecord,
+) -> Result<InstalledModRecord, String> {
+    installed_mods_store(&app, &store, None)?.add_installed_mod(record)
+}
+
+#[tauri::command]
+fn update_installed_mod(
+    app: tauri::AppHandle,
+    store: StoreState,
+    id: i64,
+    patch: InstalledModPatch,
+) -> Result<InstalledModRecord, String> {
+    installed_mods_store(&app, &store, None)?.update_installed_mod(id, patch)
+}
+
+/// Removes a record, prunes its id from every profile and deletes its backups in Mods.backup.
+/// Returns the removed record.
+#[tauri::command]
+fn remove_installed_mod(
+    app: tauri::AppHandle,
+    store: StoreState,
+    id: i64,
+) -> Result<InstalledModRecord, String> {
+    let mut store = installed_mods_store(&app, &store, None)?;
+    let backups = store.list_mod_backups(id)?;
+    let removed = store.remove_installed_mod(id)?;
+    if let Ok(roots) = mod_roots(&app) {
+        mod_backups::delete_files(&roots, &backups);
+    }
+    Ok(removed)
+}
+
+#[tauri::command]
+fn set_mods_enabled(
+    app: tauri::AppHandle,
+    store: StoreState,
+    ids: Vec<i64>,
+    enabled: bool,
+) -> Result<(), String> {
+    installed_mods_store(&app, &store, None)?.set_mods_enabled(&ids, enabled)
+}
+
+/// Replaces the whole list. Kept for compatibility; prefer the record-level commands.
+/// Mods missing from the new list are pruned from every profile and their backups deleted.
+#[tauri::command]
+fn write_installed_mods(
+    app: tauri::AppHandle,
+    store: StoreState,
+    mods: Vec<InstalledModRecord>,
+) -> Result<(), String> {
+    let dropped = installed_mods_store(&app, &store, None)?.replace_installed_mods(&mods)?;
+    if let Ok(roots) = mod_roots(&app) {
+        mod_backups::delete_files(&roots, &dropped);
+    }
     Ok(())
 }
 
@@ -145,7 +552,7 @@ fn ensure_mods_disabled_dir(mods_dir: String) -> Result<EnsureModsDirResult, Str
     if mods_path.as_os_str().is_empty() {
         return Err("Mods path is empty".to_string());
     }
-    let disabled_path = mods_path.with_extension("disabled");
+    let disabled_path = mod_paths::disabled_dir(&mods_path);
     if disabled_path.exists() {
         if !disabled_path.is_dir() {
             return Err("Mods.disabled exists but is not a directory".to_string());
@@ -158,7 +565,12 @@ fn ensure_mods_disabled_dir(mods_dir: String) -> Result<EnsureModsDirResult, Str
 
 /// Returns a path that doesn't exist yet; if `target` exists, appends (1), (2), etc.
 fn unique_file_path(target: &Path) -> PathBuf {
-    if !target.exists() {
+    unique_path_by(target, |p| p.exists())
+}
+
+/// Like [`unique_file_path`], with `taken` deciding which paths are in use.
+fn unique_path_by(target: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    if !taken(target) {
         return target.to_path_buf();
     }
     let stem = target.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
@@ -171,19 +583,32 @@ fn unique_file_path(target: &Path) -> PathBuf {
             format!("{stem} ({n})")
         };
         let p = parent.join(name);
-        if !p.exists() {
+        if !taken(&p) {
             return p;
         }
     }
     target.to_path_buf()
 }
 
+/// The last component of `path` as a string, empty if there is none.
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
 /// Move a file from one path to another. If destination exists, use a unique name.
-/// Returns the final path where the file was moved (for DB update).
+/// Returns the final path where the file was moved (for DB update). Both paths must be inside
+/// the mod folders, else the error starts with `PATH_OUTSIDE_MODS:`.
 #[tauri::command]
-fn move_mod_file(from_path: String, to_path: String) -> Result<String, String> {
-    let from = PathBuf::from(from_path.trim());
-    let to = PathBuf::from(to_path.trim());
+fn move_mod_file(
+    app: tauri::AppHandle,
+    from_path: String,
+    to_path: String,
+) -> Result<String, String> {
+    let roots = mod_roots(&app)?;
+    let from = roots.check(Path::new(from_path.trim()))?;
+    let to = roots.check_file(Path::new(to_path.trim()))?;
     if !from.exists() {
         return Err("Source file does not exist".to_string());
     }
@@ -194,13 +619,14 @@ fn move_mod_file(from_path: String, to_path: String) -> Result<String, String> {
         fs::create_dir_all(p).map_err(|e| e.to_string())?;
     }
     let final_to = unique_file_path(&to);
-    fs::rename(&from, &final_to).map_err(|e| e.to_string())?;
+    persist::move_file(&from, &final_to).map_err(|e| e.to_string())?;
     Ok(final_to.to_string_lossy().into_owned())
 }
 
+/// Move a file to the OS trash. Fails with `PATH_NOT_ALLOWED:` outside the sandbox.
 #[tauri::command]
-fn move_file_to_trash(path: String) -> Result<(), String> {
-    let p = PathBuf::from(path.trim());
+fn move_file_to_trash(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    let p = sandbox(&app)?.check(Path::new(path.trim()))?;
     if !p.exists() {
         return Err("File does not exist".to_string());
     }
@@ -227,34 +653,104 @@ fn list_mod_dir_file_names(dir_path: String) -> Result<Vec<String>, String> {
     Ok(names)
 }
 
-/// Download URL to destination path. Creates parent dirs. Writes to temp then renames.
+/// Download `request.url` to `request.dest_path`. Creates parent dirs. Streams to a temp file
+/// then renames. When `download_id` is given, `download-progress` events carrying that id are
+/// emitted. Expected hashes and size are checked before the rename; a mismatch deletes the temp
+/// file and fails with a `SIZE_MISMATCH:` or `CHECKSUM_MISMATCH:` error. Served from the download
+/// cache when `cache_key` or the expected SHA-1 is found there. With `use_server_file_name`, the
+/// file is named after the server's Content-Disposition or final URL; the chosen name is returned
+/// with the path. The destination must be inside the mod folders.
 #[tauri::command]
-fn download_file_to_path(url: String, dest_path: String) -> Result<String, String> {
-    let url = url.trim();
-    let dest = PathBuf::from(dest_path.trim());
-    if url.is_empty() {
-        return Err("URL is empty".to_string());
-    }
-    if let Some(parent) = dest.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
-    let client = reqwest::blocking::Client::builder()
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .build()
-        .map_err(|e| e.to_string())?;
-    let resp = client.get(url).send().map_err(|e| e.to_string())?;
-    if !resp.status().is_success() {
-        return Err(format!("Download failed: HTTP {}", resp.status()));
+async fn download_file_to_path(
+    app: tauri::AppHandle,
+    mut request: download::DownloadRequest,
+) -> Result<download::DownloadedFile, String> {
+    prepare_download(&app, &mod_roots(&app)?, &mut request)?;
+    let cache = app.state::<DownloadCache>().inner().clone();
+    let http = http_client(&app);
+    tauri::async_runtime::spawn_blocking(move || {
+        cache.fetch(&request, &http, &|| false, |p| {
+            let _ = app.emit(download::PROGRESS_EVENT, p);
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map(|p| download::DownloadedFile::from(p.as_path()))
+}
+
+/// Download a batch of files, a few at a time. Emits `download-progress` for every item and
+/// `download-status` whenever an item changes state. Resolves once all items are finished; while
+/// every unfinished item is paused, no thread is kept busy.
+#[tauri::command]
+async fn run_download_batch(
+    app: tauri::AppHandle,
+    manager: tauri::State<'_, DownloadManager>,
+    batch_id: String,
+    mut items: Vec<download::DownloadRequest>,
+    concurrency: Option<usize>,
+) -> Result<download_manager::BatchSummary, String> {
+    let roots = mod_roots(&app)?;
+    for item in &mut items {
+        prepare_download(&app, &roots, item)?;
     }
-    let bytes = resp.bytes().map_err(|e| e.to_string())?;
-    let temp_path = dest.with_extension("tmp");
-    {
-        let mut f = fs::File::create(&temp_path).map_err(|e| e.to_string())?;
-        f.write_all(&bytes).map_err(|e| e.to_string())?;
+    let manager = manager.inner().clone();
+    let cache = app.state::<DownloadCache>().inner().clone();
+    let http = http_client(&app);
+    let mut woken = manager.start_batch(&batch_id, items, concurrency)?;
+    loop {
+        let (manager, cache, http, app, batch_id) = (
+            manager.clone(),
+            cache.clone(),
+            http.clone(),
+            app.clone(),
+            batch_id.clone(),
+        );
+        let round = tauri::async_runtime::spawn_blocking(move || {
+            manager.work_batch(&batch_id, &cache, &http, |event| {
+                let _ = match event {
+                    DownloadEvent::Progress(p) => app.emit(download::PROGRESS_EVENT, p),
+                    DownloadEvent::Status(u) => app.emit(download_manager::STATUS_EVENT, u),
+                };
+            })
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        if let Some(summary) = round {
+            return Ok(summary);
+        }
+        // Only paused items are left; wait for a resume or cancel.
+        woken.recv().await;
     }
-    let final_path = unique_file_path(&dest);
-    fs::rename(&temp_path, &final_path).map_err(|e| e.to_string())?;
-    Ok(final_path.to_string_lossy().into_owned())
+}
+
+/// Pause one item of a running batch, or the whole batch when `item_id` is omitted.
+#[tauri::command]
+fn pause_download(
+    manager: tauri::State<'_, DownloadManager>,
+    batch_id: String,
+    item_id: Option<String>,
+) -> Result<Vec<download_manager::ItemUpdate>, String> {
+    manager.pause(&batch_id, item_id.as_deref())
+}
+
+/// Resume paused items; partial files are continued where they stopped.
+#[tauri::command]
+fn resume_download(
+    manager: tauri::State<'_, DownloadManager>,
+    batch_id: String,
+    item_id: Option<String>,
+) -> Result<Vec<download_manager::ItemUpdate>, String> {
+    manager.resume(&batch_id, item_id.as_deref())
+}
+
+/// Cancel items and delete their partial files.
+#[tauri::command]
+fn cancel_download(
+    manager: tauri::State<'_, DownloadManager>,
+    batch_id: String,
+    item_id: Option<String>,
+) -> Result<Vec<download_manager::ItemUpdate>, String> {
+    manager.cancel(&batch_id, item_id.as_deref())
 }
 
 /// Returns candidate paths for the default Hytale Mods folder per platform.
@@ -338,10 +834,13 @@ fn ensure_mods_dir(path: String) -> Result<EnsureModsDirResult, String> {
 
 #[tauri::command]
 fn check_path_access(path: String) -> CheckPathAccessResult {
-    let p = PathBuf::from(path.trim());
+    path_access(Path::new(path.trim()))
+}
+
+fn path_access(p: &Path) -> CheckPathAccessResult {
     let exists = p.exists();
     let is_dir = exists && p.is_dir();
-    let writable = is_dir && check_writable(&p);
+    let writable = is_dir && check_writable(p);
     CheckPathAccessResult {
         exists,
         is_dir,
@@ -361,18 +860,45 @@ fn check_writable(path: &std::path::Path) -> bool {
     }
 }
 
+/// The mod `apply_mod_update` installs a new file for, and what to save about that file.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModUpdateRecord {
+    mod_id: i64,
+    installed_file_id: Option<serde_json::Value>,
+    /// Hash the new file was verified against; `None` clears the old file's hash.
+    file_hash: Option<String>,
+}
+
+/// What `apply_mod_update` did.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AppliedModUpdate {
+    /// Name of the new file in `final_dir`.
+    file_name: String,
+    /// The mod's record as saved with the new file, if `update` was given.
+    record: Option<InstalledModRecord>,
+}
+
 /// Safe update: move old file to backup (Mods.backup/name.bak), then move new temp file into place.
-/// Returns the new filename (so the frontend can update the DB).
+/// Both moves run as one transaction, so a failure leaves the old file where it was. With
+/// `update`, the backup is added to the index with the mod's current record, for `rollback_mod`,
+/// and the record is saved in the same transaction with the new file name, `installed_file_id`,
+/// `file_hash` and the current time as `installed_at`. The new filename is sanitized, and every
+/// path must be inside the mod folders (else `PATH_OUTSIDE_MODS:`).
 #[tauri::command]
 fn apply_mod_update(
+    app: tauri::AppHandle,
+    store: StoreState,
     old_path: String,
     new_temp_path: String,
     final_dir: String,
     new_filename: String,
-) -> Result<String, String> {
-    let old_p = PathBuf::from(old_path.trim());
-    let new_temp = PathBuf::from(new_temp_path.trim());
-    let final_dir_p = PathBuf::from(final_dir.trim());
+    update: Option<ModUpdateRecord>,
+) -> Result<AppliedModUpdate, String> {
+    let roots = mod_roots(&app)?;
+    let old_p = roots.check(Path::new(old_path.trim()))?;
+    let new_temp = roots.check(Path::new(new_temp_path.trim()))?;
     let new_filename = new_filename.trim();
     if !old_p.exists() {
         return Err("Existing mod file not found".to_string());
@@ -383,28 +909,450 @@ fn apply_mod_update(
     if new_filename.is_empty() {
         return Err("New filename is empty".to_string());
     }
-    let parent = final_dir_p.parent().unwrap_or_else(|| final_dir_p.as_path());
-    let backup_dir = parent.join("Mods.backup");
-    fs::create_dir_all(&backup_dir).map_err(|e| e.to_string())?;
-    let old_name = old_p
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("mod");
-    let backup_base = format!("{}.bak", old_name);
-    let backup_path = unique_file_path(&backup_dir.join(&backup_base));
-    fs::rename(&old_p, &backup_path).map_err(|e| e.to_string())?;
-    let dest = final_dir_p.join(new_filename);
-    fs::rename(&new_temp, &dest).map_err(|e| e.to_string())?;
-    Ok(new_filename.to_string())
+    let dest = roots.file_in(Path::new(final_dir.trim()), new_filename)?;
+    let backup_path = mod_backups::backup_path(&roots, &file_name_of(&old_p))?;
+    let mut store = installed_mods_store(&app, &store, None)?;
+    let mut records = Vec::new();
+    let mut updated = None;
+    if let Some(update) = update {
+        let mod_id = update.mod_id;
+        let record = store
+            .get_installed_mod(mod_id)?
+            .ok_or_else(|| format!("Installed mod {mod_id} not found"))?;
+        let now = mod_backups::now();
+        let new_record = InstalledModRecord {
+            installed_file_id: update.installed_file_id,
+            installed_filename: file_name_of(&dest),
+            installed_at: now.clone(),
+            file_hash: update.file_hash,
+            ..record.clone()
+        };
+        records.push(RecordChange::AddBackup {
+            backup: Box::new(ModBackup {
+                id: store.next_mod_backup_id()?,
+                mod_id,
+                file_name: file_name_of(&backup_path),
+                created_at: now,
+                record,
+            }),
+        });
+        records.push(RecordChange::PutMod {
+            record: Box::new(new_record.clone()),
+        });
+        updated = Some(new_record);
+    }
+    let plan = Plan {
+        files: vec![
+            FileOp::Move {
+                from: old_p,
+                to: backup_path,
+            },
+            FileOp::Move {
+                from: new_temp,
+                to: dest.clone(),
+            },
+        ],
+        records,
+    };
+    transaction::run(&app_journal_path(&app)?, &mut store, plan)?;
+    Ok(AppliedModUpdate {
+        file_name: file_name_of(&dest),
+        record: updated,
+    })
 }
 
-/// Write UTF-8 text to a file. Used for export (e.g. profile JSON). Creates parent dirs.
+/// Backups of mod `mod_id` in Mods.backup, newest first. Entries whose file is gone are left out.
 #[tauri::command]
-fn write_text_file(path: String, content: String) -> Result<(), String> {
-    let p = PathBuf::from(path.trim());
-    if p.as_os_str().is_empty() {
-        return Err("Path is empty".to_string());
+fn list_mod_backups(
+    app: tauri::AppHandle,
+    store: StoreState,
+    mod_id: i64,
+) -> Result<Vec<ModBackup>, String> {
+    let roots = mod_roots(&app)?;
+    let backups = installed_mods_store(&app, &store, None)?.list_mod_backups(mod_id)?;
+    Ok(backups
+        .into_iter()
+        .filter(|b| {
+            roots
+                .file_in(&roots.backup, &b.file_name)
+                .is_ok_and(|p| p.is_file())
+        })
+        .collect())
+}
+
+/// Put the version in backup `backup_id` back for mod `mod_id`: the installed file becomes a
+/// new backup, the backed-up file takes its place and the record is restored from the backup
+/// (keeping `enabled` and `pinned`), all in one transaction. Returns the restored record.
+#[tauri::command]
+fn rollback_mod(
+    app: tauri::AppHandle,
+    store: StoreState,
+    mod_id: i64,
+    backup_id: i64,
+) -> Result<InstalledModRecord, String> {
+    let roots = mod_roots(&app)?;
+    let mut store = installed_mods_store(&app, &store, None)?;
+    let current = store
+        .get_installed_mod(mod_id)?
+        .ok_or_else(|| format!("Installed mod {mod_id} not found"))?;
+    let backup = store
+        .get_mod_backup(backup_id)?
+        .ok_or_else(|| format!("Backup {backup_id} not found"))?;
+    let next_id = store.next_mod_backup_id()?;
+    let (plan, restored) = mod_backups::rollback_plan(&current, &backup, &roots, next_id)?;
+    transaction::run(&app_journal_path(&app)?, &mut store, plan)?;
+    Ok(restored)
+}
+
+/// Read settings.json. `initial` is only used on first run, when no settings file exists yet:
+/// the frontend passes its old localStorage values so they are migrated once.
+#[tauri::command]
+fn get_settings(
+    app: tauri::AppHandle,
+    cache: tauri::State<'_, DownloadCache>,
+    initial: Option<Settings>,
+) -> Result<LoadResult<Settings>, String> {
+    let res = settings::load_or_init(&app_settings_path(&app)?, initial)?;
+    cache.set_limit(res.data.download_cache_limit_bytes())?;
+    match HttpClient::from_settings(&res.data) {
+        Ok(http) => *lock_http_client(&app) = http,
+        Err(e) => eprintln!("[http] Keeping the previous client: {e}"),
+    }
+    Ok(res)
+}
+
+/// Validate and save settings. Returns them as stored (trimmed, blanks as null).
+#[tauri::command]
+fn update_settings(
+    app: tauri::AppHandle,
+    cache: tauri::State<'_, DownloadCache>,
+    settings: Settings,
+) -> Result<Settings, String> {
+    let settings = settings::update(&app_settings_path(&app)?, settings)?;
+    cache.set_limit(settings.download_cache_limit_bytes())?;
+    *lock_http_client(&app) = HttpClient::from_settings(&settings)?;
+    Ok(settings)
+}
+
+/// Tell the user, in a native dialog, that an interrupted mod change could not be finished or
+/// undone. While its journal is still there, every change that moves mod files is refused, so
+/// the user can set it aside (some files may then be left in the wrong folder) or keep it for
+/// the next start.
+fn report_failed_recovery(app: &tauri::AppHandle, journal: PathBuf, error: String) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let mut message = format!(
+        "HygherTales was closed in the middle of changing your mod files, and that change could not be finished or undone:\n\n{error}"
+    );
+    let pending = journal.exists();
+    if pending {
+        message.push_str("\n\nUntil it is resolved, enabling, disabling, updating and switching profiles are refused. Set it aside to continue (some mod files may be left in the wrong folder), or keep it to try again on the next start.");
+    }
+    let dialog = app
+        .dialog()
+        .message(message)
+        .title("An interrupted change could not be recovered")
+        .kind(MessageDialogKind::Warning);
+    if !pending {
+        dialog.show(|_| {});
+        return;
     }
+    dialog
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Set aside".to_string(),
+            "Keep".to_string(),
+        ))
+        .show(move |set_aside| {
+            if set_aside {
+                if let Err(e) = transaction::set_aside(&journal) {
+                    eprintln!("[transaction] Could not set the journal aside: {e}");
+                }
+            }
+        });
+}
+
+/// Ask the user a yes/no question in a native dialog. The dialog runs on the Rust side so the
+/// webview cannot confirm it on the user's behalf.
+async fn confirm(
+    app: &tauri::AppHandle,
+    title: &'static str,
+    message: String,
+    ok_label: &'static str,
+) -> Result<bool, String> {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let dialog = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        dialog
+            .dialog()
+            .message(message)
+            .title(title)
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                ok_label.to_string(),
+                "Cancel".to_string(),
+            ))
+            .blocking_show()
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Save settings changed by a native dialog, use them for downloads and tell the frontend.
+fn apply_confirmed_settings(
+    app: &tauri::AppHandle,
+    change: impl FnOnce(&mut Settings),
+) -> Result<Settings, String> {
+    let settings = settings::modify(&app_settings_path(app)?, change)?;
+    *lock_http_client(app) = HttpClient::from_settings(&settings)?;
+    let _ = app.emit(settings::CHANGED_EVENT, &settings);
+    Ok(settings)
+}
+
+/// Ask the user, in a native dialog, whether downloads from the host of `url` may be allowed.
+/// Returns true if the host is (now) allowed.
+#[tauri::command]
+async fn trust_download_host(app: tauri::AppHandle, url: String) -> Result<bool, String> {
+    let host = allowlist::host_of(&url)?;
+    if http_client(&app).allowlist.allows_host(&host) {
+        return Ok(true);
+    }
+    let confirmed = confirm(
+        &app,
+        "Allow downloads from this host?",
+        format!(
+            "{host} is not a known mod download host.\n\nOnly allow it if you trust this source. Files from it will be saved to your Mods folder."
+        ),
+        "Allow",
+    )
+    .await?;
+    if !confirmed {
+        return Ok(false);
+    }
+    let settings = settings::trust_host(&app_settings_path(&app)?, &host)?;
+    *lock_http_client(&app) = HttpClient::from_settings(&settings)?;
+    let _ = app.emit(settings::CHANGED_EVENT, &settings);
+    eprintln!("[download] User allowed downloads from {host}");
+    Ok(true)
+}
+
+/// Send downloads through `url`, after the user confirmed it in a native dialog. A blank URL
+/// turns the proxy off without asking. Returns the saved settings, or `None` if cancelled.
+#[tauri::command]
+async fn set_http_proxy_url(
+    app: tauri::AppHandle,
+    url: Option<String>,
+) -> Result<Option<Settings>, String> {
+    let url = url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty());
+    if let Some(ref url) = url {
+        http::proxy(url)?;
+        let confirmed = confirm(
+            &app,
+            "Use this download proxy?",
+            format!(
+                "All mod downloads will go through {url}.\n\nOnly use a proxy you trust: it can see and change what is downloaded."
+            ),
+            "Use proxy",
+        )
+        .await?;
+        if !confirmed {
+            return Ok(None);
+        }
+    }
+    let settings = apply_confirmed_settings(&app, |s| s.http_proxy_url = url)?;
+    eprintln!("[http] Download proxy set to {:?}", settings.http_proxy_url);
+    Ok(Some(settings))
+}
+
+/// Point the app at another HygherTales proxy. A proxy that is not on this machine is only
+/// used after the user confirmed it in a native dialog, which also allows downloads from it.
+/// A blank URL restores the default. Returns the saved settings, or `None` if cancelled.
+#[tauri::command]
+async fn set_proxy_base_url(
+    app: tauri::AppHandle,
+    url: String,
+) -> Result<Option<Settings>, String> {
+    let url = match url.trim() {
+        "" => settings::DEFAULT_PROXY_BASE_URL.to_string(),
+        url => url.to_string(),
+    };
+    let host = allowlist::host_of(&url)?;
+    let local = allowlist::is_local_host(&host);
+    if !local {
+        let confirmed = confirm(
+            &app,
+            "Use this HygherTales proxy?",
+            format!(
+                "Mod searches and download links will come from {host}, and files from it will be saved to your Mods folder.\n\nOnly use a proxy you trust."
+            ),
+            "Use proxy",
+        )
+        .await?;
+        if !confirmed {
+            return Ok(None);
+        }
+    }
+    let settings = apply_confirmed_settings(&app, |s| {
+        s.proxy_base_url = url;
+        if !local {
+            s.trusted_download_hosts.push(host.clone());
+        }
+    })?;
+    eprintln!("[proxy] Proxy base URL set to {}", settings.proxy_base_url);
+    Ok(Some(settings))
+}
+
+/// Let the user pick the Mods folder in a native folder dialog and save it. Returns the saved
+/// settings, or `None` if cancelled.
+#[tauri::command]
+async fn pick_mods_dir(app: tauri::AppHandle) -> Result<Option<Settings>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let dialog = app.dialog().file().set_title("Select your Hytale Mods folder");
+    let picked = tauri::async_runtime::spawn_blocking(move || dialog.blocking_pick_folder())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    let path = path.to_string_lossy().into_owned();
+    let settings = apply_confirmed_settings(&app, |s| s.mods_dir_path = Some(path))?;
+    eprintln!("[settings] Mods folder set to {:?}", settings.mods_dir_path);
+    Ok(Some(settings))
+}
+
+/// Use `path` (typed or auto-detected) as the Mods folder, after the user confirmed it in a
+/// native dialog. A blank path unsets it without asking. Returns the saved settings, or `None`
+/// if cancelled.
+#[tauri::command]
+async fn set_mods_dir(
+    app: tauri::AppHandle,
+    path: Option<String>,
+) -> Result<Option<Settings>, String> {
+    let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(ref path) = path {
+        let confirmed = confirm(
+            &app,
+            "Use this Mods folder?",
+            format!(
+                "{path}\n\nHygherTales will add, move and delete mod files in this folder and in the Mods.disabled and Mods.backup folders next to it."
+            ),
+            "Use folder",
+        )
+        .await?;
+        if !confirmed {
+            return Ok(None);
+        }
+    }
+    let settings = apply_confirmed_settings(&app, |s| s.mods_dir_path = path)?;
+    eprintln!("[settings] Mods folder set to {:?}", settings.mods_dir_path);
+    Ok(Some(settings))
+}
+
+/// Let the user pick a root certificate to trust in a native file dialog and add it to the
+/// settings. Returns the saved settings, or `None` if cancelled.
+#[tauri::command]
+async fn add_ca_cert(app: tauri::AppHandle) -> Result<Option<Settings>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let dialog = app
+        .dialog()
+        .file()
+        .set_title("Select a root certificate to trust")
+        .add_filter("Certificates", &["pem", "crt", "cer", "der"]);
+    let picked = tauri::async_runtime::spawn_blocking(move || dialog.blocking_pick_file())
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    let path = path.to_string_lossy().into_owned();
+    let settings = apply_confirmed_settings(&app, |s| {
+        if !s.extra_ca_certs.contains(&path) {
+            s.extra_ca_certs.push(path.clone());
+        }
+    })?;
+    eprintln!("[http] Trusting root certificates from {path}");
+    Ok(Some(settings))
+}
+
+/// Number and total size of the files in the download cache.
+#[tauri::command]
+fn get_download_cache_usage(cache: tauri::State<'_, DownloadCache>) -> CacheUsage {
+    cache.usage()
+}
+
+/// Delete every file in the download cache. Returns the (empty) usage.
+#[tauri::command]
+fn clear_download_cache(cache: tauri::State<'_, DownloadCache>) -> Result<CacheUsage, String> {
+    cache.clear()
+}
+
+/// Show a native save dialog. The chosen path is added to the sandbox, so it can be written
+/// with `write_text_file`. `None` if the dialog was cancelled.
+#[tauri::command]
+async fn pick_save_path(
+    app: tauri::AppHandle,
+    default_name: Option<String>,
+    filter: Option<FileFilter>,
+) -> Result<Option<String>, String> {
+    pick_path(app, move |mut dialog| {
+        if let Some(name) = default_name.as_deref().and_then(file_name::sanitize) {
+            dialog = dialog.set_file_name(name);
+        }
+        dialog_with_filter(dialog, filter).blocking_save_file()
+    })
+    .await
+}
+
+/// Show a native open dialog for one file. The chosen file is added to the sandbox, so it can be
+/// read with `read_text_file`. `None` if the dialog was cancelled.
+#[tauri::command]
+async fn pick_open_path(
+    app: tauri::AppHandle,
+    filter: Option<FileFilter>,
+) -> Result<Option<String>, String> {
+    pick_path(app, move |dialog| dialog_with_filter(dialog, filter).blocking_pick_file()).await
+}
+
+type FileDialog = tauri_plugin_dialog::FileDialogBuilder<tauri::Wry>;
+
+fn dialog_with_filter(dialog: FileDialog, filter: Option<FileFilter>) -> FileDialog {
+    match filter {
+        Some(f) => {
+            let extensions: Vec<&str> = f.extensions.iter().map(String::as_str).collect();
+            dialog.add_filter(f.name, &extensions)
+        }
+        None => dialog,
+    }
+}
+
+/// Run `show` with a file dialog off the async runtime and remember the path the user chose.
+async fn pick_path(
+    app: tauri::AppHandle,
+    show: impl FnOnce(FileDialog) -> Option<tauri_plugin_dialog::FilePath> + Send + 'static,
+) -> Result<Option<String>, String> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let dialog = app.dialog().file();
+    let picked = tauri::async_runtime::spawn_blocking(move || show(dialog))
+        .await
+        .map_err(|e| e.to_string())?;
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    app.state::<PickedPaths>().add(path.clone());
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+/// Write UTF-8 text to a file. Used for export (e.g. profile JSON). Creates parent dirs. Fails
+/// with `PATH_NOT_ALLOWED:` outside the sandbox.
+#[tauri::command]
+fn write_text_file(app: tauri::AppHandle, path: String, content: String) -> Result<(), String> {
+    let p = sandbox(&app)?.check(Path::new(path.trim()))?;
     if let Some(parent) = p.parent() {
         fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
@@ -412,10 +1360,11 @@ fn write_text_file(path: String, content: String) -> Result<(), String> {
     Ok(())
 }
 
-/// Read a file as UTF-8 text. Used for import (e.g. profile JSON).
+/// Read a file as UTF-8 text. Used for import (e.g. profile JSON). Fails with
+/// `PATH_NOT_ALLOWED:` outside the sandbox.
 #[tauri::command]
-fn read_text_file(path: String) -> Result<String, String> {
-    let p = PathBuf::from(path.trim());
+fn read_text_file(app: tauri::AppHandle, path: String) -> Result<String, String> {
+    let p = sandbox(&app)?.check(Path::new(path.trim()))?;
     if !p.exists() || !p.is_file() {
         return Err("File does not exist or is not a file".to_string());
     }
@@ -564,11 +1513,101 @@ fn stop_proxy_sidecar() -> Result<(), String> {
     Ok(())
 }
 
+/// Payload of the `single-instance` event: the arguments a second launch was started with.
+#[derive(serde::Serialize, Clone)]
+struct SecondInstancePayload {
+    args: Vec<String>,
+    cwd: String,
+}
+
 pub fn run() {
     tauri::Builder::default()
+        // Must be registered first so a second launch exits before it touches any state.
+        .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            eprintln!("[instance] Second launch forwarded: {args:?}");
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            let _ = app.emit("single-instance", SecondInstancePayload { args, cwd });
+        }))
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .setup(|app| {
+            let data_dir = app_data_dir(app.handle())?;
+            // Backstop for launches the plugin cannot detect; the OS drops the lock on exit.
+            match instance::acquire(&data_dir) {
+                Ok(lock) => {
+                    app.manage(lock);
+                }
+                Err(e) => {
+                    use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+
+                    // Nothing is set up past this point; the app exits once the user saw why.
+                    eprintln!("[instance] {e}; exiting");
+                    if let Some(window) = app.get_webview_window("main") {
+                        let _ = window.hide();
+                    }
+                    let handle = app.handle().clone();
+                    app.dialog()
+                        .message(format!(
+                            "HygherTales is already running and is using its data folder.\n\n{e}"
+                        ))
+                        .title("HygherTales is already running")
+                        .kind(MessageDialogKind::Error)
+                        .show(move |_| handle.exit(1));
+                    return Ok(());
+                }
+            }
+
+            // The frontend applies the configured values again when it loads settings.
+            let saved = schema::read::<Settings>(
+                &schema::SETTINGS,
+                &data_dir.join(settings::SETTINGS_FILENAME),
+            )
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+            let db_path = data_dir.join(store::DB_FILENAME);
+            let mut store = Store::open_or_recover(&db_path)?;
+            // Finish or undo a mod change the last run was killed in the middle of.
+            let journal = data_dir.join(transaction::JOURNAL_FILENAME);
+            let roots = ModRoots::from_settings(&saved).ok();
+            match transaction::recover(&journal, &mut store, roots.as_ref()) {
+                Ok(Some(how)) => eprintln!("[transaction] Interrupted change {how:?}"),
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("[transaction] Recovery failed: {e}");
+                    report_failed_recovery(app.handle(), journal, e);
+                }
+            }
+            app.manage(Mutex::new(store));
+            app.manage(DownloadManager::new());
+            app.manage(PickedPaths::default());
+            app.manage(DownloadCache::open(
+                &data_dir.join(download_cache::CACHE_DIRNAME),
+                saved.download_cache_limit_bytes(),
+            ));
+            let http = HttpClient::from_settings(&saved).or_else(|e| {
+                eprintln!("[http] {e}; using the default client");
+                HttpClient::from_settings(&Settings::default())
+            })?;
+            app.manage(Mutex::new(http));
+
+            // No download runs yet, so anything staged or half-written is a leftover.
+            let mod_folders = ModRoots::from_settings(&saved)
+                .map(|r| vec![r.mods, r.disabled])
+                .unwrap_or_default();
+            let removed = download::sweep_leftovers(
+                &data_dir.join(download::STAGING_DIRNAME),
+                &mod_folders,
+                download::STAGING_MAX_AGE,
+            );
+            if removed > 0 {
+                eprintln!("[download] Cleaned up {removed} leftover download file(s)");
+            }
+
             // Auto-start the proxy sidecar as a hidden subprocess on app boot.
             // This makes the app behave as a true monolith — no frontend call required.
             let handle = app.handle().clone();
@@ -592,17 +1631,47 @@ pub fn run() {
             get_default_hytale_mods_paths,
             ensure_mods_dir,
             check_path_access,
+            get_settings,
+            update_settings,
+            trust_download_host,
+            set_http_proxy_url,
+            set_proxy_base_url,
+            pick_mods_dir,
+            set_mods_dir,
+            add_ca_cert,
             read_installed_mods,
             write_installed_mods,
             read_profiles,
             write_profiles,
+            create_profile,
+            rename_profile,
+            delete_profile,
+            set_active_profile,
+            set_profile_mods,
+            apply_profile,
+            get_installed_mod,
+            add_installed_mod,
+            update_installed_mod,
+            remove_installed_mod,
+            set_mods_enabled,
+            set_mods_enabled_with_files,
             ensure_mods_disabled_dir,
             move_mod_file,
             move_file_to_trash,
             list_mod_dir_file_names,
             download_file_to_path,
+            run_download_batch,
+            pause_download,
+            resume_download,
+            cancel_download,
+            get_download_cache_usage,
+            clear_download_cache,
             open_path_in_file_manager,
             apply_mod_update,
+            list_mod_backups,
+            rollback_mod,
+            pick_save_path,
+            pick_open_path,
             write_text_file,
             read_text_file,
             launch_game,
@@ -0,0 +1,204 @@
+//! The index of replaced mod files in `Mods.backup`.
+//!
+//! `apply_mod_update` moves the old file to `Mods.backup/<name>.bak` and records a [`ModBackup`]
+//! with the mod's record as it was, so `rollback_mod` can later put that version back. A
+//! rollback keeps the version it replaces as a new backup, so it can be undone the same way.
+
+use crate::mod_paths::ModRoots;
+use crate::transaction::{FileOp, Plan, RecordChange};
+use crate::{file_name_of, unique_path_by, InstalledModRecord};
+
+/// One file in `Mods.backup` and the version of the mod it holds.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ModBackup {
+    pub id: i64,
+    pub mod_id: i64,
+    /// Name of the file in `Mods.backup`.
+    pub file_name: String,
+    /// When the file was replaced (RFC 3339).
+    pub created_at: String,
+    /// The mod's record while this file was installed, including its `installed_file_id`.
+    pub record: InstalledModRecord,
+}
+
+/// Current time in the format the frontend uses for `installed_at`.
+pub fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// `Mods.backup/<file name>.bak`, with a ` (1)` suffix if taken.
+pub fn backup_path(roots: &ModRoots, file_name: &str) -> Result<std::path::PathBuf, String> {
+    let path = roots.file_in(&roots.backup, &format!("{file_name}.bak"))?;
+    Ok(unique_path_by(&path, |p| p.exists()))
+}
+
+/// Delete the files of `backups`, whose index entries were dropped with their mod. A file that
+/// cannot be deleted is only logged, as nothing refers to it any more.
+pub fn delete_files(roots: &ModRoots, backups: &[ModBackup]) {
+    for backup in backups {
+        let Ok(path) = roots.file_in(&roots.backup, &backup.file_name) else {
+            continue;
+        };
+        if let Err(e) = std::fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                eprintln!("[backups] Could not delete {}: {e}", path.display());
+            }
+        }
+    }
+}
+
+/// Plan to swap the installed file of `current` with `backup`: the installed file goes to
+/// `Mods.backup` as backup `next_id`, the backed-up file takes its place (in `Mods` or
+/// `Mods.disabled`, wherever the mod is now) and the record is restored from the backup,
+/// keeping whether the mod is enabled and pinned. Returns the plan and the restored record.
+pub fn rollback_plan(
+    current: &InstalledModRecord,
+    backup: &ModBackup,
+    roots: &ModRoots,
+    next_id: i64,
+) -> Result<(Plan, InstalledModRecord), String> {
+    let mod_id = current
+        .id
+        .ok_or_else(|| format!("{} has no record id", current.name))?;
+    if backup.mod_id != mod_id {
+        return Err(format!(
+            "Backup {} belongs to another mod ({})",
+            backup.id, backup.mod_id
+        ));
+    }
+    let stored = roots.file_in(&roots.backup, &backup.file_name)?;
+    if !stored.is_file() {
+        return Err(format!("Backup file {} not found", stored.display()));
+    }
+    let dir = if current.enabled {
+        &roots.mods
+    } else {
+        &roots.disabled
+    };
+    let installed = roots.file_in(dir, &current.installed_filename)?;
+
+    let mut files = Vec::new();
+    let mut records = Vec::new();
+    if installed.is_file() {
+        let kept = backup_path(roots, &current.installed_filename)?;
+        records.push(RecordChange::AddBackup {
+            backup: Box::new(ModBackup {
+                id: next_id,
+                mod_id,
+                file_name: file_name_of(&kept),
+                created_at: now(),
+                record: current.clone(),
+            }),
+        });
+        files.push(FileOp::Move {
+            from: installed.clone(),
+            to: kept,
+        });
+    }
+    // The installed file is moved away first, so its name is free.
+    let target = roots.file_in(dir, &backup.record.installed_filename)?;
+    let target = unique_path_by(&target, |p| p.exists() && *p != installed);
+    files.push(FileOp::Move {
+        from: stored,
+        to: target.clone(),
+    });
+
+    let mut restored = backup.record.clone();
+    restored.id = Some(mod_id);
+    restored.enabled = current.enabled;
+    restored.pinned = current.pinned;
+    restored.installed_filename = file_name_of(&target);
+    records.push(RecordChange::PutMod {
+        record: Box::new(restored.clone()),
+    });
+    records.push(RecordChange::RemoveBackup { id: backup.id });
+    Ok((Plan { files, records }, restored))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn record(filename: &str, file_id: i64, enabled: bool) -> InstalledModRecord {
+        InstalledModRecord {
+            installed_file_id: Some(file_id.into()),
+            ..InstalledModRecord::for_test(1, filename, enabled)
+        }
+    }
+
+    #[test]
+    fn swaps_the_installed_file_with_the_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let roots = ModRoots::new(&dir.path().join("Mods"));
+        fs::create_dir_all(&roots.disabled).unwrap();
+        fs::create_dir_all(&roots.backup).unwrap();
+        fs::write(roots.disabled.join("mod-2.0.jar"), "v2").unwrap();
+        fs::write(roots.backup.join("mod-1.0.jar.bak"), "v1").unwrap();
+        let mut current = record("mod-2.0.jar", 20, false);
+        current.pinned = true;
+        let backup = ModBackup {
+            id: 4,
+            mod_id: 1,
+            file_name: "mod-1.0.jar.bak".to_string(),
+            created_at: "2024-02-01T00:00:00.000Z".to_string(),
+            record: record("mod-1.0.jar", 10, true),
+        };
+
+        let (plan, restored) = rollback_plan(&current, &backup, &roots, 5).unwrap();
+        assert_eq!(
+            plan.files,
+            [
+                FileOp::Move {
+                    from: roots.disabled.join("mod-2.0.jar"),
+                    to: roots.backup.join("mod-2.0.jar.bak"),
+                },
+                FileOp::Move {
+                    from: roots.backup.join("mod-1.0.jar.bak"),
+                    to: roots.disabled.join("mod-1.0.jar"),
+                },
+            ]
+        );
+        assert_eq!(restored.installed_file_id, Some(10.into()));
+        assert_eq!(restored.installed_filename, "mod-1.0.jar");
+        assert!(!restored.enabled && restored.pinned);
+        let RecordChange::AddBackup { backup: kept } = &plan.records[0] else {
+            panic!("{:?}", plan.records[0]);
+        };
+        assert_eq!((kept.id, kept.mod_id), (5, 1));
+        assert_eq!(kept.record, current);
+        assert!(matches!(
+            plan.records[2],
+            RecordChange::RemoveBackup { id: 4 }
+        ));
+
+        let other = ModBackup {
+            mod_id: 2,
+            ..backup.clone()
+        };
+        assert!(rollback_plan(&current, &other, &roots, 5).is_err());
+        fs::remove_file(roots.backup.join("mod-1.0.jar.bak")).unwrap();
+        assert!(rollback_plan(&current, &backup, &roots, 5).is_err());
+    }
+
+    #[test]
+    fn deletes_the_files_of_dropped_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let roots = ModRoots::new(&dir.path().join("Mods"));
+        fs::create_dir_all(&roots.backup).unwrap();
+        fs::write(roots.backup.join("a.jar.bak"), "").unwrap();
+        fs::write(roots.backup.join("b.jar.bak"), "").unwrap();
+        let backup = |file_name: &str| ModBackup {
+            id: 1,
+            mod_id: 1,
+            file_name: file_name.to_string(),
+            created_at: String::new(),
+            record: record("a.jar", 10, true),
+        };
+
+        delete_files(&roots, &[backup("a.jar.bak"), backup("gone.jar.bak")]);
+        assert!(!roots.backup.join("a.jar.bak").exists());
+        assert!(roots.backup.join("b.jar.bak").exists());
+    }
+}
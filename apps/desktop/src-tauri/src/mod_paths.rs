@@ -0,0 +1,158 @@
+//! The folders mod files live in, and the check that a path stays inside them.
+//!
+//! Paths and file names reach the mod file commands from the webview and from providers, so a
+//! name like `../../.bashrc` or an absolute path must not let a download, move or update write
+//! outside the configured Mods folder and its `Mods.disabled` and `Mods.backup` siblings.
+
+use std::path::{Path, PathBuf};
+
+use crate::file_name;
+use crate::sandbox::resolve;
+use crate::settings::Settings;
+
+/// Error code prefix of a path outside the mod folders.
+pub const PATH_OUTSIDE_MODS: &str = "PATH_OUTSIDE_MODS";
+
+/// `.../UserData/Mods` -> `.../UserData/Mods.disabled`
+pub fn disabled_dir(mods_dir: &Path) -> PathBuf {
+    let mut name = mods_dir.file_name().unwrap_or_default().to_os_string();
+    name.push(".disabled");
+    mods_dir.with_file_name(name)
+}
+
+/// `.../UserData/Mods` -> `.../UserData/Mods.backup`, where replaced files are kept.
+pub fn backup_dir(mods_dir: &Path) -> PathBuf {
+    mods_dir.parent().unwrap_or(mods_dir).join("Mods.backup")
+}
+
+fn outside(path: &Path, why: &str) -> String {
+    format!("{PATH_OUTSIDE_MODS}: {} {why}", path.display())
+}
+
+/// The Mods folder and its siblings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModRoots {
+    pub mods: PathBuf,
+    pub disabled: PathBuf,
+    pub backup: PathBuf,
+}
+
+impl ModRoots {
+    pub fn new(mods_dir: &Path) -> Self {
+        Self {
+            mods: mods_dir.to_path_buf(),
+            disabled: disabled_dir(mods_dir),
+            backup: backup_dir(mods_dir),
+        }
+    }
+
+    /// Roots of the Mods folder in the saved settings.
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        match settings.mods_dir_path {
+            Some(ref dir) => Ok(Self::new(Path::new(dir))),
+            None => Err(format!("{PATH_OUTSIDE_MODS}: no Mods folder is configured")),
+        }
+    }
+
+    /// Returns `path` if it is a file path inside one of the roots, after resolving symlinks in
+    /// the part that exists. Paths containing `..`, relative paths and the roots themselves are
+    /// rejected with [`PATH_OUTSIDE_MODS`].
+    pub fn check(&self, path: &Path) -> Result<PathBuf, String> {
+        let resolved = resolve(path).map_err(|why| outside(path, &why))?;
+        for root in [&self.mods, &self.disabled, &self.backup] {
+            let Ok(root) = resolve(root) else { continue };
+            if resolved != root && resolved.starts_with(&root) {
+                return Ok(path.to_path_buf());
+            }
+        }
+        Err(outside(
+            path,
+            "is not inside the Mods, Mods.disabled or Mods.backup folder",
+        ))
+    }
+
+    /// `dir` joined with `name` cleaned by [`file_name::sanitize`], checked like [`Self::check`].
+    pub fn file_in(&self, dir: &Path, name: &str) -> Result<PathBuf, String> {
+        let name = file_name::sanitize(name)
+            .ok_or_else(|| outside(Path::new(name), "is not a usable file name"))?;
+        self.check(&dir.join(name))
+    }
+
+    /// Like [`Self::check`], after cleaning the last component of `path` (the file name).
+    pub fn check_file(&self, path: &Path) -> Result<PathBuf, String> {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| outside(path, "has no file name"))?;
+        self.file_in(path.parent().unwrap_or(Path::new("")), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn sibling_folders() {
+        let mods = Path::new("/games/UserData/Mods");
+        assert_eq!(
+            disabled_dir(mods),
+            Path::new("/games/UserData/Mods.disabled")
+        );
+        assert_eq!(backup_dir(mods), Path::new("/games/UserData/Mods.backup"));
+    }
+
+    #[test]
+    fn keeps_paths_inside_the_mod_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("Mods");
+        fs::create_dir_all(&mods).unwrap();
+        let roots = ModRoots::new(&mods);
+        assert_eq!(
+            roots.check(&mods.join("a.jar")).unwrap(),
+            mods.join("a.jar")
+        );
+        // Mods.disabled and Mods.backup need not exist yet.
+        assert!(roots.check(&roots.disabled.join("b.jar")).is_ok());
+        assert!(roots.check(&roots.backup.join("sub/c.jar.bak")).is_ok());
+        assert_eq!(
+            roots.file_in(&mods, "../../.bashrc").unwrap(),
+            mods.join("bashrc")
+        );
+
+        for bad in [
+            mods.join("../escape.jar"),
+            mods.clone(),
+            dir.path().join("Mods2/a.jar"),
+            dir.path().join("a.jar"),
+            PathBuf::from("Mods/a.jar"),
+            PathBuf::from("/etc/passwd"),
+        ] {
+            let err = roots.check(&bad).unwrap_err();
+            assert!(
+                err.starts_with(PATH_OUTSIDE_MODS),
+                "{}: {err}",
+                bad.display()
+            );
+        }
+        assert!(roots.file_in(&mods, "..").is_err());
+        assert!(ModRoots::from_settings(&Settings::default()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_lead_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("Mods");
+        let elsewhere = dir.path().join("elsewhere");
+        fs::create_dir_all(&mods).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, mods.join("link")).unwrap();
+
+        let err = ModRoots::new(&mods)
+            .check(&mods.join("link/a.jar"))
+            .unwrap_err();
+        assert!(err.starts_with(PATH_OUTSIDE_MODS), "{err}");
+    }
+}
@@ -0,0 +1,270 @@
+//! Crash-safe persistence for the JSON files in the app data dir.
+//!
+//! Writes go to a temp file next to the target, are fsynced, then renamed over the live
+//! file, so readers only ever see the old or the new contents. The previous contents are
+//! kept as rolling generations (`installed_mods.json.1` is the newest, `.2` the one before).
+//!
+//! [`move_file`] moves mod files between folders that may be on different filesystems.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Number of previous generations kept next to each persisted file.
+pub const GENERATIONS: usize = 3;
+
+/// Path of backup generation `n` (1 = most recent) for `path`.
+pub fn generation_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{n}"));
+    path.with_file_name(name)
+}
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("data");
+    let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{name}.tmp-{}-{n}", std::process::id()))
+}
+
+/// Shift `.1` -> `.2` -> ... and copy the live file into `.1`. The oldest generation is dropped.
+fn rotate_generations(path: &Path, generations: usize) -> io::Result<()> {
+    if generations == 0 || !path.is_file() {
+        return Ok(());
+    }
+    let oldest = generation_path(path, generations);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for n in (1..generations).rev() {
+        let from = generation_path(path, n);
+        if from.exists() {
+            fs::rename(&from, generation_path(path, n + 1))?;
+        }
+    }
+    // Copy rather than rename so the live file never disappears, even briefly.
+    fs::copy(path, generation_path(path, 1))?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Atomically replace `path` with `contents`, keeping `generations` previous versions.
+pub fn write_atomic(path: &Path, contents: &[u8], generations: usize) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let tmp = temp_path(path);
+    let result = (|| {
+        {
+            let mut f = fs::File::create(&tmp)?;
+            f.write_all(contents)?;
+            f.sync_all()?;
+        }
+        rotate_generations(path, generations)?;
+        fs::rename(&tmp, path)?;
+        sync_dir(dir)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// `rename` error code for a target on another filesystem (`EXDEV`, `ERROR_NOT_SAME_DEVICE`).
+#[cfg(unix)]
+const CROSS_DEVICE: i32 = 18;
+#[cfg(windows)]
+const CROSS_DEVICE: i32 = 17;
+
+/// Move `from` to `to`, replacing `to`. A plain rename when both are on one filesystem; across
+/// filesystems (a symlinked or network-mounted Mods folder, the staging dir on another drive)
+/// see [`copy_then_delete`].
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(CROSS_DEVICE) => copy_then_delete(from, to),
+        result => result,
+    }
+}
+
+/// The fallback of [`move_file`]: copy into a temp file next to `to`, fsync it, check it has the
+/// same bytes as `from`, give it the access and modification times of `from`, rename it over
+/// `to` and only then delete `from`. If any step fails, `from` is untouched and no temp is left.
+fn copy_then_delete(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = fs::metadata(from)?;
+    let tmp = temp_path(to);
+    let result = (|| {
+        fs::copy(from, &tmp)?;
+        fs::OpenOptions::new().write(true).open(&tmp)?.sync_all()?;
+        if !same_contents(from, &tmp)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("copy of {} does not match the original", from.display()),
+            ));
+        }
+        filetime::set_file_times(
+            &tmp,
+            filetime::FileTime::from_last_access_time(&meta),
+            filetime::FileTime::from_last_modification_time(&meta),
+        )?;
+        fs::rename(&tmp, to)?;
+        if let Some(dir) = to.parent().filter(|p| !p.as_os_str().is_empty()) {
+            sync_dir(dir)?;
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return result;
+    }
+    fs::remove_file(from)
+}
+
+/// Compare two files byte by byte.
+fn same_contents(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (fs::File::open(a)?, fs::File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut buf_a, mut buf_b) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    loop {
+        let n = read_full(&mut a, &mut buf_a)?;
+        let m = read_full(&mut b, &mut buf_b)?;
+        if buf_a[..n] != buf_b[..m] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fill `buf` unless the end of the file comes first; returns the number of bytes read.
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Serialize `value` as pretty JSON and write it with [`write_atomic`].
+pub fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    write_atomic(path, json.as_bytes(), GENERATIONS).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_replaces_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installed_mods.json");
+        write_atomic(&path, b"[1]", GENERATIONS).unwrap();
+        write_atomic(&path, b"[2]", GENERATIONS).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[2]");
+        assert_eq!(
+            fs::read_to_string(generation_path(&path, 1)).unwrap(),
+            "[1]"
+        );
+        let leftovers = fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .contains(".tmp-")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn keeps_only_configured_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles.json");
+        for i in 0..6 {
+            write_atomic(&path, format!("{i}").as_bytes(), 3).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "5");
+        assert_eq!(fs::read_to_string(generation_path(&path, 1)).unwrap(), "4");
+        assert_eq!(fs::read_to_string(generation_path(&path, 2)).unwrap(), "3");
+        assert_eq!(fs::read_to_string(generation_path(&path, 3)).unwrap(), "2");
+        assert!(!generation_path(&path, 4).exists());
+    }
+
+    #[test]
+    fn cross_device_fallback_keeps_contents_and_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("a.jar");
+        let to = dir.path().join("Mods.disabled/a.jar");
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        fs::write(&from, &data).unwrap();
+        fs::write(&to, b"old").unwrap();
+        let mtime = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&from, mtime).unwrap();
+
+        copy_then_delete(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read(&to).unwrap(), data);
+        let meta = fs::metadata(&to).unwrap();
+        assert_eq!(filetime::FileTime::from_last_modification_time(&meta), mtime);
+        assert_eq!(fs::read_dir(to.parent().unwrap()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_fallback_keeps_the_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("a.jar");
+        fs::write(&from, b"mod").unwrap();
+        let to = dir.path().join("missing/a.jar");
+        assert!(copy_then_delete(&from, &to).is_err());
+        assert_eq!(fs::read(&from).unwrap(), b"mod");
+
+        assert!(same_contents(&from, &from).unwrap());
+        let other = dir.path().join("b.jar");
+        fs::write(&other, b"moe").unwrap();
+        assert!(!same_contents(&from, &other).unwrap());
+    }
+
+    #[test]
+    fn move_file_renames_on_one_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("a.jar");
+        let to = dir.path().join("b.jar");
+        fs::write(&from, b"mod").unwrap();
+        move_file(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read(&to).unwrap(), b"mod");
+    }
+
+    #[test]
+    fn generation_path_appends_number() {
+        let p = Path::new("/data/installed_mods.json");
+        assert_eq!(
+            generation_path(p, 2),
+            PathBuf::from("/data/installed_mods.json.2")
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! Switching to a profile: the diff between the mods a profile enables and the mods that are
+//! enabled now, as one [`Plan`] for [`crate::transaction::run`].
+//!
+//! Only mods whose `enabled` flag has to change are touched. Their file moves between the Mods
+//! and `Mods.disabled` folders (getting a ` (1)` suffix if the name is taken there) and their
+//! record is updated; the active profile is set in the same transaction. [`set_enabled_plan`]
+//! does the same for a list of mods without a profile.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::mod_paths::ModRoots;
+use crate::transaction::{FileOp, Plan, RecordChange};
+use crate::{file_name_of, unique_path_by, InstalledModRecord, ProfileRecord};
+
+/// A mod the profile wants switched that could not be.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingMod {
+    pub mod_id: i64,
+    /// `None` if no mod with this id is installed.
+    pub name: Option<String>,
+    pub reason: String,
+}
+
+/// What `apply_profile` changed.
+#[derive(serde::Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyProfileSummary {
+    pub profile_id: i64,
+    /// Updated records of the mods that were enabled.
+    pub enabled: Vec<InstalledModRecord>,
+    /// Updated records of the mods that were disabled.
+    pub disabled: Vec<InstalledModRecord>,
+    /// Mods left as they were.
+    pub missing: Vec<MissingMod>,
+}
+
+/// Plan the switch to `profile` given the installed `mods`. Mods in the profile that are not
+/// installed, and mods whose file is in neither folder, end up in `missing`.
+pub fn plan(
+    profile: &ProfileRecord,
+    mods: &[InstalledModRecord],
+    roots: &ModRoots,
+) -> (Plan, ApplyProfileSummary) {
+    let wanted: HashSet<i64> = profile.enabled_mod_ids.iter().copied().collect();
+    let mut summary = ApplyProfileSummary {
+        profile_id: profile.id,
+        ..Default::default()
+    };
+    let installed: HashSet<i64> = mods.iter().filter_map(|m| m.id).collect();
+    for &id in &profile.enabled_mod_ids {
+        if !installed.contains(&id) {
+            summary.missing.push(MissingMod {
+                mod_id: id,
+                name: None,
+                reason: "not installed".to_string(),
+            });
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut records = Vec::new();
+    let mut taken = HashSet::new();
+    // Disable first, as the frontend used to.
+    let (to_disable, to_enable): (Vec<_>, Vec<_>) = mods
+        .iter()
+        .filter_map(|m| Some((m.id?, m)))
+        .filter(|(id, m)| wanted.contains(id) != m.enabled)
+        .partition(|(_, m)| m.enabled);
+    for (id, m) in to_disable.into_iter().chain(to_enable) {
+        match switch(m, roots, &mut taken) {
+            Ok((file, record)) => {
+                files.extend(file);
+                records.push(RecordChange::PutMod {
+                    record: Box::new(record.clone()),
+                });
+                if record.enabled {
+                    summary.enabled.push(record);
+                } else {
+                    summary.disabled.push(record);
+                }
+            }
+            Err(reason) => summary.missing.push(MissingMod {
+                mod_id: id,
+                name: Some(m.name.clone()),
+                reason,
+            }),
+        }
+    }
+    records.push(RecordChange::SetActiveProfile {
+        id: Some(profile.id),
+    });
+    (Plan { files, records }, summary)
+}
+
+/// Plan to enable (or disable) the mods `ids` together. Mods already in that state are left
+/// alone. Fails if a mod is not installed or its file is in neither folder. Returns the plan and
+/// the records of `ids` as they will be.
+pub fn set_enabled_plan(
+    ids: &[i64],
+    enabled: bool,
+    mods: &[InstalledModRecord],
+    roots: &ModRoots,
+) -> Result<(Plan, Vec<InstalledModRecord>), String> {
+    let mut files = Vec::new();
+    let mut records = Vec::new();
+    let mut updated = Vec::new();
+    let mut taken = HashSet::new();
+    let mut seen = HashSet::new();
+    for &id in ids.iter().filter(|id| seen.insert(**id)) {
+        let m = mods
+            .iter()
+            .find(|m| m.id == Some(id))
+            .ok_or_else(|| format!("Installed mod {id} not found"))?;
+        if m.enabled == enabled {
+            updated.push(m.clone());
+            continue;
+        }
+        let (file, record) =
+            switch(m, roots, &mut taken).map_err(|reason| format!("{}: {reason}", m.name))?;
+        files.extend(file);
+        records.push(RecordChange::PutMod {
+            record: Box::new(record.clone()),
+        });
+        updated.push(record);
+    }
+    Ok((Plan { files, records }, updated))
+}
+
+/// The move (if the file is not already in place) and updated record that flip `m`.
+fn switch(
+    m: &InstalledModRecord,
+    roots: &ModRoots,
+    taken: &mut HashSet<PathBuf>,
+) -> Result<(Option<FileOp>, InstalledModRecord), String> {
+    let (from_dir, to_dir) = if m.enabled {
+        (&roots.mods, &roots.disabled)
+    } else {
+        (&roots.disabled, &roots.mods)
+    };
+    let from = roots.file_in(from_dir, &m.installed_filename)?;
+    let in_place = roots.file_in(to_dir, &m.installed_filename)?;
+    let mut record = m.clone();
+    record.enabled = !m.enabled;
+    if !from.is_file() {
+        if in_place.is_file() {
+            // The file was already moved, only the flag is stale.
+            return Ok((None, record));
+        }
+        return Err(format!("{} was not found", m.installed_filename));
+    }
+    let to = unique_path_by(&in_place, |p| p.exists() || taken.contains(p));
+    taken.insert(to.clone());
+    record.installed_filename = file_name_of(&to);
+    Ok((Some(FileOp::Move { from, to }), record))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn plans_only_the_difference() {
+        let dir = tempfile::tempdir().unwrap();
+        let roots = ModRoots::new(&dir.path().join("Mods"));
+        fs::create_dir_all(&roots.mods).unwrap();
+        fs::create_dir_all(&roots.disabled).unwrap();
+        fs::write(roots.mods.join("keep.jar"), "").unwrap();
+        fs::write(roots.mods.join("off.jar"), "").unwrap();
+        fs::write(roots.disabled.join("on.jar"), "").unwrap();
+        // Another mod's file already has this name in Mods.
+        fs::write(roots.mods.join("on.jar"), "").unwrap();
+        fs::write(roots.mods.join("stale.jar"), "").unwrap();
+        let mods = [
+            InstalledModRecord::for_test(1, "keep.jar", true),
+            InstalledModRecord::for_test(2, "off.jar", true),
+            InstalledModRecord::for_test(3, "on.jar", false),
+            InstalledModRecord::for_test(4, "stale.jar", false),
+            InstalledModRecord::for_test(5, "gone.jar", false),
+            InstalledModRecord::for_test(6, "idle.jar", false),
+        ];
+        let profile = ProfileRecord {
+            id: 7,
+            name: "Survival".to_string(),
+            created_at: String::new(),
+            enabled_mod_ids: vec![1, 3, 4, 5, 99],
+        };
+
+        let (plan, summary) = plan(&profile, &mods, &roots);
+        assert_eq!(
+            plan.files,
+            [
+                FileOp::Move {
+                    from: roots.mods.join("off.jar"),
+                    to: roots.disabled.join("off.jar"),
+                },
+                FileOp::Move {
+                    from: roots.disabled.join("on.jar"),
+                    to: roots.mods.join("on (1).jar"),
+                },
+            ]
+        );
+        // Two moved mods, the stale flag, and the active profile.
+        assert_eq!(plan.records.len(), 4);
+        assert_eq!(summary.profile_id, 7);
+        let ids = |v: &[InstalledModRecord]| v.iter().map(|m| m.id.unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids(&summary.disabled), [2]);
+        assert_eq!(ids(&summary.enabled), [3, 4]);
+        assert_eq!(summary.enabled[0].installed_filename, "on (1).jar");
+        assert!(summary.enabled[0].enabled);
+        assert_eq!(
+            summary.missing,
+            [
+                MissingMod {
+                    mod_id: 99,
+                    name: None,
+                    reason: "not installed".to_string(),
+                },
+                MissingMod {
+                    mod_id: 5,
+                    name: Some("Mod 5".to_string()),
+                    reason: "gone.jar was not found".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn plans_a_bulk_switch() {
+        let dir = tempfile::tempdir().unwrap();
+        let roots = ModRoots::new(&dir.path().join("Mods"));
+        fs::create_dir_all(&roots.mods).unwrap();
+        fs::create_dir_all(&roots.disabled).unwrap();
+        fs::write(roots.mods.join("a.jar"), "").unwrap();
+        fs::write(roots.mods.join("b.jar"), "").unwrap();
+        fs::write(roots.disabled.join("b.jar"), "").unwrap();
+        fs::write(roots.disabled.join("c.jar"), "").unwrap();
+        let mods = [
+            InstalledModRecord::for_test(1, "a.jar", true),
+            InstalledModRecord::for_test(2, "b.jar", true),
+            InstalledModRecord::for_test(3, "c.jar", false),
+        ];
+
+        let (plan, updated) = set_enabled_plan(&[1, 2, 3, 1], false, &mods, &roots).unwrap();
+        assert_eq!(
+            plan.files,
+            [
+                FileOp::Move {
+                    from: roots.mods.join("a.jar"),
+                    to: roots.disabled.join("a.jar"),
+                },
+                FileOp::Move {
+                    from: roots.mods.join("b.jar"),
+                    to: roots.disabled.join("b (1).jar"),
+                },
+            ]
+        );
+        assert_eq!(plan.records.len(), 2);
+        assert_eq!(updated.len(), 3);
+        assert!(updated.iter().all(|m| !m.enabled));
+        assert_eq!(updated[1].installed_filename, "b (1).jar");
+
+        assert!(set_enabled_plan(&[9], false, &mods, &roots).is_err());
+        fs::remove_file(roots.disabled.join("c.jar")).unwrap();
+        let err = set_enabled_plan(&[1, 3], true, &mods, &roots).unwrap_err();
+        assert_eq!(err, "Mod 3: c.jar was not found");
+    }
+}
@@ -0,0 +1,195 @@
+//! Integrity checks for `ProfilesData`.
+//!
+//! `repair` fixes what can be fixed without guessing (stale `next_id`, duplicate ids, an active
+//! profile that no longer exists, mod ids that are no longer installed). `check` is the strict
+//! variant used on writes from the frontend, where structural problems indicate a bug and are
+//! rejected instead.
+
+use std::collections::HashSet;
+
+use crate::ProfilesData;
+
+/// A profile entry pointing at a mod id that is not installed.
+#[derive(serde::Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DanglingModRef {
+    pub profile_id: i64,
+    pub mod_id: i64,
+}
+
+/// What `repair` changed.
+#[derive(serde::Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesValidation {
+    /// Human-readable descriptions of each repair.
+    pub repairs: Vec<String>,
+    /// Mod references removed because the mod is no longer installed.
+    pub dangling_mod_ids: Vec<DanglingModRef>,
+}
+
+impl ProfilesValidation {
+    pub fn is_clean(&self) -> bool {
+        self.repairs.is_empty() && self.dangling_mod_ids.is_empty()
+    }
+}
+
+fn max_profile_id(data: &ProfilesData) -> i64 {
+    data.profiles.iter().map(|p| p.id).max().unwrap_or(0)
+}
+
+/// Reject structurally inconsistent data: duplicate profile ids or an unknown active profile.
+pub fn check(data: &ProfilesData) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for p in &data.profiles {
+        if !seen.insert(p.id) {
+            return Err(format!("Duplicate profile id {}", p.id));
+        }
+    }
+    if let Some(active) = data.active_profile_id {
+        if !seen.contains(&active) {
+            return Err(format!("Active profile {active} does not exist"));
+        }
+    }
+    Ok(())
+}
+
+/// Repair `data` in place. Mod references are only checked when `installed` is given.
+pub fn repair(data: &mut ProfilesData, installed: Option<&HashSet<i64>>) -> ProfilesValidation {
+    let mut report = ProfilesValidation::default();
+
+    let mut seen = HashSet::new();
+    let mut next_free = max_profile_id(data).max(data.next_id - 1) + 1;
+    for p in data.profiles.iter_mut() {
+        if !seen.insert(p.id) {
+            report.repairs.push(format!(
+                "Profile \"{}\" had duplicate id {}; assigned id {next_free}",
+                p.name, p.id
+            ));
+            p.id = next_free;
+            seen.insert(p.id);
+            next_free += 1;
+        }
+    }
+
+    let min_next = max_profile_id(data) + 1;
+    if data.next_id < min_next {
+        report.repairs.push(format!(
+            "nextId {} was not above every profile id; set to {min_next}",
+            data.next_id
+        ));
+        data.next_id = min_next;
+    }
+
+    if let Some(active) = data.active_profile_id {
+        if !seen.contains(&active) {
+            report
+                .repairs
+                .push(format!("Active profile {active} does not exist; cleared"));
+            data.active_profile_id = None;
+        }
+    }
+
+    for p in data.profiles.iter_mut() {
+        let mut unique = HashSet::new();
+        let before = p.enabled_mod_ids.len();
+        p.enabled_mod_ids.retain(|id| unique.insert(*id));
+        if p.enabled_mod_ids.len() != before {
+            report.repairs.push(format!(
+                "Profile \"{}\" listed the same mod more than once",
+                p.name
+            ));
+        }
+        if let Some(installed) = installed {
+            let profile_id = p.id;
+            p.enabled_mod_ids.retain(|id| {
+                let keep = installed.contains(id);
+                if !keep {
+                    report.dangling_mod_ids.push(DanglingModRef {
+                        profile_id,
+                        mod_id: *id,
+                    });
+                }
+                keep
+            });
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProfileRecord;
+
+    fn profile(id: i64, mods: &[i64]) -> ProfileRecord {
+        ProfileRecord {
+            id,
+            name: format!("P{id}"),
+            created_at: "t".to_string(),
+            enabled_mod_ids: mods.to_vec(),
+        }
+    }
+
+    #[test]
+    fn repairs_ids_and_active_profile() {
+        let mut data = ProfilesData {
+            next_id: 2,
+            active_profile_id: Some(9),
+            profiles: vec![profile(1, &[]), profile(3, &[]), profile(3, &[])],
+        };
+        let report = repair(&mut data, None);
+        let ids: Vec<i64> = data.profiles.iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+        assert_eq!(data.next_id, 5);
+        assert_eq!(data.active_profile_id, None);
+        assert_eq!(report.repairs.len(), 3);
+        assert!(check(&data).is_ok());
+    }
+
+    #[test]
+    fn prunes_dangling_and_duplicate_mod_ids() {
+        let mut data = ProfilesData {
+            next_id: 2,
+            active_profile_id: Some(1),
+            profiles: vec![profile(1, &[1, 2, 2, 5])],
+        };
+        let installed: HashSet<i64> = [1, 2].into_iter().collect();
+        let report = repair(&mut data, Some(&installed));
+        assert_eq!(data.profiles[0].enabled_mod_ids, vec![1, 2]);
+        assert_eq!(
+            report.dangling_mod_ids,
+            vec![DanglingModRef {
+                profile_id: 1,
+                mod_id: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn clean_data_is_untouched() {
+        let mut data = ProfilesData {
+            next_id: 3,
+            active_profile_id: Some(2),
+            profiles: vec![profile(1, &[1]), profile(2, &[])],
+        };
+        let installed: HashSet<i64> = [1].into_iter().collect();
+        assert!(repair(&mut data, Some(&installed)).is_clean());
+    }
+
+    #[test]
+    fn check_rejects_structural_problems() {
+        let dup = ProfilesData {
+            next_id: 3,
+            active_profile_id: None,
+            profiles: vec![profile(1, &[]), profile(1, &[])],
+        };
+        assert!(check(&dup).is_err());
+        let bad_active = ProfilesData {
+            next_id: 2,
+            active_profile_id: Some(7),
+            profiles: vec![profile(1, &[])],
+        };
+        assert!(check(&bad_active).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Recovery for app data files that no longer parse.
+//!
+//! The broken file is moved aside as `<stem>.corrupt-<timestamp>.json`, then the newest backup
+//! generation that still parses is restored. If there is none, the caller's fallback is used
+//! (e.g. rebuilding the installed list from the Mods folders).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::schema::{self, DocSchema, ReadError};
+use crate::{download, mod_paths, persist, InstalledModRecord};
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecoveryAction {
+    /// A previous generation was restored.
+    RestoredBackup,
+    /// No usable backup; records were rebuilt by scanning the Mods folders.
+    RebuiltFromDisk,
+    /// No usable backup and nothing to rebuild from; started empty.
+    Reset,
+    /// No usable backup of the database; a new one was created, which imports the legacy
+    /// `installed_mods.json` and `profiles.json` again.
+    Recreated,
+}
+
+/// What happened when a file had to be recovered. Sent to the frontend as-is.
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryReport {
+    pub file: String,
+    pub error: String,
+    pub quarantined_path: String,
+    pub action: RecoveryAction,
+    pub backup_path: Option<String>,
+}
+
+/// Document contents plus a recovery report if the file had to be repaired.
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadResult<T> {
+    pub data: T,
+    pub recovered: Option<RecoveryReport>,
+}
+
+/// `installed_mods.json` -> `installed_mods.corrupt-20250101T120000Z.json`
+pub fn quarantine_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("data");
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("json");
+    let ts = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+    let mut target = path.with_file_name(format!("{stem}.corrupt-{ts}.{ext}"));
+    let mut n = 1;
+    while target.exists() {
+        target = path.with_file_name(format!("{stem}.corrupt-{ts}-{n}.{ext}"));
+        n += 1;
+    }
+    target
+}
+
+/// Newest backup generation of `path` that parses, if any.
+fn newest_valid_backup<T: DeserializeOwned>(
+    schema: &DocSchema,
+    path: &Path,
+) -> Option<(PathBuf, T)> {
+    (1..=persist::GENERATIONS)
+        .map(|n| persist::generation_path(path, n))
+        .filter(|p| p.is_file())
+        .find_map(|p| {
+            let text = fs::read_to_string(&p).ok()?;
+            let (_, value) = schema::parse::<T>(schema, &text).ok()?;
+            Some((p, value))
+        })
+}
+
+/// Read a document, recovering from a corrupt file instead of failing.
+/// `fallback` is used when no backup is usable; returning `None` means "start empty".
+pub fn read_or_recover<T, F>(
+    schema: &DocSchema,
+    path: &Path,
+    fallback: F,
+) -> Result<LoadResult<Option<T>>, String>
+where
+    T: DeserializeOwned + Serialize,
+    F: FnOnce() -> Option<T>,
+{
+    let error = match schema::read::<T>(schema, path) {
+        Ok(data) => {
+            return Ok(LoadResult {
+                data,
+                recovered: None,
+            })
+        }
+        Err(ReadError::Corrupt(e)) => e,
+        Err(e) => return Err(e.into()),
+    };
+    let quarantined = quarantine_path(path);
+    fs::rename(path, &quarantined).map_err(|e| e.to_string())?;
+    eprintln!(
+        "[recovery] {} file is corrupt ({error}); moved to {}",
+        schema.name,
+        quarantined.display()
+    );
+
+    let (data, action, backup_path) = match newest_valid_backup::<T>(schema, path) {
+        Some((backup, value)) => (Some(value), RecoveryAction::RestoredBackup, Some(backup)),
+        None => match fallback() {
+            Some(value) => (Some(value), RecoveryAction::RebuiltFromDisk, None),
+            None => (None, RecoveryAction::Reset, None),
+        },
+    };
+    if let Some(ref value) = data {
+        schema::write(schema, path, value)?;
+    }
+    Ok(LoadResult {
+        data,
+        recovered: Some(RecoveryReport {
+            file: schema.name.to_string(),
+            error,
+            quarantined_path: quarantined.to_string_lossy().into_owned(),
+            action,
+            backup_path: backup_path.map(|p| p.to_string_lossy().into_owned()),
+        }),
+    })
+}
+
+fn scan_mod_files(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| e.file_name().to_str().map(str::to_string))
+        .filter(|n| !n.starts_with('.') && !download::is_partial_download(n))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Best-effort installed list from the files in Mods and Mods.disabled.
+/// Records are untracked (no provider ids), like the ones added from "Rescan folders".
+pub fn rebuild_from_mods_dirs(mods_dir: &Path) -> Vec<InstalledModRecord> {
+    let disabled_dir = mod_paths::disabled_dir(mods_dir);
+    let now = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let enabled = scan_mod_files(mods_dir).into_iter().map(|n| (n, true));
+    let disabled = scan_mod_files(&disabled_dir)
+        .into_iter()
+        .map(|n| (n, false));
+    enabled
+        .chain(disabled)
+        .enumerate()
+        .map(|(i, (filename, enabled))| InstalledModRecord {
+            id: Some(i as i64 + 1),
+            provider: "orbis".to_string(),
+            project_id: None,
+            resource_id: None,
+            slug: "__untracked__".to_string(),
+            name: filename.clone(),
+            installed_file_id: None,
+            installed_filename: filename,
+            installed_at: now.clone(),
+            source_url: None,
+            enabled,
+            pinned: false,
+            file_hash: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::INSTALLED_MODS;
+
+    fn quarantined_files(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .filter(|n| n.contains(".corrupt-"))
+            .collect()
+    }
+
+    fn untracked(n: usize) -> Vec<InstalledModRecord> {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..n {
+            fs::write(dir.path().join(format!("{i}.jar")), "").unwrap();
+        }
+        rebuild_from_mods_dirs(dir.path())
+    }
+
+    #[test]
+    fn skips_corrupt_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installed_mods.json");
+        for n in 1..=3 {
+            schema::write(&INSTALLED_MODS, &path, &untracked(n)).unwrap();
+        }
+        fs::write(persist::generation_path(&path, 1), "{not json").unwrap();
+        fs::write(&path, "[{\"id\": 1,").unwrap();
+
+        let res = read_or_recover::<Vec<InstalledModRecord>, _>(&INSTALLED_MODS, &path, || {
+            panic!("fallback should not run")
+        })
+        .unwrap();
+        assert_eq!(res.data.unwrap().len(), 1);
+        let report = res.recovered.unwrap();
+        assert_eq!(report.action, RecoveryAction::RestoredBackup);
+        assert!(report
+            .backup_path
+            .unwrap()
+            .ends_with("installed_mods.json.2"));
+    }
+
+    #[test]
+    fn quarantines_and_uses_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installed_mods.json");
+        schema::write(&INSTALLED_MODS, &path, &untracked(2)).unwrap();
+        schema::write(&INSTALLED_MODS, &path, &untracked(1)).unwrap();
+        fs::write(&path, "[{\"id\": 1,").unwrap();
+
+        let res =
+            read_or_recover::<Vec<InstalledModRecord>, _>(&INSTALLED_MODS, &path, || None).unwrap();
+        let report = res.recovered.unwrap();
+        assert_eq!(report.action, RecoveryAction::RestoredBackup);
+        assert!(report
+            .backup_path
+            .unwrap()
+            .ends_with("installed_mods.json.1"));
+        assert_eq!(res.data.unwrap().len(), 2);
+        assert_eq!(quarantined_files(dir.path()).len(), 1);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn rebuilds_from_mods_dirs_without_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        // A dot in the folder name must not be taken for an extension.
+        let mods_dir = dir.path().join("Hytale.Mods");
+        let disabled_dir = dir.path().join("Hytale.Mods.disabled");
+        fs::create_dir_all(&mods_dir).unwrap();
+        fs::create_dir_all(&disabled_dir).unwrap();
+        fs::write(mods_dir.join("a.jar"), "").unwrap();
+        fs::write(mods_dir.join("a.tmp"), "").unwrap();
+        fs::write(disabled_dir.join("b.jar"), "").unwrap();
+        let path = dir.path().join("installed_mods.json");
+        fs::write(&path, "garbage").unwrap();
+
+        let res = read_or_recover(&INSTALLED_MODS, &path, || {
+            Some(rebuild_from_mods_dirs(&mods_dir))
+        })
+        .unwrap();
+        let mods = res.data.unwrap();
+        assert_eq!(
+            res.recovered.unwrap().action,
+            RecoveryAction::RebuiltFromDisk
+        );
+        assert_eq!(mods.len(), 2);
+        assert!(mods[0].enabled && mods[0].installed_filename == "a.jar");
+        assert!(!mods[1].enabled && mods[1].installed_filename == "b.jar");
+    }
+
+    #[test]
+    fn valid_file_is_not_recovered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installed_mods.json");
+        schema::write::<Vec<InstalledModRecord>>(&INSTALLED_MODS, &path, &Vec::new()).unwrap();
+        let res =
+            read_or_recover::<Vec<InstalledModRecord>, _>(&INSTALLED_MODS, &path, || None).unwrap();
+        assert!(res.recovered.is_none());
+        assert!(quarantined_files(dir.path()).is_empty());
+    }
+}
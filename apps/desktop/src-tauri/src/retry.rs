@@ -0,0 +1,214 @@
+//! Retries with exponential backoff for transient download failures.
+//!
+//! Connection errors, timeouts, 429 and 5xx responses (and transfers cut off midway) are
+//! retried; other 4xx responses and integrity errors fail on the first attempt. The delay
+//! doubles with every attempt up to a cap, and half of it is random so parallel downloads do
+//! not retry in lockstep. A `Retry-After` header is honoured when the server sends one.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const MAX_RETRIES: u32 = 10;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// A server asking for a longer wait is not retried; the download fails instead.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+/// How often [`wait`] checks whether the download was stopped.
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts after the first one.
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::with_retries(DEFAULT_MAX_RETRIES)
+    }
+}
+
+impl RetryPolicy {
+    /// Fail on the first error.
+    pub const NONE: Self = Self {
+        max_retries: 0,
+        base_delay: BASE_DELAY,
+        max_delay: MAX_DELAY,
+    };
+
+    pub fn with_retries(max_retries: u32) -> Self {
+        Self {
+            max_retries: max_retries.min(MAX_RETRIES),
+            ..Self::NONE
+        }
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_retries + 1
+    }
+
+    /// Time to wait after attempt number `attempt` (1-based) failed. `None` if the server asked
+    /// for a wait longer than we are willing to block a download slot for.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        let backoff = self
+            .base_delay
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        let jittered = backoff / 2 + (backoff / 2).mul_f64(random_unit());
+        match retry_after {
+            Some(wait) if wait > MAX_RETRY_AFTER => None,
+            Some(wait) => Some(wait.max(jittered)),
+            None => Some(jittered),
+        }
+    }
+}
+
+/// Uniform-ish value in `[0, 1]`. `RandomState` is seeded randomly per instance, which is plenty
+/// for spreading out retries.
+fn random_unit() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+/// Why a download attempt failed.
+#[derive(Debug, PartialEq)]
+pub enum Failure {
+    /// Worth another attempt, after `retry_after` if the server said so.
+    Transient {
+        error: String,
+        retry_after: Option<Duration>,
+    },
+    Fatal(String),
+}
+
+impl Failure {
+    pub fn transient(error: String) -> Self {
+        Self::Transient {
+            error,
+            retry_after: None,
+        }
+    }
+
+    /// Failure for a response with a non-success status.
+    pub fn from_response(resp: &reqwest::blocking::Response, error: String) -> Self {
+        if !is_transient_status(resp.status()) {
+            return Self::Fatal(error);
+        }
+        let retry_after = resp
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| parse_retry_after(v, SystemTime::now()));
+        Self::Transient { error, retry_after }
+    }
+
+    /// Connect errors and timeouts are transient; redirect loops, bad URLs and the like are not.
+    pub fn from_reqwest(e: reqwest::Error) -> Self {
+        if e.is_redirect() {
+            // The redirect policy's reason (e.g. a host outside the allowlist) is only in the source.
+            let reason = std::error::Error::source(&e).map(|s| s.to_string());
+            return Self::Fatal(reason.unwrap_or_else(|| e.to_string()));
+        }
+        if e.is_connect() || e.is_timeout() || e.is_request() || e.is_body() {
+            Self::transient(e.to_string())
+        } else {
+            Self::Fatal(e.to_string())
+        }
+    }
+}
+
+impl From<String> for Failure {
+    fn from(error: String) -> Self {
+        Self::Fatal(error)
+    }
+}
+
+pub fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at: SystemTime = chrono::DateTime::parse_from_rfc2822(value).ok()?.into();
+    Some(at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+/// Sleep for `delay` unless `stop` returns true first. Returns false if stopped.
+pub fn wait(delay: Duration, stop: &dyn Fn() -> bool) -> bool {
+    let mut left = delay;
+    while !left.is_zero() {
+        if stop() {
+            return false;
+        }
+        let step = left.min(STOP_POLL);
+        thread::sleep(step);
+        left -= step;
+    }
+    !stop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_grows_exponentially_with_jitter() {
+        let policy = RetryPolicy::with_retries(5);
+        for attempt in 1..=8 {
+            let backoff = (BASE_DELAY * 2u32.pow(attempt - 1)).min(MAX_DELAY);
+            let delay = policy.delay(attempt, None).unwrap();
+            assert!(
+                delay >= backoff / 2 && delay <= backoff,
+                "{attempt}: {delay:?}"
+            );
+        }
+        assert_eq!(RetryPolicy::with_retries(99).max_retries, MAX_RETRIES);
+    }
+
+    #[test]
+    fn honours_retry_after() {
+        let policy = RetryPolicy::default();
+        let wait = Duration::from_secs(45);
+        assert_eq!(policy.delay(1, Some(wait)), Some(wait));
+        assert!(policy.delay(1, Some(Duration::ZERO)).unwrap() >= BASE_DELAY / 2);
+        assert_eq!(policy.delay(1, Some(MAX_RETRY_AFTER * 2)), None);
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(784_111_777);
+        assert_eq!(
+            parse_retry_after(" 120 ", now),
+            Some(Duration::from_secs(120))
+        );
+        // 1994-11-06T08:49:37Z is 784111777.
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:50:07 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_transient_status(StatusCode::BAD_GATEWAY));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+        assert!(!is_transient_status(StatusCode::FORBIDDEN));
+    }
+}
@@ -0,0 +1,162 @@
+//! Where the generic file commands may read, write and trash files.
+//!
+//! `read_text_file`, `write_text_file` and `move_file_to_trash` take any path from the webview.
+//! They are limited to the `files` folder under app data (the settings, database and journal
+//! next to it stay out of reach), the configured Mods folders, and paths the user chose in a
+//! native file dialog opened by the backend (`pick_open_path` / `pick_save_path`) during this
+//! session. The webview cannot add to that list itself.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Error code prefix of a path outside the sandbox.
+pub const PATH_NOT_ALLOWED: &str = "PATH_NOT_ALLOWED";
+/// Folder under app data the file commands may use.
+pub const FILES_DIRNAME: &str = "files";
+
+/// Absolute form of `path` with the part that exists canonicalized, so a symlink inside a
+/// folder cannot point out of it. Nothing that does not exist yet is touched. The error is the
+/// reason only (e.g. `contains ".."`), for the caller to prefix.
+pub fn resolve(path: &Path) -> Result<PathBuf, String> {
+    if !path.is_absolute() {
+        return Err("is not an absolute path".to_string());
+    }
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err("contains \"..\"".to_string());
+    }
+    let mut existing = path.to_path_buf();
+    let mut rest = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => return Ok(rest.iter().rev().fold(canonical, |p, c| p.join(c))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let Some(name) = existing.file_name() else {
+                    return Err("has no existing parent".to_string());
+                };
+                rest.push(name.to_os_string());
+                existing.pop();
+            }
+            Err(e) => return Err(format!("cannot be resolved: {e}")),
+        }
+    }
+}
+
+/// Paths picked in a backend file dialog this session. Kept in Tauri managed state.
+#[derive(Default)]
+pub struct PickedPaths(Mutex<Vec<PathBuf>>);
+
+impl PickedPaths {
+    pub fn add(&self, path: PathBuf) {
+        let mut picked = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !picked.contains(&path) {
+            picked.push(path);
+        }
+    }
+
+    pub fn list(&self) -> Vec<PathBuf> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// The allowed locations for one command call.
+#[derive(Debug, Default)]
+pub struct Sandbox {
+    /// Anything inside these folders is allowed, but not the folders themselves.
+    roots: Vec<PathBuf>,
+    /// These paths and anything inside them (for a picked folder) are allowed.
+    picked: Vec<PathBuf>,
+}
+
+impl Sandbox {
+    pub fn new(roots: Vec<PathBuf>, picked: Vec<PathBuf>) -> Self {
+        Self { roots, picked }
+    }
+
+    /// Returns `path` if it is allowed, else an error starting with [`PATH_NOT_ALLOWED`].
+    pub fn check(&self, path: &Path) -> Result<PathBuf, String> {
+        let denied = |why: &str| format!("{PATH_NOT_ALLOWED}: {} {why}", path.display());
+        let resolved = resolve(path).map_err(|why| denied(&why))?;
+        let inside = |root: &PathBuf, or_equal: bool| {
+            resolve(root)
+                .is_ok_and(|root| resolved.starts_with(&root) && (or_equal || resolved != root))
+        };
+        if self.roots.iter().any(|r| inside(r, false))
+            || self.picked.iter().any(|p| inside(p, true))
+        {
+            return Ok(path.to_path_buf());
+        }
+        Err(denied(
+            "is not in the app's files folder or a Mods folder, nor chosen in a file dialog",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn allows_roots_and_picked_paths_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_data = dir.path().join("app-data");
+        let files = app_data.join(FILES_DIRNAME);
+        let mods = dir.path().join("Mods");
+        let export = dir.path().join("Documents/profile.json");
+        let picked_dir = dir.path().join("Shared");
+        for d in [&app_data, &mods, &picked_dir] {
+            fs::create_dir_all(d).unwrap();
+        }
+        let picked = PickedPaths::default();
+        picked.add(export.clone());
+        picked.add(picked_dir.clone());
+        picked.add(export.clone());
+        assert_eq!(picked.list().len(), 2);
+        let sandbox = Sandbox::new(vec![files.clone(), mods.clone()], picked.list());
+
+        for ok in [
+            files.join("profiles.json"),
+            mods.join("a.jar"),
+            mods.join("new/b.jar"),
+            export.clone(),
+            picked_dir.clone(),
+            picked_dir.join("x.json"),
+        ] {
+            assert_eq!(sandbox.check(&ok).unwrap(), ok);
+        }
+        for bad in [
+            app_data.join("settings.json"),
+            app_data.join("hyghertales.db"),
+            files.clone(),
+            mods.clone(),
+            dir.path().join("Documents/other.json"),
+            dir.path().join("Mods2/a.jar"),
+            mods.join("../escape.json"),
+            PathBuf::from("relative.json"),
+            PathBuf::from("/etc/passwd"),
+        ] {
+            let err = sandbox.check(&bad).unwrap_err();
+            assert!(
+                err.starts_with(PATH_NOT_ALLOWED),
+                "{}: {err}",
+                bad.display()
+            );
+        }
+        let empty = Sandbox::default();
+        assert!(empty.check(&export).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cannot_lead_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let app_data = dir.path().join("app-data");
+        fs::create_dir_all(&app_data).unwrap();
+        std::os::unix::fs::symlink("/etc", app_data.join("etc")).unwrap();
+
+        let sandbox = Sandbox::new(vec![app_data.clone()], Vec::new());
+        let err = sandbox.check(&app_data.join("etc/passwd")).unwrap_err();
+        assert!(err.starts_with(PATH_NOT_ALLOWED), "{err}");
+    }
+}
@@ -0,0 +1,297 @@
+//! Versioned envelope and migrations for the JSON files in the app data dir.
+//!
+//! Files are stored as `{"schemaVersion": N, "data": ...}`. Anything without the envelope is
+//! treated as version 1 (the original bare array/object format). Older files are upgraded on
+//! read, after a copy of the original is written next to them as `<file>.v<N>.bak`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::persist;
+
+type Migration = fn(Value) -> Result<Value, String>;
+
+/// Describes one persisted document and how to upgrade it.
+pub struct DocSchema {
+    pub name: &'static str,
+    /// `migrations[i]` upgrades data from version `i + 1` to `i + 2`.
+    pub migrations: &'static [Migration],
+}
+
+impl DocSchema {
+    pub fn current_version(&self) -> u32 {
+        self.migrations.len() as u32 + 1
+    }
+}
+
+/// installed_mods.json
+/// - v1: bare array, `pinned` may be missing.
+/// - v2: envelope, every record has `pinned`.
+pub const INSTALLED_MODS: DocSchema = DocSchema {
+    name: "installed mods",
+    migrations: &[installed_mods_v1_to_v2],
+};
+
+/// profiles.json
+/// - v1: bare `ProfilesData` object.
+/// - v2: envelope, unchanged data.
+pub const PROFILES: DocSchema = DocSchema {
+    name: "profiles",
+    migrations: &[profiles_v1_to_v2],
+};
+
+/// settings.json
+/// - v1: envelope around `Settings`.
+pub const SETTINGS: DocSchema = DocSchema {
+    name: "settings",
+    migrations: &[],
+};
+
+/// download-cache/index.json
+/// - v1: envelope around the cache index.
+pub const DOWNLOAD_CACHE: DocSchema = DocSchema {
+    name: "download cache",
+    migrations: &[],
+};
+
+fn installed_mods_v1_to_v2(data: Value) -> Result<Value, String> {
+    let Value::Array(mut mods) = data else {
+        return Err("expected an array of installed mods".to_string());
+    };
+    for m in mods.iter_mut() {
+        if let Value::Object(obj) = m {
+            obj.entry("pinned").or_insert(Value::Bool(false));
+        }
+    }
+    Ok(Value::Array(mods))
+}
+
+fn profiles_v1_to_v2(data: Value) -> Result<Value, String> {
+    if !data.is_object() {
+        return Err("expected a profiles object".to_string());
+    }
+    Ok(data)
+}
+
+/// Why a document could not be read.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The file could not be read from disk.
+    Io(String),
+    /// The contents are not a valid document (truncated, malformed, wrong shape).
+    Corrupt(String),
+    /// The file was written by a newer app with a schema this build does not know.
+    TooNew(String),
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(e) | ReadError::Corrupt(e) | ReadError::TooNew(e) => f.write_str(e),
+        }
+    }
+}
+
+impl From<ReadError> for String {
+    fn from(e: ReadError) -> Self {
+        e.to_string()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EnvelopeRef<'a, T> {
+    schema_version: u32,
+    data: &'a T,
+}
+
+/// Splits raw file contents into (version, data). Bare documents are version 1.
+fn decode(text: &str) -> Result<(u32, Value), ReadError> {
+    let value: Value = serde_json::from_str(text).map_err(|e| ReadError::Corrupt(e.to_string()))?;
+    if let Value::Object(ref obj) = value {
+        if let Some(v) = obj.get("schemaVersion") {
+            let version = v
+                .as_u64()
+                .filter(|v| *v >= 1)
+                .ok_or_else(|| ReadError::Corrupt("Invalid schemaVersion".to_string()))?;
+            let version = u32::try_from(version).map_err(|_| {
+                ReadError::TooNew(format!(
+                    "The file was written by a newer version of HygherTales (schema v{version}). Update the app to open it."
+                ))
+            })?;
+            let data = obj.get("data").cloned().unwrap_or(Value::Null);
+            return Ok((version, data));
+        }
+    }
+    Ok((1, value))
+}
+
+/// Runs the migration chain from `version` up to the current version.
+fn upgrade(schema: &DocSchema, version: u32, mut data: Value) -> Result<Value, ReadError> {
+    let current = schema.current_version();
+    if version > current {
+        return Err(ReadError::TooNew(format!(
+            "The {} file was written by a newer version of HygherTales (schema v{version}, this app supports v{current}). Update the app to open it.",
+            schema.name
+        )));
+    }
+    for (i, migrate) in schema
+        .migrations
+        .iter()
+        .enumerate()
+        .skip(version as usize - 1)
+    {
+        data = migrate(data).map_err(|e| {
+            ReadError::Corrupt(format!(
+                "Failed to migrate {} from v{} to v{}: {e}",
+                schema.name,
+                i + 1,
+                i + 2
+            ))
+        })?;
+    }
+    Ok(data)
+}
+
+/// Parse raw file contents into `T`, upgrading older versions in memory.
+/// Returns the version found in the file alongside the value.
+pub fn parse<T: DeserializeOwned>(schema: &DocSchema, text: &str) -> Result<(u32, T), ReadError> {
+    let (version, data) = decode(text)?;
+    let data = upgrade(schema, version, data)?;
+    let value = serde_json::from_value(data).map_err(|e| ReadError::Corrupt(e.to_string()))?;
+    Ok((version, value))
+}
+
+/// Path of the copy written before migrating a file from `version`.
+pub fn pre_migration_backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{version}.bak"));
+    path.with_file_name(name)
+}
+
+/// Read a versioned document. Returns `None` if the file does not exist.
+/// Older files are backed up and rewritten in the current format.
+pub fn read<T>(schema: &DocSchema, path: &Path) -> Result<Option<T>, ReadError>
+where
+    T: DeserializeOwned + Serialize,
+{
+    if !path.exists() {
+        return Ok(None);
+    }
+    let bytes = fs::read(path).map_err(|e| ReadError::Io(e.to_string()))?;
+    let text = String::from_utf8(bytes).map_err(|e| ReadError::Corrupt(e.to_string()))?;
+    let (version, value) = parse::<T>(schema, &text)?;
+    if version < schema.current_version() {
+        let backup = pre_migration_backup_path(path, version);
+        if !backup.exists() {
+            fs::write(&backup, &text).map_err(|e| ReadError::Io(e.to_string()))?;
+        }
+        write(schema, path, &value).map_err(ReadError::Io)?;
+    }
+    Ok(Some(value))
+}
+
+/// Write a document in the current versioned envelope.
+pub fn write<T: Serialize>(schema: &DocSchema, path: &Path, data: &T) -> Result<(), String> {
+    persist::write_json(
+        path,
+        &EnvelopeRef {
+            schema_version: schema.current_version(),
+            data,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InstalledModRecord, ProfilesData};
+
+    const BARE_WITHOUT_PINNED: &str = r#"[{
+        "id": 1, "provider": "curseforge", "projectId": 42, "resourceId": null,
+        "slug": "foo", "name": "Foo", "installedFileId": 7, "installedFilename": "foo.jar",
+        "installedAt": "2025-01-01T00:00:00Z", "sourceUrl": null, "enabled": true
+    }]"#;
+
+    const BARE_WITH_PINNED: &str = r#"[{
+        "id": 2, "provider": "orbis", "projectId": null, "resourceId": "abc",
+        "slug": "bar", "name": "Bar", "installedFileId": "v1:0", "installedFilename": "bar.jar",
+        "installedAt": "2025-01-01T00:00:00Z", "sourceUrl": null, "enabled": false, "pinned": true
+    }]"#;
+
+    const BARE_PROFILES: &str = r#"{
+        "nextId": 2, "activeProfileId": 1,
+        "profiles": [{"id": 1, "name": "Default", "createdAt": "2025-01-01T00:00:00Z", "enabledModIds": [1]}]
+    }"#;
+
+    #[test]
+    fn loads_bare_array_without_pinned() {
+        let (version, mods) =
+            parse::<Vec<InstalledModRecord>>(&INSTALLED_MODS, BARE_WITHOUT_PINNED).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(mods.len(), 1);
+        assert!(!mods[0].pinned);
+    }
+
+    #[test]
+    fn loads_bare_array_with_pinned() {
+        let (version, mods) =
+            parse::<Vec<InstalledModRecord>>(&INSTALLED_MODS, BARE_WITH_PINNED).unwrap();
+        assert_eq!(version, 1);
+        assert!(mods[0].pinned);
+    }
+
+    #[test]
+    fn loads_current_envelope() {
+        let text = format!(r#"{{"schemaVersion": 2, "data": {BARE_WITH_PINNED}}}"#);
+        let (version, mods) = parse::<Vec<InstalledModRecord>>(&INSTALLED_MODS, &text).unwrap();
+        assert_eq!(version, 2);
+        assert_eq!(mods[0].slug, "bar");
+    }
+
+    #[test]
+    fn loads_bare_profiles_object() {
+        let (version, data) = parse::<ProfilesData>(&PROFILES, BARE_PROFILES).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(data.active_profile_id, Some(1));
+        assert_eq!(data.profiles[0].enabled_mod_ids, vec![1]);
+    }
+
+    #[test]
+    fn refuses_newer_schema() {
+        for text in [
+            r#"{"schemaVersion": 99, "data": []}"#,
+            // Would be v1 if truncated to 32 bits.
+            r#"{"schemaVersion": 4294967297, "data": []}"#,
+        ] {
+            let err = parse::<Vec<InstalledModRecord>>(&INSTALLED_MODS, text).unwrap_err();
+            assert!(matches!(err, ReadError::TooNew(_)), "{err}");
+        }
+    }
+
+    #[test]
+    fn read_upgrades_file_and_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("installed_mods.json");
+        fs::write(&path, BARE_WITHOUT_PINNED).unwrap();
+
+        let mods: Vec<InstalledModRecord> = read(&INSTALLED_MODS, &path).unwrap().unwrap();
+        assert_eq!(mods.len(), 1);
+
+        let backup = pre_migration_backup_path(&path, 1);
+        assert_eq!(fs::read_to_string(backup).unwrap(), BARE_WITHOUT_PINNED);
+        let (version, _) = decode(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(version, INSTALLED_MODS.current_version());
+    }
+
+    #[test]
+    fn read_missing_file_is_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles.json");
+        assert!(read::<ProfilesData>(&PROFILES, &path).unwrap().is_none());
+    }
+}
@@ -0,0 +1,463 @@
+//! User settings, persisted as `settings.json` in the app data dir.
+//!
+//! These used to live in webview localStorage, where Rust commands could not see them. The
+//! frontend hands its old values to [`load_or_init`] on startup; they are only used if no
+//! settings file exists yet, so the migration happens exactly once.
+
+use std::path::Path;
+
+use crate::http;
+use crate::recovery::{self, LoadResult};
+use crate::retry::{self, RetryPolicy};
+use crate::schema;
+
+pub const SETTINGS_FILENAME: &str = "settings.json";
+/// Tauri event carrying the [`Settings`] when Rust saved them without the frontend asking.
+pub const CHANGED_EVENT: &str = "settings-changed";
+pub const DEFAULT_PROXY_BASE_URL: &str = "http://localhost:8787";
+pub const DEFAULT_DOWNLOAD_CACHE_LIMIT_MB: u64 = 1024;
+
+fn default_proxy_base_url() -> String {
+    DEFAULT_PROXY_BASE_URL.to_string()
+}
+
+fn default_download_cache_limit_mb() -> u64 {
+    DEFAULT_DOWNLOAD_CACHE_LIMIT_MB
+}
+
+fn default_download_max_retries() -> u32 {
+    retry::DEFAULT_MAX_RETRIES
+}
+
+fn default_http_connect_timeout_secs() -> u64 {
+    http::DEFAULT_CONNECT_TIMEOUT_SECS
+}
+
+fn default_http_read_timeout_secs() -> u64 {
+    http::DEFAULT_READ_TIMEOUT_SECS
+}
+
+/// Matches the TS `Settings` interface.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Settings {
+    /// HygherTales proxy for searches and download links. Only changed through [`modify`];
+    /// [`update`] keeps the saved value.
+    #[serde(default = "default_proxy_base_url")]
+    pub proxy_base_url: String,
+    #[serde(default)]
+    pub hytale_user_data_path: Option<String>,
+    /// Only changed through [`modify`], with a folder the user picked or confirmed in a native
+    /// dialog; [`update`] keeps the saved value.
+    #[serde(default)]
+    pub mods_dir_path: Option<String>,
+    #[serde(default)]
+    pub game_exe_path: Option<String>,
+    /// Size limit of the download cache; 0 turns the cache off.
+    #[serde(default = "default_download_cache_limit_mb")]
+    pub download_cache_limit_mb: u64,
+    /// Retries of a download after a transient failure (connection error, 429, 5xx).
+    #[serde(default = "default_download_max_retries")]
+    pub download_max_retries: u32,
+    /// 0 means no limit.
+    #[serde(default = "default_http_connect_timeout_secs")]
+    pub http_connect_timeout_secs: u64,
+    /// Longest a download may stall without receiving data; 0 means no limit.
+    #[serde(default = "default_http_read_timeout_secs")]
+    pub http_read_timeout_secs: u64,
+    /// Proxy for downloads: `http://`, `https://`, `socks5://` or `socks5h://`. Only changed
+    /// through [`modify`] after the user confirmed it; [`update`] keeps the saved value.
+    #[serde(default)]
+    pub http_proxy_url: Option<String>,
+    /// PEM or DER files with root certificates to trust in addition to the system ones. Files
+    /// are only added through [`modify`]; settings from the webview can only remove entries.
+    #[serde(default)]
+    pub extra_ca_certs: Vec<String>,
+    /// Download hosts the user allowed in addition to the built-in allowlist. Only
+    /// [`trust_host`] adds to this; settings from the webview can only remove entries.
+    #[serde(default)]
+    pub trusted_download_hosts: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            proxy_base_url: default_proxy_base_url(),
+            hytale_user_data_path: None,
+            mods_dir_path: None,
+            game_exe_path: None,
+            download_cache_limit_mb: DEFAULT_DOWNLOAD_CACHE_LIMIT_MB,
+            download_max_retries: retry::DEFAULT_MAX_RETRIES,
+            http_connect_timeout_secs: http::DEFAULT_CONNECT_TIMEOUT_SECS,
+            http_read_timeout_secs: http::DEFAULT_READ_TIMEOUT_SECS,
+            http_proxy_url: None,
+            extra_ca_certs: Vec::new(),
+            trusted_download_hosts: Vec::new(),
+        }
+    }
+}
+
+fn non_blank(v: Option<String>) -> Option<String> {
+    v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+impl Settings {
+    pub fn download_cache_limit_bytes(&self) -> u64 {
+        self.download_cache_limit_mb.saturating_mul(1024 * 1024)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::with_retries(self.download_max_retries)
+    }
+
+    /// Trim every value, turn blank paths into `None` and fall back to the default proxy URL.
+    pub fn normalized(self) -> Self {
+        let proxy = self.proxy_base_url.trim().trim_end_matches('/');
+        Self {
+            proxy_base_url: if proxy.is_empty() {
+                default_proxy_base_url()
+            } else {
+                proxy.to_string()
+            },
+            hytale_user_data_path: non_blank(self.hytale_user_data_path),
+            mods_dir_path: non_blank(self.mods_dir_path),
+            game_exe_path: non_blank(self.game_exe_path),
+            download_cache_limit_mb: self.download_cache_limit_mb,
+            download_max_retries: self.download_max_retries.min(retry::MAX_RETRIES),
+            http_connect_timeout_secs: self.http_connect_timeout_secs,
+            http_read_timeout_secs: self.http_read_timeout_secs,
+            http_proxy_url: non_blank(self.http_proxy_url),
+            extra_ca_certs: self
+                .extra_ca_certs
+                .into_iter()
+                .filter_map(|p| non_blank(Some(p)))
+                .collect(),
+            trusted_download_hosts: {
+                let mut hosts: Vec<String> = self
+                    .trusted_download_hosts
+                    .into_iter()
+                    .filter_map(|h| non_blank(Some(h)))
+                    .map(|h| h.to_ascii_lowercase())
+                    .collect();
+                hosts.sort();
+                hosts.dedup();
+                hosts
+            },
+        }
+    }
+
+    /// Reject values other commands could not use. A Mods dir that does not exist yet is
+    /// accepted, since it is created on first use.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        match reqwest::Url::parse(&self.proxy_base_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+            _ => errors.push(format!(
+                "Proxy base URL \"{}\" is not an http(s) URL",
+                self.proxy_base_url
+            )),
+        }
+
+        if let Some(ref p) = self.mods_dir_path {
+            let access = crate::path_access(Path::new(p));
+            if access.exists && !access.is_dir {
+                errors.push("Mods directory path is not a directory".to_string());
+            } else if access.is_dir && !access.writable {
+                errors.push("Mods directory is not writable".to_string());
+            }
+        }
+
+        if let Some(ref p) = self.hytale_user_data_path {
+            let access = crate::path_access(Path::new(p));
+            if access.exists && !access.is_dir {
+                errors.push("Hytale user data path is not a directory".to_string());
+            }
+        }
+
+        if let Some(ref p) = self.game_exe_path {
+            let path = Path::new(p);
+            if !path.exists() {
+                errors.push("Hytale executable does not exist".to_string());
+            } else if path.is_dir() {
+                errors.push("Hytale executable path is a directory".to_string());
+            }
+        }
+
+        if let Some(ref url) = self.http_proxy_url {
+            if let Err(e) = http::proxy(url) {
+                errors.push(e);
+            }
+        }
+
+        for p in &self.extra_ca_certs {
+            if let Err(e) = http::load_certificates(Path::new(p)) {
+                errors.push(e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+/// Read settings, recovering from a corrupt file. If no file exists yet, `initial` (or the
+/// defaults) is normalized and saved without validation, so migrated values are never lost.
+pub fn load_or_init(
+    path: &Path,
+    initial: Option<Settings>,
+) -> Result<LoadResult<Settings>, String> {
+    if !path.exists() {
+        // Older builds had no proxy, certificate or host settings to migrate.
+        let settings = Settings {
+            http_proxy_url: None,
+            extra_ca_certs: Vec::new(),
+            trusted_download_hosts: Vec::new(),
+            ..initial.unwrap_or_default()
+        }
+        .normalized();
+        save(path, &settings)?;
+        return Ok(LoadResult {
+            data: settings,
+            recovered: None,
+        });
+    }
+    let res = recovery::read_or_recover::<Settings, _>(&schema::SETTINGS, path, || None)?;
+    Ok(LoadResult {
+        data: res.data.unwrap_or_default(),
+        recovered: res.recovered,
+    })
+}
+
+/// Normalize, validate and save. Returns the settings as stored. The Mods folder and both
+/// proxies are kept as saved, and trusted hosts and certificates that are not already saved are dropped; those
+/// are only changed through [`trust_host`] and [`modify`].
+pub fn update(path: &Path, settings: Settings) -> Result<Settings, String> {
+    let saved = current(path);
+    let mut settings = Settings {
+        proxy_base_url: saved.proxy_base_url,
+        mods_dir_path: saved.mods_dir_path,
+        http_proxy_url: saved.http_proxy_url,
+        ..settings.normalized()
+    };
+    settings
+        .trusted_download_hosts
+        .retain(|h| saved.trusted_download_hosts.contains(h));
+    settings
+        .extra_ca_certs
+        .retain(|p| saved.extra_ca_certs.contains(p));
+    settings.validate()?;
+    save(path, &settings)?;
+    Ok(settings)
+}
+
+/// Apply `change` to the saved settings, then normalize, validate and save. Only call this
+/// for values the user chose or confirmed in a native dialog.
+pub fn modify(path: &Path, change: impl FnOnce(&mut Settings)) -> Result<Settings, String> {
+    let mut settings = current(path);
+    change(&mut settings);
+    let settings = settings.normalized();
+    settings.validate()?;
+    save(path, &settings)?;
+    Ok(settings)
+}
+
+/// Add `host` to the trusted download hosts. Only call this after the user confirmed it.
+pub fn trust_host(path: &Path, host: &str) -> Result<Settings, String> {
+    let mut settings = current(path);
+    settings.trusted_download_hosts.push(host.to_string());
+    let settings = settings.normalized();
+    save(path, &settings)?;
+    Ok(settings)
+}
+
+/// Saved settings, or the defaults if there are none yet or they cannot be read.
+pub fn current(path: &Path) -> Settings {
+    schema::read::<Settings>(&schema::SETTINGS, path)
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+fn save(path: &Path, settings: &Settings) -> Result<(), String> {
+    schema::write(&schema::SETTINGS, path, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn initial_values_are_used_only_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILENAME);
+        let legacy = Settings {
+            proxy_base_url: " http://localhost:9000/ ".to_string(),
+            mods_dir_path: Some("  ".to_string()),
+            game_exe_path: Some("/missing/Hytale.exe".to_string()),
+            ..Settings::default()
+        };
+
+        let first = load_or_init(&path, Some(legacy.clone())).unwrap().data;
+        assert_eq!(first.proxy_base_url, "http://localhost:9000");
+        assert_eq!(first.mods_dir_path, None);
+        // Migrated values are kept even if they would not pass validation.
+        assert_eq!(first.game_exe_path.as_deref(), Some("/missing/Hytale.exe"));
+
+        let second = load_or_init(&path, Some(Settings::default())).unwrap().data;
+        assert_eq!(second, first);
+    }
+
+    #[test]
+    fn update_validates_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILENAME);
+        let exe = dir.path().join("Hytale.exe");
+        fs::write(&exe, "").unwrap();
+
+        let good = Settings {
+            game_exe_path: Some(exe.to_string_lossy().into_owned()),
+            ..Settings::default()
+        };
+        assert_eq!(update(&path, good.clone()).unwrap(), good);
+        assert_eq!(load_or_init(&path, None).unwrap().data, good);
+
+        let bad = Settings {
+            hytale_user_data_path: Some(exe.to_string_lossy().into_owned()),
+            game_exe_path: Some(dir.path().to_string_lossy().into_owned()),
+            ..Settings::default()
+        };
+        let err = update(&path, bad).unwrap_err();
+        assert!(
+            err.contains("Hytale user data path is not a directory"),
+            "{err}"
+        );
+        assert!(err.contains("is a directory"), "{err}");
+        assert_eq!(load_or_init(&path, None).unwrap().data, good);
+    }
+
+    #[test]
+    fn mods_dir_is_only_changed_by_modify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILENAME);
+        let mods = dir.path().join("Mods").to_string_lossy().into_owned();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "").unwrap();
+
+        let saved = update(
+            &path,
+            Settings {
+                mods_dir_path: Some(mods.clone()),
+                ..Settings::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(saved.mods_dir_path, None);
+
+        let saved = modify(&path, |s| s.mods_dir_path = Some(mods.clone())).unwrap();
+        assert_eq!(saved.mods_dir_path.as_deref(), Some(mods.as_str()));
+        let saved = update(&path, Settings::default()).unwrap();
+        assert_eq!(saved.mods_dir_path.as_deref(), Some(mods.as_str()));
+
+        let file = file.to_string_lossy().into_owned();
+        let err = modify(&path, |s| s.mods_dir_path = Some(file)).unwrap_err();
+        assert!(
+            err.contains("Mods directory path is not a directory"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn trusted_hosts_are_only_added_by_trust_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILENAME);
+        let with_hosts = |hosts: &[&str]| Settings {
+            trusted_download_hosts: hosts.iter().map(|h| h.to_string()).collect(),
+            ..Settings::default()
+        };
+        let first = load_or_init(&path, Some(with_hosts(&["evil.example"]))).unwrap();
+        assert!(first.data.trusted_download_hosts.is_empty());
+
+        let saved = update(&path, with_hosts(&["evil.example"])).unwrap();
+        assert!(saved.trusted_download_hosts.is_empty());
+
+        trust_host(&path, "a.example").unwrap();
+        let saved = trust_host(&path, "B.example").unwrap();
+        assert_eq!(saved.trusted_download_hosts, ["a.example", "b.example"]);
+
+        let saved = update(&path, with_hosts(&["b.example", "evil.example"])).unwrap();
+        assert_eq!(saved.trusted_download_hosts, ["b.example"]);
+    }
+
+    #[test]
+    fn proxy_and_certificates_are_not_set_from_the_webview() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILENAME);
+        let cert = dir.path().join("ca.pem");
+        fs::write(&cert, "").unwrap();
+        let cert = cert.to_string_lossy().into_owned();
+        let from_webview = Settings {
+            http_proxy_url: Some("http://evil.example:3128".to_string()),
+            extra_ca_certs: vec![cert.clone()],
+            ..Settings::default()
+        };
+        let first = load_or_init(&path, Some(from_webview.clone())).unwrap().data;
+        assert_eq!(first.http_proxy_url, None);
+        assert!(first.extra_ca_certs.is_empty());
+
+        let saved = update(&path, from_webview.clone()).unwrap();
+        assert_eq!(saved.http_proxy_url, None);
+        assert!(saved.extra_ca_certs.is_empty());
+
+        let saved = modify(&path, |s| {
+            s.http_proxy_url = Some(" http://proxy.lan:3128 ".to_string())
+        })
+        .unwrap();
+        assert_eq!(saved.http_proxy_url.as_deref(), Some("http://proxy.lan:3128"));
+        // As if the certificate had been picked earlier.
+        save(&path, &Settings { extra_ca_certs: vec![cert.clone()], ..saved }).unwrap();
+
+        // The webview can remove a certificate, but not add it back or change the proxy.
+        let saved = update(&path, Settings::default()).unwrap();
+        assert_eq!(saved.http_proxy_url.as_deref(), Some("http://proxy.lan:3128"));
+        assert!(saved.extra_ca_certs.is_empty());
+        let saved = update(&path, from_webview).unwrap();
+        assert!(saved.extra_ca_certs.is_empty());
+
+        let err = modify(&path, |s| s.http_proxy_url = Some("ftp://x".to_string())).unwrap_err();
+        assert!(!err.is_empty());
+        assert_eq!(current(&path), saved);
+    }
+
+    #[test]
+    fn proxy_base_url_is_only_changed_by_modify() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILENAME);
+        let elsewhere = Settings {
+            proxy_base_url: "https://evil.example".to_string(),
+            ..Settings::default()
+        };
+        let saved = update(&path, elsewhere).unwrap();
+        assert_eq!(saved.proxy_base_url, DEFAULT_PROXY_BASE_URL);
+
+        let err = modify(&path, |s| s.proxy_base_url = "localhost".to_string()).unwrap_err();
+        assert!(err.contains("Proxy base URL"), "{err}");
+        let saved =
+            modify(&path, |s| s.proxy_base_url = "https://proxy.example/".to_string()).unwrap();
+        assert_eq!(saved.proxy_base_url, "https://proxy.example");
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SETTINGS_FILENAME);
+        fs::write(&path, "{\"schemaVersion\": 1, \"data\": ").unwrap();
+        let res = load_or_init(&path, None).unwrap();
+        assert_eq!(res.data, Settings::default());
+        assert!(res.recovered.is_some());
+    }
+}
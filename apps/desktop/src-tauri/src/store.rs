@@ -0,0 +1,1333 @@
+//! SQLite-backed state store for installed mods, profiles and settings.
+//!
+//! Lives at `hyghertales.db` in the app data dir and is shared through managed state as
+//! `Mutex<Store>`. The legacy `installed_mods.json` / `profiles.json` files are imported once
+//! (through the schema + recovery readers) and then left in place untouched.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::mod_backups::ModBackup;
+use crate::recovery::{self, RecoveryAction, RecoveryReport};
+use crate::transaction::RecordChange;
+use crate::{persist, profiles, schema};
+use crate::{InstalledModPatch, InstalledModRecord, ProfileRecord, ProfilesData};
+
+pub const DB_FILENAME: &str = "hyghertales.db";
+
+/// `MIGRATIONS[i]` brings the database from `user_version` i to i + 1.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE installed_mods (
+        id INTEGER PRIMARY KEY,
+        provider TEXT NOT NULL,
+        project_id INTEGER,
+        resource_id TEXT,
+        slug TEXT NOT NULL,
+        name TEXT NOT NULL,
+        installed_file_id TEXT,
+        installed_filename TEXT NOT NULL,
+        installed_at TEXT NOT NULL,
+        source_url TEXT,
+        enabled INTEGER NOT NULL,
+        pinned INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE profiles (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        created_at TEXT NOT NULL
+    );
+    CREATE TABLE profile_mods (
+        profile_id INTEGER NOT NULL REFERENCES profiles(id) ON DELETE CASCADE,
+        mod_id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (profile_id, mod_id)
+    );
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+"#, r#"
+    ALTER TABLE installed_mods ADD COLUMN file_hash TEXT;
+"#, r#"
+    CREATE TABLE mod_backups (
+        id INTEGER PRIMARY KEY,
+        mod_id INTEGER NOT NULL,
+        file_name TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        record TEXT NOT NULL
+    );
+    CREATE INDEX mod_backups_mod_id ON mod_backups (mod_id);
+"#];
+
+const KEY_PROFILES_NEXT_ID: &str = "profiles.nextId";
+const KEY_ACTIVE_PROFILE_ID: &str = "profiles.activeProfileId";
+const KEY_INSTALLED_MODS_LAST_ID: &str = "installedMods.lastId";
+const KEY_IMPORTED_INSTALLED_MODS: &str = "import.installedModsJson";
+const KEY_IMPORTED_PROFILES: &str = "import.profilesJson";
+const KEY_LAST_TRANSACTION: &str = "transactions.lastId";
+
+const MOD_COLUMNS: &str = "id, provider, project_id, resource_id, slug, name, installed_file_id, \
+     installed_filename, installed_at, source_url, enabled, pinned, file_hash";
+
+/// `file` of the [`RecoveryReport`] of a recovered database.
+pub const DB_RECOVERY_NAME: &str = "mod database";
+
+/// Why the database could not be opened.
+enum OpenError {
+    /// Written by a newer version of the app.
+    TooNew(String),
+    /// Locked by another process for longer than the busy timeout.
+    Busy(String),
+    /// Not a database, damaged, or unreadable.
+    Corrupt(String),
+}
+
+impl From<rusqlite::Error> for OpenError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked) => {
+                OpenError::Busy(e.to_string())
+            }
+            _ => OpenError::Corrupt(e.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for OpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenError::TooNew(e) | OpenError::Busy(e) | OpenError::Corrupt(e) => f.write_str(e),
+        }
+    }
+}
+
+/// `hyghertales.db` -> `hyghertales.db-wal`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+pub struct Store {
+    conn: Connection,
+    /// Reports from the legacy JSON import, handed to the frontend on the next read.
+    pending_recovery: Vec<RecoveryReport>,
+}
+
+fn mod_from_row(row: &Row) -> rusqlite::Result<InstalledModRecord> {
+    let file_id: Option<String> = row.get(6)?;
+    Ok(InstalledModRecord {
+        id: row.get(0)?,
+        provider: row.get(1)?,
+        project_id: row.get(2)?,
+        resource_id: row.get(3)?,
+        slug: row.get(4)?,
+        name: row.get(5)?,
+        installed_file_id: file_id.and_then(|s| serde_json::from_str(&s).ok()),
+        installed_filename: row.get(7)?,
+        installed_at: row.get(8)?,
+        source_url: row.get(9)?,
+        enabled: row.get(10)?,
+        pinned: row.get(11)?,
+        file_hash: row.get(12)?,
+    })
+}
+
+fn backup_from_row(row: &Row) -> rusqlite::Result<ModBackup> {
+    let record: String = row.get(4)?;
+    Ok(ModBackup {
+        id: row.get(0)?,
+        mod_id: row.get(1)?,
+        file_name: row.get(2)?,
+        created_at: row.get(3)?,
+        record: serde_json::from_str(&record).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+    })
+}
+
+fn insert_mod(conn: &Connection, m: &InstalledModRecord) -> rusqlite::Result<i64> {
+    let file_id = m.installed_file_id.as_ref().map(|v| v.to_string());
+    conn.execute(
+        &format!("INSERT INTO installed_mods ({MOD_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"),
+        params![
+            m.id,
+            m.provider,
+            m.project_id,
+            m.resource_id,
+            m.slug,
+            m.name,
+            file_id,
+            m.installed_filename,
+            m.installed_at,
+            m.source_url,
+            m.enabled,
+            m.pinned,
+            m.file_hash
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn update_mod(conn: &Connection, m: &InstalledModRecord) -> rusqlite::Result<usize> {
+    let file_id = m.installed_file_id.as_ref().map(|v| v.to_string());
+    conn.execute(
+        "UPDATE installed_mods SET provider = ?2, project_id = ?3, resource_id = ?4, slug = ?5,
+             name = ?6, installed_file_id = ?7, installed_filename = ?8, installed_at = ?9,
+             source_url = ?10, enabled = ?11, pinned = ?12, file_hash = ?13
+         WHERE id = ?1",
+        params![
+            m.id,
+            m.provider,
+            m.project_id,
+            m.resource_id,
+            m.slug,
+            m.name,
+            file_id,
+            m.installed_filename,
+            m.installed_at,
+            m.source_url,
+            m.enabled,
+            m.pinned,
+            m.file_hash
+        ],
+    )
+}
+
+fn get_setting_on(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        params![key],
+        |r| r.get(0),
+    )
+    .optional()
+}
+
+/// Highest mod id ever handed out. Ids are not reused after removal because profiles
+/// may still refer to them.
+fn last_mod_id(conn: &Connection) -> rusqlite::Result<i64> {
+    let max: i64 = conn.query_row("SELECT COALESCE(MAX(id), 0) FROM installed_mods", [], |r| {
+        r.get(0)
+    })?;
+    let last = get_setting_on(conn, KEY_INSTALLED_MODS_LAST_ID)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    Ok(max.max(last))
+}
+
+/// Insert `mods` after the table was emptied. Records without an id get the next one from the
+/// last-id counter, so they never take the id of a removed mod that backups or profiles may still
+/// refer to. Duplicate ids are refused. The counter ends up past every inserted id.
+fn insert_mods(conn: &Connection, mods: &[InstalledModRecord]) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for id in mods.iter().filter_map(|m| m.id) {
+        if !ids.insert(id) {
+            return Err(format!("Installed mod id {id} is used more than once"));
+        }
+    }
+    let mut next = last_mod_id(conn).map_err(|e| e.to_string())?;
+    next = next.max(ids.into_iter().max().unwrap_or(0));
+    for m in mods {
+        if m.id.is_some() {
+            insert_mod(conn, m).map_err(|e| e.to_string())?;
+        } else {
+            next += 1;
+            let m = InstalledModRecord {
+                id: Some(next),
+                ..m.clone()
+            };
+            insert_mod(conn, &m).map_err(|e| e.to_string())?;
+        }
+    }
+    set_setting_on(conn, KEY_INSTALLED_MODS_LAST_ID, &next.to_string()).map_err(|e| e.to_string())
+}
+
+/// Id of another record for the same provider project/resource, if any.
+/// Untracked records (no project or resource id) never conflict.
+fn find_duplicate(conn: &Connection, m: &InstalledModRecord) -> rusqlite::Result<Option<i64>> {
+    if m.project_id.is_none() && m.resource_id.is_none() {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT id FROM installed_mods
+         WHERE provider = ?1
+           AND ((?2 IS NOT NULL AND project_id = ?2) OR (?3 IS NOT NULL AND resource_id = ?3))
+           AND id IS NOT ?4
+         LIMIT 1",
+        params![m.provider, m.project_id, m.resource_id, m.id],
+        |r| r.get(0),
+    )
+    .optional()
+}
+
+fn check_unique(conn: &Connection, m: &InstalledModRecord) -> Result<(), String> {
+    if let Some(existing) = find_duplicate(conn, m).map_err(|e| e.to_string())? {
+        return Err(format!(
+            "{} is already installed (record {existing})",
+            m.name
+        ));
+    }
+    Ok(())
+}
+
+fn set_setting_on(conn: &Connection, key: &str, value: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+fn write_profile_mods(conn: &Connection, profile_id: i64, mod_ids: &[i64]) -> rusqlite::Result<()> {
+    conn.execute(
+        "DELETE FROM profile_mods WHERE profile_id = ?1",
+        params![profile_id],
+    )?;
+    let mut stmt = conn.prepare(
+        "INSERT OR IGNORE INTO profile_mods (profile_id, mod_id, position) VALUES (?1, ?2, ?3)",
+    )?;
+    for (pos, mod_id) in mod_ids.iter().enumerate() {
+        stmt.execute(params![profile_id, mod_id, pos as i64])?;
+    }
+    Ok(())
+}
+
+fn write_profiles_on(conn: &Connection, data: &ProfilesData) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM profiles", [])?;
+    for p in &data.profiles {
+        conn.execute(
+            "INSERT INTO profiles (id, name, created_at) VALUES (?1, ?2, ?3)",
+            params![p.id, p.name, p.created_at],
+        )?;
+        write_profile_mods(conn, p.id, &p.enabled_mod_ids)?;
+    }
+    set_setting_on(conn, KEY_PROFILES_NEXT_ID, &data.next_id.to_string())?;
+    match data.active_profile_id {
+        Some(id) => set_setting_on(conn, KEY_ACTIVE_PROFILE_ID, &id.to_string())?,
+        None => {
+            conn.execute(
+                "DELETE FROM settings WHERE key = ?1",
+                params![KEY_ACTIVE_PROFILE_ID],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+impl Store {
+    /// Open (or create) the database at `path` and bring its schema up to date.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Self::open_checked(path).map_err(|e| e.to_string())
+    }
+
+    fn open_checked(path: &Path) -> Result<Self, OpenError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Open the database like [`Self::open`], recovering from a damaged file the way
+    /// [`recovery::read_or_recover`] does for the JSON files: it is moved aside and the newest
+    /// backup copy that opens is restored, else a new database is created (which imports the
+    /// legacy JSON files again). The report is handed out as [`DB_RECOVERY_NAME`]. A database
+    /// from a newer app version or one locked by another process is left alone.
+    ///
+    /// After a successful open a copy is kept as `hyghertales.db.1` (older copies are shifted
+    /// up to `.3`).
+    pub fn open_or_recover(path: &Path) -> Result<Self, String> {
+        let error = match Self::open_checked(path) {
+            Ok(store) => {
+                if let Err(e) = store.keep_generation(path) {
+                    eprintln!("[store] Could not back up the database: {e}");
+                }
+                return Ok(store);
+            }
+            Err(OpenError::Corrupt(e)) => e,
+            Err(e) => return Err(e.to_string()),
+        };
+        let quarantined = recovery::quarantine_path(path);
+        for suffix in ["", "-wal", "-shm"] {
+            let from = with_suffix(path, suffix);
+            if from.exists() {
+                fs::rename(&from, with_suffix(&quarantined, suffix)).map_err(|e| e.to_string())?;
+            }
+        }
+        eprintln!(
+            "[store] Database is unusable ({error}); moved to {}",
+            quarantined.display()
+        );
+
+        let restored = (1..=persist::GENERATIONS)
+            .map(|n| persist::generation_path(path, n))
+            .filter(|g| g.is_file())
+            .find_map(|g| {
+                fs::copy(&g, path).ok()?;
+                match Self::open_checked(path) {
+                    Ok(store) => Some((store, g)),
+                    Err(_) => {
+                        for suffix in ["", "-wal", "-shm"] {
+                            let _ = fs::remove_file(with_suffix(path, suffix));
+                        }
+                        None
+                    }
+                }
+            });
+        let (mut store, action, backup_path) = match restored {
+            Some((store, g)) => (store, RecoveryAction::RestoredBackup, Some(g)),
+            None => (Self::open(path)?, RecoveryAction::Recreated, None),
+        };
+        store.pending_recovery.push(RecoveryReport {
+            file: DB_RECOVERY_NAME.to_string(),
+            error,
+            quarantined_path: quarantined.to_string_lossy().into_owned(),
+            action,
+            backup_path: backup_path.map(|p| p.to_string_lossy().into_owned()),
+        });
+        Ok(store)
+    }
+
+    /// Copy the database to generation 1, shifting the older ones.
+    fn keep_generation(&self, path: &Path) -> Result<(), String> {
+        let tmp = with_suffix(path, ".tmp");
+        let _ = fs::remove_file(&tmp);
+        self.conn
+            .execute("VACUUM INTO ?1", params![tmp.to_string_lossy()])
+            .map_err(|e| e.to_string())?;
+        let oldest = persist::generation_path(path, persist::GENERATIONS);
+        let _ = fs::remove_file(oldest);
+        for n in (1..persist::GENERATIONS).rev() {
+            let from = persist::generation_path(path, n);
+            if from.exists() {
+                fs::rename(&from, persist::generation_path(path, n + 1))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        fs::rename(&tmp, persist::generation_path(path, 1)).map_err(|e| e.to_string())
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        Self::init(conn).map_err(|e| e.to_string())
+    }
+
+    fn init(conn: Connection) -> Result<Self, OpenError> {
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        let check: String = conn.query_row("PRAGMA quick_check", [], |r| r.get(0))?;
+        if check != "ok" {
+            return Err(OpenError::Corrupt(format!("integrity check failed: {check}")));
+        }
+        let mut store = Store {
+            conn,
+            pending_recovery: Vec::new(),
+        };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    fn migrate(&mut self) -> Result<(), OpenError> {
+        let version: usize = self
+            .conn
+            .query_row("PRAGMA user_version", [], |r| r.get::<_, i64>(0))?
+            as usize;
+        if version > MIGRATIONS.len() {
+            return Err(OpenError::TooNew(format!(
+                "The database was written by a newer version of HygherTales (schema v{version}, this app supports v{}).",
+                MIGRATIONS.len()
+            )));
+        }
+        for (i, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", (i + 1) as i64)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        get_setting_on(&self.conn, key).map_err(|e| e.to_string())
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        set_setting_on(&self.conn, key, value).map_err(|e| e.to_string())
+    }
+
+    pub fn list_installed_mods(&self) -> Result<Vec<InstalledModRecord>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {MOD_COLUMNS} FROM installed_mods ORDER BY id"
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], mod_from_row)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn get_installed_mod(&self, id: i64) -> Result<Option<InstalledModRecord>, String> {
+        self.conn
+            .query_row(
+                &format!("SELECT {MOD_COLUMNS} FROM installed_mods WHERE id = ?1"),
+                params![id],
+                mod_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Replace the whole installed list (compatibility path for `write_installed_mods`). Returns
+    /// the `Mods.backup` index entries dropped with the mods that are gone; their files are left
+    /// to the caller.
+    pub fn replace_installed_mods(
+        &mut self,
+        mods: &[InstalledModRecord],
+    ) -> Result<Vec<ModBackup>, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM installed_mods", [])
+            .map_err(|e| e.to_string())?;
+        insert_mods(&tx, mods)?;
+        const GONE: &str = "mod_id NOT IN (SELECT id FROM installed_mods)";
+        let dropped = {
+            let mut stmt = tx
+                .prepare(&format!(
+                    "SELECT id, mod_id, file_name, created_at, record FROM mod_backups \
+                     WHERE {GONE} ORDER BY id"
+                ))
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], backup_from_row)
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        };
+        for table in ["profile_mods", "mod_backups"] {
+            tx.execute(&format!("DELETE FROM {table} WHERE {GONE}"), [])
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(dropped)
+    }
+
+    /// Insert a new record with a fresh id. Fails if the same provider project/resource
+    /// is already installed.
+    pub fn add_installed_mod(
+        &mut self,
+        mut m: InstalledModRecord,
+    ) -> Result<InstalledModRecord, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let id = last_mod_id(&tx).map_err(|e| e.to_string())? + 1;
+        m.id = Some(id);
+        check_unique(&tx, &m)?;
+        insert_mod(&tx, &m).map_err(|e| e.to_string())?;
+        set_setting_on(&tx, KEY_INSTALLED_MODS_LAST_ID, &id.to_string())
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(m)
+    }
+
+    /// Apply `patch` to record `id`. Returns the updated record.
+    pub fn update_installed_mod(
+        &mut self,
+        id: i64,
+        patch: InstalledModPatch,
+    ) -> Result<InstalledModRecord, String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let mut m = tx
+            .query_row(
+                &format!("SELECT {MOD_COLUMNS} FROM installed_mods WHERE id = ?1"),
+                params![id],
+                mod_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Installed mod {id} not found"))?;
+        patch.apply(&mut m);
+        check_unique(&tx, &m)?;
+        update_mod(&tx, &m).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(m)
+    }
+
+    /// Delete record `id`, its profile entries and its `Mods.backup` index entries (not their
+    /// files). Returns the removed record.
+    pub fn remove_installed_mod(&mut self, id: i64) -> Result<InstalledModRecord, String> {
+        let m = self
+            .get_installed_mod(id)?
+            .ok_or_else(|| format!("Installed mod {id} not found"))?;
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM installed_mods WHERE id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM profile_mods WHERE mod_id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM mod_backups WHERE mod_id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(m)
+    }
+
+    pub fn installed_mod_ids(&self) -> Result<HashSet<i64>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM installed_mods")
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([], |r| r.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<HashSet<i64>, _>>()
+            .map_err(|e| e.to_string())?;
+        Ok(ids)
+    }
+
+    fn check_mod_ids_exist(&self, mod_ids: &[i64]) -> Result<(), String> {
+        let installed = self.installed_mod_ids()?;
+        match mod_ids.iter().find(|id| !installed.contains(id)) {
+            Some(id) => Err(format!("Installed mod {id} not found")),
+            None => Ok(()),
+        }
+    }
+
+    /// Set the `enabled` flag on several records at once. Nothing changes if any id is unknown.
+    pub fn set_mods_enabled(&mut self, ids: &[i64], enabled: bool) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        for id in ids {
+            let n = tx
+                .execute(
+                    "UPDATE installed_mods SET enabled = ?2 WHERE id = ?1",
+                    params![id, enabled],
+                )
+                .map_err(|e| e.to_string())?;
+            if n == 0 {
+                return Err(format!("Installed mod {id} not found"));
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    pub fn read_profiles(&self) -> Result<ProfilesData, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, created_at FROM profiles ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let mut profiles = stmt
+            .query_map([], |r| {
+                Ok(ProfileRecord {
+                    id: r.get(0)?,
+                    name: r.get(1)?,
+                    created_at: r.get(2)?,
+                    enabled_mod_ids: Vec::new(),
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let mut mods_stmt = self
+            .conn
+            .prepare("SELECT mod_id FROM profile_mods WHERE profile_id = ?1 ORDER BY position")
+            .map_err(|e| e.to_string())?;
+        for p in profiles.iter_mut() {
+            p.enabled_mod_ids = mods_stmt
+                .query_map(params![p.id], |r| r.get(0))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<i64>, _>>()
+                .map_err(|e| e.to_string())?;
+        }
+        let max_id = profiles.iter().map(|p| p.id).max().unwrap_or(0);
+        let next_id = self
+            .get_setting(KEY_PROFILES_NEXT_ID)?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(max_id + 1);
+        let active_profile_id = self
+            .get_setting(KEY_ACTIVE_PROFILE_ID)?
+            .and_then(|v| v.parse().ok());
+        Ok(ProfilesData {
+            next_id,
+            active_profile_id,
+            profiles,
+        })
+    }
+
+    /// Replace all profiles (compatibility path for `write_profiles`).
+    pub fn replace_profiles(&mut self, data: &ProfilesData) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        write_profiles_on(&tx, data).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// Create a profile with the next id; optionally make it the active one.
+    pub fn create_profile(
+        &mut self,
+        name: &str,
+        created_at: &str,
+        enabled_mod_ids: &[i64],
+        set_active: bool,
+    ) -> Result<ProfileRecord, String> {
+        self.check_mod_ids_exist(enabled_mod_ids)?;
+        let data = self.read_profiles()?;
+        let id = data.next_id;
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO profiles (id, name, created_at) VALUES (?1, ?2, ?3)",
+            params![id, name, created_at],
+        )
+        .map_err(|e| e.to_string())?;
+        write_profile_mods(&tx, id, enabled_mod_ids).map_err(|e| e.to_string())?;
+        set_setting_on(&tx, KEY_PROFILES_NEXT_ID, &(id + 1).to_string())
+            .map_err(|e| e.to_string())?;
+        if set_active {
+            set_setting_on(&tx, KEY_ACTIVE_PROFILE_ID, &id.to_string())
+                .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(ProfileRecord {
+            id,
+            name: name.to_string(),
+            created_at: created_at.to_string(),
+            enabled_mod_ids: enabled_mod_ids.to_vec(),
+        })
+    }
+
+    pub fn rename_profile(&self, id: i64, name: &str) -> Result<(), String> {
+        let n = self
+            .conn
+            .execute(
+                "UPDATE profiles SET name = ?2 WHERE id = ?1",
+                params![id, name],
+            )
+            .map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err(format!("Profile {id} not found"));
+        }
+        Ok(())
+    }
+
+    /// Delete a profile. If it was active, the first remaining profile becomes active.
+    pub fn delete_profile(&mut self, id: i64) -> Result<(), String> {
+        let was_active = self.read_profiles()?.active_profile_id == Some(id);
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM profiles WHERE id = ?1", params![id])
+            .map_err(|e| e.to_string())?;
+        if was_active {
+            let next: Option<i64> = tx
+                .query_row("SELECT MIN(id) FROM profiles", [], |r| r.get(0))
+                .map_err(|e| e.to_string())?;
+            match next {
+                Some(next) => set_setting_on(&tx, KEY_ACTIVE_PROFILE_ID, &next.to_string()),
+                None => tx
+                    .execute(
+                        "DELETE FROM settings WHERE key = ?1",
+                        params![KEY_ACTIVE_PROFILE_ID],
+                    )
+                    .map(|_| ()),
+            }
+            .map_err(|e| e.to_string())?;
+        }
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    pub fn set_active_profile(&self, id: Option<i64>) -> Result<(), String> {
+        match id {
+            Some(id) => {
+                let exists: bool = self
+                    .conn
+                    .query_row(
+                        "SELECT EXISTS(SELECT 1 FROM profiles WHERE id = ?1)",
+                        params![id],
+                        |r| r.get(0),
+                    )
+                    .map_err(|e| e.to_string())?;
+                if !exists {
+                    return Err(format!("Profile {id} not found"));
+                }
+                self.set_setting(KEY_ACTIVE_PROFILE_ID, &id.to_string())
+            }
+            None => self
+                .conn
+                .execute(
+                    "DELETE FROM settings WHERE key = ?1",
+                    params![KEY_ACTIVE_PROFILE_ID],
+                )
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn set_profile_mods(&mut self, id: i64, mod_ids: &[i64]) -> Result<(), String> {
+        self.check_mod_ids_exist(mod_ids)?;
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let exists: bool = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM profiles WHERE id = ?1)",
+                params![id],
+                |r| r.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            return Err(format!("Profile {id} not found"));
+        }
+        write_profile_mods(&tx, id, mod_ids).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// Apply the record changes of transaction `txn_id` in one SQLite transaction, together with
+    /// a marker that it was committed (see [`Self::last_transaction_id`]).
+    pub fn apply_record_changes(
+        &mut self,
+        changes: &[RecordChange],
+        txn_id: &str,
+    ) -> Result<(), String> {
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        for change in changes {
+            match change {
+                RecordChange::PutMod { record } => {
+                    let id = record
+                        .id
+                        .ok_or_else(|| format!("{} has no record id", record.name))?;
+                    check_unique(&tx, record)?;
+                    if update_mod(&tx, record).map_err(|e| e.to_string())? == 0 {
+                        insert_mod(&tx, record).map_err(|e| e.to_string())?;
+                        let last = last_mod_id(&tx).map_err(|e| e.to_string())?.max(id);
+                        set_setting_on(&tx, KEY_INSTALLED_MODS_LAST_ID, &last.to_string())
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+                RecordChange::RemoveMod { id } => {
+                    tx.execute("DELETE FROM installed_mods WHERE id = ?1", params![id])
+                        .map_err(|e| e.to_string())?;
+                    tx.execute("DELETE FROM profile_mods WHERE mod_id = ?1", params![id])
+                        .map_err(|e| e.to_string())?;
+                    tx.execute("DELETE FROM mod_backups WHERE mod_id = ?1", params![id])
+                        .map_err(|e| e.to_string())?;
+                }
+                RecordChange::SetActiveProfile { id: Some(id) } => {
+                    set_setting_on(&tx, KEY_ACTIVE_PROFILE_ID, &id.to_string())
+                        .map_err(|e| e.to_string())?;
+                }
+                RecordChange::SetActiveProfile { id: None } => {
+                    tx.execute(
+                        "DELETE FROM settings WHERE key = ?1",
+                        params![KEY_ACTIVE_PROFILE_ID],
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+                RecordChange::AddBackup { backup } => {
+                    let record =
+                        serde_json::to_string(&backup.record).map_err(|e| e.to_string())?;
+                    tx.execute(
+                        "INSERT OR REPLACE INTO mod_backups
+                             (id, mod_id, file_name, created_at, record)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        params![
+                            backup.id,
+                            backup.mod_id,
+                            backup.file_name,
+                            backup.created_at,
+                            record
+                        ],
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+                RecordChange::RemoveBackup { id } => {
+                    tx.execute("DELETE FROM mod_backups WHERE id = ?1", params![id])
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+        }
+        set_setting_on(&tx, KEY_LAST_TRANSACTION, txn_id).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// Entries of the `Mods.backup` index for `mod_id`, newest first.
+    pub fn list_mod_backups(&self, mod_id: i64) -> Result<Vec<ModBackup>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT id, mod_id, file_name, created_at, record FROM mod_backups
+                 WHERE mod_id = ?1 ORDER BY id DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![mod_id], backup_from_row)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn get_mod_backup(&self, id: i64) -> Result<Option<ModBackup>, String> {
+        self.conn
+            .query_row(
+                "SELECT id, mod_id, file_name, created_at, record FROM mod_backups WHERE id = ?1",
+                params![id],
+                backup_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Id for the next entry of the `Mods.backup` index.
+    pub fn next_mod_backup_id(&self) -> Result<i64, String> {
+        self.conn
+            .query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM mod_backups", [], |r| {
+                r.get(0)
+            })
+            .map_err(|e| e.to_string())
+    }
+
+    /// Id of the last transaction whose record changes were committed.
+    pub fn last_transaction_id(&self) -> Result<Option<String>, String> {
+        self.get_setting(KEY_LAST_TRANSACTION)
+    }
+
+    /// Take the pending recovery report for `file` (a `DocSchema::name`), if any.
+    pub fn take_recovery(&mut self, file: &str) -> Option<RecoveryReport> {
+        let idx = self.pending_recovery.iter().position(|r| r.file == file)?;
+        Some(self.pending_recovery.remove(idx))
+    }
+
+    /// Whether [`Self::import_installed_mods_json`] has run.
+    pub fn installed_mods_imported(&self) -> Result<bool, String> {
+        Ok(self.get_setting(KEY_IMPORTED_INSTALLED_MODS)?.is_some())
+    }
+
+    /// One-time import of the legacy installed_mods.json. No-op once it has run.
+    /// `mods_dir` is used to rebuild the list if the file is corrupt and has no usable backup.
+    pub fn import_installed_mods_json(
+        &mut self,
+        path: &Path,
+        mods_dir: Option<&Path>,
+    ) -> Result<(), String> {
+        if self.installed_mods_imported()? {
+            return Ok(());
+        }
+        let res = recovery::read_or_recover(&schema::INSTALLED_MODS, path, || {
+            mods_dir.map(recovery::rebuild_from_mods_dirs)
+        })?;
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        // Also moves the last-id counter, so imported ids are not handed out again once removed.
+        insert_mods(&tx, &res.data.unwrap_or_default())?;
+        set_setting_on(&tx, KEY_IMPORTED_INSTALLED_MODS, "1").map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        self.pending_recovery.extend(res.recovered);
+        Ok(())
+    }
+
+    /// One-time import of the legacy profiles.json. No-op once it has run.
+    pub fn import_profiles_json(&mut self, path: &Path) -> Result<(), String> {
+        if self.get_setting(KEY_IMPORTED_PROFILES)?.is_some() {
+            return Ok(());
+        }
+        let res = recovery::read_or_recover::<ProfilesData, _>(&schema::PROFILES, path, || None)?;
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        if let Some(mut data) = res.data {
+            // Mod references are checked on the next read, once installed mods are imported too.
+            let report = profiles::repair(&mut data, None);
+            for r in &report.repairs {
+                eprintln!("[profiles] import: {r}");
+            }
+            write_profiles_on(&tx, &data).map_err(|e| e.to_string())?;
+        }
+        set_setting_on(&tx, KEY_IMPORTED_PROFILES, "1").map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        self.pending_recovery.extend(res.recovered);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn record(id: i64, slug: &str) -> InstalledModRecord {
+        InstalledModRecord {
+            slug: slug.to_string(),
+            name: slug.to_uppercase(),
+            installed_file_id: Some(serde_json::json!(5000 + id)),
+            ..InstalledModRecord::for_test(id, &format!("{slug}.jar"), true)
+        }
+    }
+
+    #[test]
+    fn installed_mods_round_trip() {
+        let mut store = Store::open_in_memory().unwrap();
+        let mut orbis = record(2, "bar");
+        orbis.provider = "orbis".to_string();
+        orbis.installed_file_id = Some(serde_json::json!("v1:0"));
+        orbis.pinned = true;
+        store
+            .replace_installed_mods(&[record(1, "foo"), orbis])
+            .unwrap();
+        let mods = store.list_installed_mods().unwrap();
+        assert_eq!(mods.len(), 2);
+        assert_eq!(mods[0].installed_file_id, Some(serde_json::json!(5001)));
+        assert_eq!(mods[1].installed_file_id, Some(serde_json::json!("v1:0")));
+        assert!(mods[1].pinned);
+        assert_eq!(store.get_installed_mod(2).unwrap().unwrap().slug, "bar");
+        assert!(store.get_installed_mod(3).unwrap().is_none());
+    }
+
+    #[test]
+    fn add_assigns_ids_and_enforces_uniqueness() {
+        let mut store = Store::open_in_memory().unwrap();
+        let a = store.add_installed_mod(record(0, "foo")).unwrap();
+        let b = store.add_installed_mod(record(9, "bar")).unwrap();
+        assert_eq!((a.id, b.id), (Some(1), Some(2)));
+
+        let mut dup = record(0, "foo-again");
+        dup.project_id = a.project_id;
+        assert!(store.add_installed_mod(dup).is_err());
+
+        // Same project id under another provider is fine, as are untracked records.
+        let mut other = record(0, "foo-orbis");
+        other.provider = "orbis".to_string();
+        other.project_id = a.project_id;
+        assert!(store.add_installed_mod(other).is_ok());
+        let mut untracked = record(0, "__untracked__");
+        untracked.project_id = None;
+        store.add_installed_mod(untracked.clone()).unwrap();
+        store.add_installed_mod(untracked).unwrap();
+    }
+
+    #[test]
+    fn removed_ids_are_not_reused() {
+        let mut store = Store::open_in_memory().unwrap();
+        store.add_installed_mod(record(1, "foo")).unwrap();
+        let b = store.add_installed_mod(record(2, "bar")).unwrap();
+        store.remove_installed_mod(b.id.unwrap()).unwrap();
+        let c = store.add_installed_mod(record(3, "baz")).unwrap();
+        assert_eq!(c.id, Some(3));
+        assert!(store.remove_installed_mod(2).is_err());
+    }
+
+    #[test]
+    fn update_and_bulk_enable() {
+        let mut store = Store::open_in_memory().unwrap();
+        let a = store.add_installed_mod(record(1, "foo")).unwrap();
+        let b = store.add_installed_mod(record(2, "bar")).unwrap();
+        let updated = store
+            .update_installed_mod(
+                a.id.unwrap(),
+                InstalledModPatch {
+                    installed_filename: Some("foo (1).jar".to_string()),
+                    pinned: Some(true),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.installed_filename, "foo (1).jar");
+        assert!(updated.pinned && updated.enabled);
+
+        let clash = InstalledModPatch {
+            project_id: Some(a.project_id),
+            ..Default::default()
+        };
+        assert!(store.update_installed_mod(b.id.unwrap(), clash).is_err());
+
+        assert!(store.set_mods_enabled(&[1, 99], false).is_err());
+        assert!(store.get_installed_mod(1).unwrap().unwrap().enabled);
+        store.set_mods_enabled(&[1, 2], false).unwrap();
+        assert!(store
+            .list_installed_mods()
+            .unwrap()
+            .iter()
+            .all(|m| !m.enabled));
+    }
+
+    #[test]
+    fn file_hash_column_is_added_to_old_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILENAME);
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.pragma_update(None, "user_version", 1).unwrap();
+            conn.execute(
+                "INSERT INTO installed_mods (id, provider, slug, name, installed_filename, \
+                 installed_at, enabled) VALUES (1, 'orbis', 'foo', 'Foo', 'foo.jar', 't', 1)",
+                [],
+            )
+            .unwrap();
+        }
+
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.get_installed_mod(1).unwrap().unwrap().file_hash, None);
+        let set = |hash: Option<&str>| InstalledModPatch {
+            file_hash: Some(hash.map(str::to_string)),
+            ..Default::default()
+        };
+        let m = store.update_installed_mod(1, set(Some("sha1:abc"))).unwrap();
+        assert_eq!(m.file_hash.as_deref(), Some("sha1:abc"));
+        assert_eq!(store.list_installed_mods().unwrap()[0].file_hash, m.file_hash);
+        // A field left out of the JSON is kept, `null` clears it.
+        let patch = |json| serde_json::from_value::<InstalledModPatch>(json).unwrap();
+        let kept = patch(serde_json::json!({ "name": "Foo 2" }));
+        assert!(kept.file_hash.is_none());
+        let m = store.update_installed_mod(1, kept).unwrap();
+        assert_eq!(m.file_hash.as_deref(), Some("sha1:abc"));
+        let cleared = patch(serde_json::json!({ "fileHash": null }));
+        assert_eq!(cleared.file_hash, Some(None));
+        let m = store.update_installed_mod(1, cleared).unwrap();
+        assert_eq!(m.file_hash, None);
+        store.update_installed_mod(1, set(Some("sha1:abc"))).unwrap();
+        let m = store.update_installed_mod(1, set(None)).unwrap();
+        assert_eq!(m.file_hash, None);
+    }
+
+    #[test]
+    fn backup_index_round_trips() {
+        let mut store = Store::open_in_memory().unwrap();
+        assert_eq!(store.next_mod_backup_id().unwrap(), 1);
+        let backup = |id: i64, mod_id: i64| ModBackup {
+            id,
+            mod_id,
+            file_name: format!("foo-{id}.jar.bak"),
+            created_at: "t".to_string(),
+            record: record(mod_id, "foo"),
+        };
+        let add = |b: ModBackup| RecordChange::AddBackup {
+            backup: Box::new(b),
+        };
+        store
+            .apply_record_changes(&[add(backup(1, 1)), add(backup(2, 1)), add(backup(3, 2))], "t1")
+            .unwrap();
+        let ids: Vec<_> = store
+            .list_mod_backups(1)
+            .unwrap()
+            .iter()
+            .map(|b| b.id)
+            .collect();
+        assert_eq!(ids, [2, 1]);
+        assert_eq!(store.get_mod_backup(3).unwrap(), Some(backup(3, 2)));
+        assert_eq!(store.next_mod_backup_id().unwrap(), 4);
+
+        store
+            .apply_record_changes(&[RecordChange::RemoveBackup { id: 2 }], "t2")
+            .unwrap();
+        assert_eq!(store.list_mod_backups(1).unwrap(), [backup(1, 1)]);
+        assert_eq!(store.last_transaction_id().unwrap().as_deref(), Some("t2"));
+    }
+
+    #[test]
+    fn removing_a_mod_drops_its_backups() {
+        let mut store = Store::open_in_memory().unwrap();
+        for (id, slug) in [(1, "foo"), (2, "bar"), (3, "baz")] {
+            store.add_installed_mod(record(id, slug)).unwrap();
+        }
+        let backup = |id: i64, mod_id: i64| ModBackup {
+            id,
+            mod_id,
+            file_name: format!("{id}.jar.bak"),
+            created_at: "t".to_string(),
+            record: record(mod_id, "foo"),
+        };
+        let changes: Vec<_> = [(1, 1), (2, 2), (3, 3), (4, 3)]
+            .into_iter()
+            .map(|(id, mod_id)| RecordChange::AddBackup {
+                backup: Box::new(backup(id, mod_id)),
+            })
+            .collect();
+        store.apply_record_changes(&changes, "t1").unwrap();
+
+        store.remove_installed_mod(1).unwrap();
+        assert_eq!(store.list_mod_backups(1).unwrap(), []);
+        store
+            .apply_record_changes(&[RecordChange::RemoveMod { id: 2 }], "t2")
+            .unwrap();
+        assert_eq!(store.list_mod_backups(2).unwrap(), []);
+        let kept = store.get_installed_mod(3).unwrap().unwrap();
+        assert_eq!(store.replace_installed_mods(&[kept]).unwrap(), []);
+        assert_eq!(store.list_mod_backups(3).unwrap().len(), 2);
+        let dropped = store.replace_installed_mods(&[]).unwrap();
+        assert_eq!(dropped, [backup(3, 3), backup(4, 3)]);
+        assert_eq!(store.next_mod_backup_id().unwrap(), 1);
+    }
+
+    #[test]
+    fn damaged_database_is_restored_from_a_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILENAME);
+        {
+            let mut store = Store::open_or_recover(&path).unwrap();
+            store.add_installed_mod(record(1, "foo")).unwrap();
+        }
+        // The copy is taken when the database is opened.
+        drop(Store::open_or_recover(&path).unwrap());
+        assert!(persist::generation_path(&path, 2).is_file());
+
+        fs::write(&path, b"not a database at all, just some junk bytes").unwrap();
+        let mut store = Store::open_or_recover(&path).unwrap();
+        assert_eq!(store.list_installed_mods().unwrap().len(), 1);
+        let report = store.take_recovery(DB_RECOVERY_NAME).unwrap();
+        assert_eq!(report.action, RecoveryAction::RestoredBackup);
+        assert_eq!(
+            fs::read(&report.quarantined_path).unwrap(),
+            b"not a database at all, just some junk bytes"
+        );
+        assert!(report.quarantined_path.ends_with(".db"));
+    }
+
+    #[test]
+    fn damaged_database_without_copies_is_recreated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILENAME);
+        fs::write(&path, b"junk").unwrap();
+        let mut store = Store::open_or_recover(&path).unwrap();
+        assert!(store.list_installed_mods().unwrap().is_empty());
+        let report = store.take_recovery(DB_RECOVERY_NAME).unwrap();
+        assert_eq!(report.action, RecoveryAction::Recreated);
+        // The legacy import runs again on the new database.
+        assert_eq!(store.get_setting(KEY_IMPORTED_INSTALLED_MODS).unwrap(), None);
+    }
+
+    #[test]
+    fn newer_database_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(DB_FILENAME);
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.pragma_update(None, "user_version", 99).unwrap();
+        }
+        let err = Store::open_or_recover(&path).err().unwrap();
+        assert!(err.contains("newer version"), "{err}");
+        let names: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(names.len(), 1);
+    }
+
+    #[test]
+    fn removing_a_mod_prunes_it_from_profiles() {
+        let mut store = Store::open_in_memory().unwrap();
+        store.add_installed_mod(record(1, "foo")).unwrap();
+        store.add_installed_mod(record(2, "bar")).unwrap();
+        store.create_profile("A", "t", &[1, 2], true).unwrap();
+        store.create_profile("B", "t", &[2], false).unwrap();
+        store.remove_installed_mod(2).unwrap();
+        let data = store.read_profiles().unwrap();
+        assert_eq!(data.profiles[0].enabled_mod_ids, vec![1]);
+        assert!(data.profiles[1].enabled_mod_ids.is_empty());
+        assert!(store.set_profile_mods(1, &[2]).is_err());
+    }
+
+    #[test]
+    fn profile_mutations() {
+        let mut store = Store::open_in_memory().unwrap();
+        for (i, slug) in ["a", "b", "c", "d", "e", "f", "g"].iter().enumerate() {
+            store.add_installed_mod(record(i as i64, slug)).unwrap();
+        }
+        let a = store.create_profile("A", "t", &[3, 1, 2], true).unwrap();
+        let b = store.create_profile("B", "t", &[], false).unwrap();
+        assert_eq!((a.id, b.id), (1, 2));
+        store.rename_profile(b.id, "Bee").unwrap();
+        store.set_profile_mods(b.id, &[7]).unwrap();
+
+        let data = store.read_profiles().unwrap();
+        assert_eq!(data.next_id, 3);
+        assert_eq!(data.active_profile_id, Some(1));
+        assert_eq!(data.profiles[0].enabled_mod_ids, vec![3, 1, 2]);
+        assert_eq!(data.profiles[1].name, "Bee");
+
+        store.delete_profile(a.id).unwrap();
+        let data = store.read_profiles().unwrap();
+        assert_eq!(data.active_profile_id, Some(2));
+        assert_eq!(data.profiles.len(), 1);
+        assert!(store.set_active_profile(Some(42)).is_err());
+    }
+
+    #[test]
+    fn imports_legacy_json_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_path = dir.path().join("installed_mods.json");
+        let profiles_path = dir.path().join("profiles.json");
+        fs::write(
+            &mods_path,
+            serde_json::to_string(&vec![record(1, "foo"), record(4, "baz")]).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            &profiles_path,
+            r#"{"nextId": 2, "activeProfileId": 2, "profiles": [
+                {"id": 2, "name": "P", "createdAt": "t", "enabledModIds": [4]},
+                {"id": 2, "name": "Dup", "createdAt": "t", "enabledModIds": []}]}"#,
+        )
+        .unwrap();
+
+        let mut store = Store::open_in_memory().unwrap();
+        store.import_installed_mods_json(&mods_path, None).unwrap();
+        store.import_profiles_json(&profiles_path).unwrap();
+        assert!(store.take_recovery(schema::INSTALLED_MODS.name).is_none());
+        assert_eq!(store.list_installed_mods().unwrap().len(), 2);
+        let data = store.read_profiles().unwrap();
+        assert_eq!(data.next_id, 4);
+        assert_eq!(data.active_profile_id, Some(2));
+        assert_eq!(data.profiles[0].enabled_mod_ids, vec![4]);
+        assert_eq!(data.profiles[1].id, 3);
+
+        // Second run is a no-op even if the file changed.
+        fs::write(&mods_path, "[]").unwrap();
+        store.import_installed_mods_json(&mods_path, None).unwrap();
+        assert_eq!(store.list_installed_mods().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn imported_ids_are_not_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_path = dir.path().join("installed_mods.json");
+        let imported: Vec<_> = (1..=3).map(|id| record(id, &format!("m{id}"))).collect();
+        fs::write(&mods_path, serde_json::to_string(&imported).unwrap()).unwrap();
+
+        let mut store = Store::open_in_memory().unwrap();
+        store.import_installed_mods_json(&mods_path, None).unwrap();
+        store.remove_installed_mod(3).unwrap();
+        let added = store.add_installed_mod(record(0, "new")).unwrap();
+        assert_eq!(added.id, Some(4));
+
+        let dup = dir.path().join("dup.json");
+        fs::write(&dup, serde_json::to_string(&[record(1, "a"), record(1, "b")]).unwrap()).unwrap();
+        let mut store = Store::open_in_memory().unwrap();
+        assert!(store.import_installed_mods_json(&dup, None).is_err());
+        assert!(store.list_installed_mods().unwrap().is_empty());
+    }
+
+    #[test]
+    fn records_without_id_get_fresh_ids() {
+        let mut store = Store::open_in_memory().unwrap();
+        store
+            .replace_installed_mods(&[record(1, "foo"), record(2, "bar")])
+            .unwrap();
+        store.remove_installed_mod(2).unwrap();
+        let mut untracked = record(0, "baz");
+        untracked.id = None;
+        store
+            .replace_installed_mods(&[record(1, "foo"), untracked])
+            .unwrap();
+        let ids: Vec<_> = store
+            .list_installed_mods()
+            .unwrap()
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(ids, [Some(1), Some(3)]);
+        assert_eq!(store.add_installed_mod(record(9, "qux")).unwrap().id, Some(4));
+        assert!(store
+            .replace_installed_mods(&[record(5, "a"), record(5, "b")])
+            .is_err());
+    }
+
+    #[test]
+    fn import_reports_recovery_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mods_path = dir.path().join("installed_mods.json");
+        fs::write(&mods_path, "[{").unwrap();
+
+        let mut store = Store::open_in_memory().unwrap();
+        store.import_installed_mods_json(&mods_path, None).unwrap();
+        assert!(store.list_installed_mods().unwrap().is_empty());
+        assert!(store.take_recovery(schema::INSTALLED_MODS.name).is_some());
+        assert!(store.take_recovery(schema::INSTALLED_MODS.name).is_none());
+    }
+}
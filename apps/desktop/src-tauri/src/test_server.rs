@@ -0,0 +1,233 @@
+//! Minimal HTTP/1.1 server for download tests. Each connection gets one response from a
+//! handler, then is closed.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Request headers, names lowercased.
+pub type Headers = Vec<(String, String)>;
+
+pub struct TestResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Close the connection after this many body bytes (Content-Length still claims all).
+    pub cut_at: Option<usize>,
+    /// Pause after every 16 KiB of body.
+    pub throttle: Option<Duration>,
+}
+
+impl TestResponse {
+    pub fn new(status: u16, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body,
+            cut_at: None,
+            throttle: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Options for [`TestServer::serving`].
+#[derive(Default, Clone)]
+pub struct ServeOptions {
+    /// Honour `Range: bytes=N-` with a 206.
+    pub ranges: bool,
+    /// Cut the first response off after this many bytes.
+    pub cut_first_at: Option<usize>,
+    pub throttle: Option<Duration>,
+}
+
+pub struct TestServer {
+    /// Base URL, e.g. `http://127.0.0.1:1234`.
+    pub base: String,
+    /// `<base>/mod.jar`
+    pub url: String,
+    requests: Arc<Mutex<Vec<Headers>>>,
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        206 => "Partial Content",
+        302 => "Found",
+        404 => "Not Found",
+        416 => "Range Not Satisfiable",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Status",
+    }
+}
+
+impl TestServer {
+    /// Serve responses from `handler(request_index, path, headers)`.
+    pub fn start<F>(handler: F) -> Self
+    where
+        F: Fn(usize, &str, &Headers) -> TestResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let handler = Arc::new(handler);
+        let counter = Arc::new(AtomicUsize::new(0));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let handler = handler.clone();
+                let seen = seen.clone();
+                let counter = counter.clone();
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    let _ = reader.read_line(&mut request_line);
+                    let path = request_line
+                        .split_whitespace()
+                        .nth(1)
+                        .unwrap_or("/")
+                        .to_string();
+                    let mut headers = Vec::new();
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                            break;
+                        }
+                        if let Some((k, v)) = line.split_once(':') {
+                            headers.push((k.trim().to_ascii_lowercase(), v.trim().to_string()));
+                        }
+                    }
+                    let index = {
+                        let mut seen = seen.lock().unwrap();
+                        seen.push(headers.clone());
+                        counter.fetch_add(1, Ordering::SeqCst)
+                    };
+                    let resp = handler(index, &path, &headers);
+
+                    let mut head = format!(
+                        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        resp.status,
+                        reason(resp.status),
+                        resp.body.len()
+                    );
+                    for (k, v) in &resp.headers {
+                        head.push_str(&format!("{k}: {v}\r\n"));
+                    }
+                    head.push_str("\r\n");
+                    if stream.write_all(head.as_bytes()).is_err() {
+                        return;
+                    }
+                    let body = match resp.cut_at {
+                        Some(n) => &resp.body[..n.min(resp.body.len())],
+                        None => &resp.body[..],
+                    };
+                    for chunk in body.chunks(16 * 1024) {
+                        if stream.write_all(chunk).is_err() {
+                            return;
+                        }
+                        if let Some(delay) = resp.throttle {
+                            thread::sleep(delay);
+                        }
+                    }
+                    let _ = stream.flush();
+                });
+            }
+        });
+        Self {
+            url: format!("{base}/mod.jar"),
+            base,
+            requests,
+        }
+    }
+
+    /// Serve `body` with a strong ETag at every path.
+    pub fn serving(body: Vec<u8>, opts: ServeOptions) -> Self {
+        Self::start(move |index, _path, headers| {
+            let range_start: Option<usize> = headers
+                .iter()
+                .find(|(k, _)| k == "range")
+                .filter(|_| opts.ranges)
+                .and_then(|(_, v)| v.strip_prefix("bytes=")?.strip_suffix('-')?.parse().ok());
+            let len = body.len();
+            let mut resp = match range_start {
+                Some(start) => TestResponse::new(206, body[start..].to_vec())
+                    .header("Content-Range", &format!("bytes {start}-{}/{len}", len - 1)),
+                None => TestResponse::new(200, body.clone()),
+            }
+            .header("ETag", "\"v1\"");
+            if index == 0 {
+                resp.cut_at = opts.cut_first_at;
+            }
+            resp.throttle = opts.throttle;
+            resp
+        })
+    }
+
+    /// Header `name` of request number `index` (in arrival order).
+    pub fn header(&self, index: usize, name: &str) -> Option<String> {
+        self.requests.lock().unwrap()[index]
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+/// Deterministic test payload of `len` bytes (at least 108): a ZIP archive with one stored
+/// entry, so it passes the archive check downloads go through.
+pub fn body(len: usize) -> Vec<u8> {
+    const NAME: &[u8] = b"a.bin";
+    let data_len = len
+        .checked_sub(30 + 46 + 2 * NAME.len() + 22)
+        .expect("test body too short for a ZIP archive") as u32;
+    let u16s = |out: &mut Vec<u8>, values: &[u16]| {
+        for v in values {
+            out.extend(v.to_le_bytes());
+        }
+    };
+    let mut out = Vec::with_capacity(len);
+    out.extend(b"PK\x03\x04");
+    // Version, flags, method (stored), time, date.
+    u16s(&mut out, &[20, 0, 0, 0, 0]);
+    // CRC-32 (not checked), compressed and uncompressed size.
+    out.extend(0u32.to_le_bytes());
+    out.extend(data_len.to_le_bytes());
+    out.extend(data_len.to_le_bytes());
+    u16s(&mut out, &[NAME.len() as u16, 0]);
+    out.extend(NAME);
+    out.extend((0..data_len).map(|i| (i % 251) as u8));
+
+    let cd_offset = out.len() as u32;
+    out.extend(b"PK\x01\x02");
+    u16s(&mut out, &[20, 20, 0, 0, 0, 0]);
+    out.extend(0u32.to_le_bytes());
+    out.extend(data_len.to_le_bytes());
+    out.extend(data_len.to_le_bytes());
+    // Name, extra and comment length, disk, internal attributes.
+    u16s(&mut out, &[NAME.len() as u16, 0, 0, 0, 0]);
+    // External attributes, local header offset.
+    out.extend(0u32.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(NAME);
+    let cd_len = out.len() as u32 - cd_offset;
+
+    out.extend(b"PK\x05\x06");
+    u16s(&mut out, &[0, 0, 1, 1]);
+    out.extend(cd_len.to_le_bytes());
+    out.extend(cd_offset.to_le_bytes());
+    u16s(&mut out, &[0]);
+    out
+}
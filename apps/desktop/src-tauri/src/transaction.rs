@@ -0,0 +1,539 @@
+//! Multi-step mod changes that either all happen or are undone.
+//!
+//! Switching profiles, updating a mod or disabling several mods moves files between the Mods
+//! folders and changes records. A [`Plan`] lists those steps; [`run`] writes it to a journal in
+//! the app data dir before touching anything, records progress after every file step, applies
+//! the record changes in one SQLite transaction and deletes the journal once done. If a step
+//! fails, the file steps done so far are undone in reverse. If the app dies halfway, [`recover`]
+//! finds the journal on the next start and finishes a committed plan or undoes the rest.
+//!
+//! Deleted files are first renamed to a hidden `.ht-txn-*` name next to them, so they can be
+//! restored, and only removed once the plan has been committed. The journal sits in app data
+//! next to other state, so [`recover`] only follows one whose paths are all inside the mod
+//! folders and sets any other aside.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::mod_backups::ModBackup;
+use crate::mod_paths::{ModRoots, PATH_OUTSIDE_MODS};
+use crate::store::Store;
+use crate::InstalledModRecord;
+use crate::{persist, recovery};
+
+/// Journal of the plan in progress, in the app data dir.
+pub const JOURNAL_FILENAME: &str = "transaction.json";
+/// Error code prefix of a failed plan; everything it did has been undone.
+pub const TRANSACTION_FAILED: &str = "TRANSACTION_FAILED";
+/// Prefix of deleted files kept until the plan is committed.
+const HOLD_PREFIX: &str = ".ht-txn-";
+
+/// One file step.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum FileOp {
+    /// Move `from` to `to`, which must not exist yet.
+    Move { from: PathBuf, to: PathBuf },
+    /// Delete `path`.
+    Delete { path: PathBuf },
+}
+
+/// One record change, applied together with the others after every file step succeeded.
+/// Each is idempotent, so a committed plan can be applied again after a crash.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum RecordChange {
+    /// Insert or replace the installed mod record with this id.
+    PutMod { record: Box<InstalledModRecord> },
+    /// Remove the installed mod record, its profile entries and its `Mods.backup` index entries.
+    /// The plan should delete the backup files.
+    RemoveMod { id: i64 },
+    /// Set (or clear) the active profile.
+    SetActiveProfile { id: Option<i64> },
+    /// Insert or replace an entry of the `Mods.backup` index.
+    AddBackup { backup: Box<ModBackup> },
+    /// Remove an entry of the `Mods.backup` index.
+    RemoveBackup { id: i64 },
+}
+
+/// What a transaction does, in order: file steps first, then record changes.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Plan {
+    pub files: Vec<FileOp>,
+    pub records: Vec<RecordChange>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.records.is_empty()
+    }
+
+    /// Fails with [`PATH_OUTSIDE_MODS`] unless every file step stays inside `roots`.
+    fn check_paths(&self, roots: Option<&ModRoots>) -> Result<(), String> {
+        for op in &self.files {
+            let paths = match op {
+                FileOp::Move { from, to } => vec![from, to],
+                FileOp::Delete { path } => vec![path],
+            };
+            for path in paths {
+                match roots {
+                    Some(roots) => roots.check(path).map(|_| ())?,
+                    None => {
+                        return Err(format!(
+                            "{PATH_OUTSIDE_MODS}: no Mods folder is configured for {}",
+                            path.display()
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum Phase {
+    /// File steps are running; `done` of them have finished.
+    Applying,
+    /// Records are saved; only the deleted files are left to remove.
+    Committed,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Journal {
+    id: String,
+    phase: Phase,
+    done: usize,
+    plan: Plan,
+}
+
+impl Journal {
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        persist::write_atomic(path, &json, 0).map_err(|e| e.to_string())
+    }
+
+    /// Where step `n` keeps a deleted file until commit.
+    fn hold_path(&self, n: usize, path: &Path) -> PathBuf {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("{HOLD_PREFIX}{}-{n}-{name}", self.id))
+    }
+
+    fn apply(&self, n: usize) -> Result<(), String> {
+        match &self.plan.files[n] {
+            FileOp::Move { from, to } => {
+                if to.exists() {
+                    return Err(format!("{} already exists", to.display()));
+                }
+                if let Some(dir) = to.parent() {
+                    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                persist::move_file(from, to)
+                    .map_err(|e| format!("Could not move {}: {e}", from.display()))
+            }
+            FileOp::Delete { path } => fs::rename(path, self.hold_path(n, path))
+                .map_err(|e| format!("Could not delete {}: {e}", path.display())),
+        }
+    }
+
+    /// Undo step `n` if it took effect; a step cut short by a crash may not have.
+    fn undo(&self, n: usize) -> Result<(), String> {
+        let (from, to) = match &self.plan.files[n] {
+            FileOp::Move { from, to } => (to.clone(), from.clone()),
+            FileOp::Delete { path } => (self.hold_path(n, path), path.clone()),
+        };
+        if !from.exists() || to.exists() {
+            return Ok(());
+        }
+        persist::move_file(&from, &to)
+            .map_err(|e| format!("Could not restore {}: {e}", to.display()))
+    }
+
+    /// Undo the first `count` file steps, last first. Stops at the first step that cannot be
+    /// undone, so the journal still describes what is left.
+    fn undo_all(&self, count: usize) -> Result<(), String> {
+        for n in (0..count.min(self.plan.files.len())).rev() {
+            self.undo(n)?;
+        }
+        Ok(())
+    }
+
+    fn remove_held_files(&self) {
+        for (n, op) in self.plan.files.iter().enumerate() {
+            if let FileOp::Delete { path } = op {
+                let held = self.hold_path(n, path);
+                if let Err(e) = fs::remove_file(&held) {
+                    if held.exists() {
+                        eprintln!("[transaction] Could not remove {}: {e}", held.display());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn new_id() -> String {
+    format!(
+        "{}-{}",
+        chrono::Utc::now().timestamp_millis(),
+        std::process::id()
+    )
+}
+
+/// Run `plan` with the journal at `journal_path`. On error everything is undone and the error
+/// starts with [`TRANSACTION_FAILED`]; if even undoing fails, the journal is kept for
+/// [`recover`] to retry on the next start.
+pub fn run(journal_path: &Path, store: &mut Store, plan: Plan) -> Result<(), String> {
+    if plan.is_empty() {
+        return Ok(());
+    }
+    if journal_path.exists() {
+        return Err(format!(
+            "{TRANSACTION_FAILED}: an interrupted change has not been recovered yet; restart \
+             HygherTales to retry it or set it aside"
+        ));
+    }
+    let mut journal = Journal {
+        id: new_id(),
+        phase: Phase::Applying,
+        done: 0,
+        plan,
+    };
+    journal.save(journal_path)?;
+
+    let result = (|| {
+        for n in 0..journal.plan.files.len() {
+            journal.apply(n)?;
+            journal.done = n + 1;
+            journal.save(journal_path)?;
+        }
+        store.apply_record_changes(&journal.plan.records, &journal.id)
+    })();
+    if let Err(e) = result {
+        return match journal.undo_all(journal.done) {
+            Ok(()) => {
+                let _ = fs::remove_file(journal_path);
+                Err(format!("{TRANSACTION_FAILED}: {e}; all changes were undone"))
+            }
+            Err(undo) => Err(format!(
+                "{TRANSACTION_FAILED}: {e}; undoing it failed too ({undo}), it will be retried on the next start"
+            )),
+        };
+    }
+    commit(journal_path, &mut journal);
+    Ok(())
+}
+
+/// Records are saved: drop the deleted files and the journal.
+fn commit(journal_path: &Path, journal: &mut Journal) {
+    journal.phase = Phase::Committed;
+    if let Err(e) = journal.save(journal_path) {
+        eprintln!("[transaction] Could not mark {} committed: {e}", journal.id);
+    }
+    journal.remove_held_files();
+    let _ = fs::remove_file(journal_path);
+}
+
+/// How [`recover`] dealt with an interrupted plan.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Recovered {
+    /// The records were saved before the interruption; the plan was finished.
+    Replayed,
+    /// The plan was undone.
+    Reverted,
+}
+
+/// Move the journal at `journal_path` aside (`transaction.corrupt-<timestamp>.json`), so it is
+/// neither recovered nor blocks [`run`] any more. Returns where it went.
+pub fn set_aside(journal_path: &Path) -> Result<PathBuf, String> {
+    let target = recovery::quarantine_path(journal_path);
+    fs::rename(journal_path, &target).map_err(|e| e.to_string())?;
+    eprintln!(
+        "[transaction] Set the journal aside as {}",
+        target.display()
+    );
+    Ok(target)
+}
+
+/// Finish or undo a plan left in the journal by a crash. Call before anything else touches the
+/// mod folders or the store. `Ok(None)` if there was nothing to recover. A journal with a file
+/// step outside `roots` (the current mod folders) is set aside untouched and reported as an
+/// error.
+pub fn recover(
+    journal_path: &Path,
+    store: &mut Store,
+    roots: Option<&ModRoots>,
+) -> Result<Option<Recovered>, String> {
+    let bytes = match fs::read(journal_path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    let Ok(mut journal) = serde_json::from_slice::<Journal>(&bytes) else {
+        // Written atomically, so a journal that does not parse was never valid.
+        eprintln!("[transaction] Ignoring unreadable journal");
+        let _ = fs::remove_file(journal_path);
+        return Ok(None);
+    };
+    if let Err(e) = journal.plan.check_paths(roots) {
+        let aside = set_aside(journal_path)?;
+        return Err(format!(
+            "{e}; the interrupted change was not recovered and its journal was moved to {}",
+            aside.display()
+        ));
+    }
+    let committed = journal.phase == Phase::Committed
+        || store.last_transaction_id()?.as_deref() == Some(journal.id.as_str());
+    if committed {
+        // Every file step finished before the records were saved.
+        store.apply_record_changes(&journal.plan.records, &journal.id)?;
+        commit(journal_path, &mut journal);
+        return Ok(Some(Recovered::Replayed));
+    }
+    // The step after the last recorded one may have finished before the crash.
+    journal.undo_all(journal.done + 1)?;
+    fs::remove_file(journal_path).map_err(|e| e.to_string())?;
+    Ok(Some(Recovered::Reverted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        journal: PathBuf,
+        mods: PathBuf,
+        disabled: PathBuf,
+        roots: ModRoots,
+        store: Store,
+    }
+
+    fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let mods = dir.path().join("Mods");
+        let disabled = dir.path().join("Mods.disabled");
+        fs::create_dir_all(&mods).unwrap();
+        fs::create_dir_all(&disabled).unwrap();
+        for name in ["a.jar", "b.jar"] {
+            fs::write(mods.join(name), name).unwrap();
+        }
+        let mut store = Store::open_in_memory().unwrap();
+        store
+            .replace_installed_mods(&[
+                InstalledModRecord::for_test(1, "a.jar", true),
+                InstalledModRecord::for_test(2, "b.jar", true),
+            ])
+            .unwrap();
+        Fixture {
+            journal: dir.path().join(JOURNAL_FILENAME),
+            roots: ModRoots::new(&mods),
+            _dir: dir,
+            mods,
+            disabled,
+            store,
+        }
+    }
+
+    /// Disable mod 1, delete mod 2.
+    fn plan(f: &Fixture) -> Plan {
+        Plan {
+            files: vec![
+                FileOp::Move {
+                    from: f.mods.join("a.jar"),
+                    to: f.disabled.join("a.jar"),
+                },
+                FileOp::Delete {
+                    path: f.mods.join("b.jar"),
+                },
+            ],
+            records: vec![
+                RecordChange::PutMod {
+                    record: Box::new(InstalledModRecord::for_test(1, "a.jar", false)),
+                },
+                RecordChange::RemoveMod { id: 2 },
+            ],
+        }
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn assert_untouched(f: &Fixture) {
+        assert_eq!(names(&f.mods), ["a.jar", "b.jar"]);
+        assert!(names(&f.disabled).is_empty());
+        let mods = f.store.list_installed_mods().unwrap();
+        assert_eq!(mods.len(), 2);
+        assert!(mods[0].enabled);
+        assert!(!f.journal.exists());
+    }
+
+    #[test]
+    fn applies_files_and_records() {
+        let mut f = fixture();
+        let plan = plan(&f);
+        run(&f.journal, &mut f.store, plan).unwrap();
+        assert!(names(&f.mods).is_empty());
+        assert_eq!(names(&f.disabled), ["a.jar"]);
+        let mods = f.store.list_installed_mods().unwrap();
+        assert_eq!(mods.len(), 1);
+        assert!(!mods[0].enabled);
+        assert!(!f.journal.exists());
+    }
+
+    #[test]
+    fn failed_step_undoes_the_earlier_ones() {
+        let mut f = fixture();
+        let mut plan = plan(&f);
+        plan.files.push(FileOp::Move {
+            from: f.mods.join("missing.jar"),
+            to: f.disabled.join("missing.jar"),
+        });
+        let err = run(&f.journal, &mut f.store, plan).unwrap_err();
+        assert!(err.starts_with(TRANSACTION_FAILED), "{err}");
+        assert_untouched(&f);
+    }
+
+    #[test]
+    fn failed_record_change_undoes_the_files() {
+        let mut f = fixture();
+        let mut plan = plan(&f);
+        let mut duplicate = InstalledModRecord::for_test(3, "c.jar", true);
+        duplicate.project_id = Some(101);
+        plan.records.push(RecordChange::PutMod {
+            record: Box::new(duplicate),
+        });
+        let err = run(&f.journal, &mut f.store, plan).unwrap_err();
+        assert!(err.starts_with(TRANSACTION_FAILED), "{err}");
+        assert_untouched(&f);
+    }
+
+    #[test]
+    fn reverts_an_interrupted_plan() {
+        let mut f = fixture();
+        let mut journal = Journal {
+            id: "1-1".to_string(),
+            phase: Phase::Applying,
+            done: 1,
+            plan: plan(&f),
+        };
+        // Both steps ran, but the crash came before the second was recorded.
+        journal.apply(0).unwrap();
+        journal.apply(1).unwrap();
+        journal.save(&f.journal).unwrap();
+
+        assert_eq!(
+            recover(&f.journal, &mut f.store, Some(&f.roots)).unwrap(),
+            Some(Recovered::Reverted)
+        );
+        assert_untouched(&f);
+
+        journal.done = 0;
+        journal.save(&f.journal).unwrap();
+        assert_eq!(
+            recover(&f.journal, &mut f.store, Some(&f.roots)).unwrap(),
+            Some(Recovered::Reverted)
+        );
+        assert_untouched(&f);
+        assert_eq!(
+            recover(&f.journal, &mut f.store, Some(&f.roots)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn replays_a_plan_whose_records_were_saved() {
+        let mut f = fixture();
+        let journal = Journal {
+            id: "1-2".to_string(),
+            phase: Phase::Applying,
+            done: 2,
+            plan: plan(&f),
+        };
+        journal.apply(0).unwrap();
+        journal.apply(1).unwrap();
+        f.store
+            .apply_record_changes(&journal.plan.records, &journal.id)
+            .unwrap();
+        journal.save(&f.journal).unwrap();
+
+        assert_eq!(
+            recover(&f.journal, &mut f.store, Some(&f.roots)).unwrap(),
+            Some(Recovered::Replayed)
+        );
+        assert!(names(&f.mods).is_empty());
+        assert_eq!(names(&f.disabled), ["a.jar"]);
+        assert_eq!(f.store.list_installed_mods().unwrap().len(), 1);
+        assert!(!f.journal.exists());
+    }
+
+    #[test]
+    fn refuses_to_overwrite_and_to_start_over_a_pending_journal() {
+        let mut f = fixture();
+        fs::write(f.disabled.join("a.jar"), "other").unwrap();
+        let plan = plan(&f);
+        assert!(run(&f.journal, &mut f.store, plan.clone()).is_err());
+        assert_eq!(
+            fs::read_to_string(f.disabled.join("a.jar")).unwrap(),
+            "other"
+        );
+        assert_eq!(names(&f.mods), ["a.jar", "b.jar"]);
+
+        fs::write(&f.journal, "{}").unwrap();
+        let err = run(&f.journal, &mut f.store, plan).unwrap_err();
+        assert!(err.contains("not been recovered"), "{err}");
+        // Unreadable journals are dropped.
+        assert_eq!(
+            recover(&f.journal, &mut f.store, Some(&f.roots)).unwrap(),
+            None
+        );
+        assert!(!f.journal.exists());
+    }
+
+    #[test]
+    fn sets_aside_a_journal_that_leads_outside_the_mod_folders() {
+        let mut f = fixture();
+        let settings = f.journal.with_file_name("settings.json");
+        fs::write(&settings, "{}").unwrap();
+        let journal = Journal {
+            id: "1-3".to_string(),
+            phase: Phase::Applying,
+            done: 1,
+            plan: Plan {
+                files: vec![FileOp::Delete {
+                    path: settings.clone(),
+                }],
+                records: Vec::new(),
+            },
+        };
+        journal.save(&f.journal).unwrap();
+        let err = recover(&f.journal, &mut f.store, Some(&f.roots)).unwrap_err();
+        assert!(err.starts_with(PATH_OUTSIDE_MODS), "{err}");
+        assert!(settings.exists());
+        assert!(!f.journal.exists());
+
+        // Without a Mods folder, no journal with file steps is followed.
+        let journal = Journal {
+            plan: plan(&f),
+            ..journal
+        };
+        journal.save(&f.journal).unwrap();
+        let err = recover(&f.journal, &mut f.store, None).unwrap_err();
+        assert!(err.starts_with(PATH_OUTSIDE_MODS), "{err}");
+        assert_untouched(&f);
+
+        // Neither journal blocks the next change.
+        let plan = plan(&f);
+        run(&f.journal, &mut f.store, plan).unwrap();
+        assert_eq!(names(&f.disabled), ["a.jar"]);
+    }
+}
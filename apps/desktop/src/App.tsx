@@ -3,7 +3,13 @@ import { Home } from "./pages/Home";
 import { Browse } from "./pages/Browse";
 import { Installed } from "./pages/Installed";
 import { SettingsPage } from "./pages/Settings";
-import { loadSettings, type Settings } from "./lib/settings";
+import {
+  defaultSettings,
+  loadSettings,
+  onSettingsChanged,
+  type Settings,
+} from "./lib/settings";
+import { describeRecovery } from "./lib/modsDb";
 import { checkHealth } from "./lib/api";
 import { Spinner } from "./components/ui";
 
@@ -18,7 +24,7 @@ const PAGES: { id: PageId; label: string }[] = [
 
 function App() {
   const [page, setPage] = useState<PageId>("home");
-  const [settings, setSettings] = useState<Settings>(() => loadSettings());
+  const [settings, setSettings] = useState<Settings | null>(null);
   const [proxyReady, setProxyReady] = useState(false);
   const [proxyError, setProxyError] = useState<string | null>(null);
 
@@ -26,8 +32,37 @@ function App() {
     setSettings(s);
   }, []);
 
+  // Settings live in Rust (settings.json); the first load also migrates old localStorage values.
+  useEffect(() => {
+    let cancelled = false;
+    loadSettings()
+      .then(({ data, recovered }) => {
+        if (recovered) console.warn("[app]", describeRecovery(recovered));
+        if (!cancelled) setSettings(data);
+      })
+      .catch((err) => {
+        console.error("[app] Failed to load settings:", err);
+        if (!cancelled) setSettings({ ...defaultSettings });
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, []);
+
+  // Rust changes settings itself when the user allows a download host.
+  useEffect(() => {
+    const unlisten = onSettingsChanged(setSettings);
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const proxyBaseUrl = settings?.proxyBaseUrl;
+
   // Wait for the proxy sidecar (auto-started by Rust on app boot) to be ready
   useEffect(() => {
+    if (!proxyBaseUrl) return;
+    const baseUrl = proxyBaseUrl;
     let cancelled = false;
 
     async function waitForProxyHealth() {
@@ -41,7 +76,7 @@ function App() {
         for (let i = 0; i < maxRetries; i++) {
           if (cancelled) return;
 
-          const healthy = await checkHealth(settings.proxyBaseUrl);
+          const healthy = await checkHealth(baseUrl);
           if (healthy) {
             console.log("[app] Proxy is ready!");
             setProxyReady(true);
@@ -72,10 +107,10 @@ function App() {
     return () => {
       cancelled = true;
     };
-  }, [settings.proxyBaseUrl]);
+  }, [proxyBaseUrl]);
 
   // Show loading screen while proxy is starting
-  if (!proxyReady && !proxyError) {
+  if (!settings || (!proxyReady && !proxyError)) {
     return (
       <div className="relative min-h-screen flex flex-col">
         {/* Background with overlay */}
@@ -182,7 +217,10 @@ function App() {
           />
         )}
         {page === "settings" && (
-          <SettingsPage onSettingsChange={handleSettingsChange} />
+          <SettingsPage
+            settings={settings}
+            onSettingsChange={handleSettingsChange}
+          />
         )}
       </main>
     </div>
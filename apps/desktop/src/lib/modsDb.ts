@@ -4,6 +4,8 @@
  */
 
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import type { ModFile } from "@hyghertales/shared";
 
 export interface InstalledModRecord {
   id?: number;
@@ -18,12 +20,96 @@ export interface InstalledModRecord {
   sourceUrl?: string | null;
   enabled: boolean;
   pinned?: boolean;
+  /** "sha1:<hex>" or "md5:<hex>" the file was verified against when downloaded. */
+  fileHash?: string | null;
 }
 
-export async function readInstalledMods(): Promise<InstalledModRecord[]> {
-  return invoke<InstalledModRecord[]>("read_installed_mods");
+/** Set when a data file failed to parse and was repaired on read. */
+export interface RecoveryReport {
+  file: string;
+  error: string;
+  quarantinedPath: string;
+  action: "restoredBackup" | "rebuiltFromDisk" | "reset" | "recreated";
+  backupPath: string | null;
 }
 
+export interface LoadResult<T> {
+  data: T;
+  recovered: RecoveryReport | null;
+}
+
+/** Read installed mods; if the file is corrupt it is recovered (rebuilt from modsDir as a last resort). */
+export async function loadInstalledMods(
+  modsDir?: string | null
+): Promise<LoadResult<InstalledModRecord[]>> {
+  return invoke<LoadResult<InstalledModRecord[]>>("read_installed_mods", {
+    modsDir: modsDir ?? null,
+  });
+}
+
+export async function readInstalledMods(
+  modsDir?: string | null
+): Promise<InstalledModRecord[]> {
+  return (await loadInstalledMods(modsDir)).data;
+}
+
+/** Human-readable summary of a recovery, for display on the Installed page. */
+export function describeRecovery(report: RecoveryReport): string {
+  const what =
+    report.action === "restoredBackup"
+      ? `restored from backup ${report.backupPath ?? ""}`.trim()
+      : report.action === "rebuiltFromDisk"
+        ? "rebuilt from the files in your Mods folders"
+        : report.action === "recreated"
+          ? "recreated from installed_mods.json and profiles.json where those exist"
+          : "reset to empty";
+  return `The ${report.file} file was unreadable and has been ${what}. The damaged file was kept at ${report.quarantinedPath}.`;
+}
+
+/** Fields of a record that can be changed with updateInstalledMod; `null` clears a nullable field. */
+export type InstalledModPatch = Partial<
+  Omit<InstalledModRecord, "id" | "provider">
+>;
+
+/** Add a record; the id is assigned in Rust. Fails if the same project/resource is already installed. */
+export async function addInstalledMod(
+  record: Omit<InstalledModRecord, "id">
+): Promise<InstalledModRecord> {
+  return invoke<InstalledModRecord>("add_installed_mod", { record });
+}
+
+export async function updateInstalledMod(
+  id: number,
+  patch: InstalledModPatch
+): Promise<InstalledModRecord> {
+  return invoke<InstalledModRecord>("update_installed_mod", { id, patch });
+}
+
+export async function removeInstalledMod(
+  id: number
+): Promise<InstalledModRecord> {
+  return invoke<InstalledModRecord>("remove_installed_mod", { id });
+}
+
+export async function setModsEnabled(
+  ids: number[],
+  enabled: boolean
+): Promise<void> {
+  return invoke("set_mods_enabled", { ids, enabled });
+}
+
+/**
+ * Enable or disable mods in one transaction: their files move between Mods and Mods.disabled and
+ * their records are saved together. Returns the updated records.
+ */
+export async function setModsEnabledWithFiles(
+  ids: number[],
+  enabled: boolean
+): Promise<InstalledModRecord[]> {
+  return invoke<InstalledModRecord[]>("set_mods_enabled_with_files", { ids, enabled });
+}
+
+/** Replace the whole list. Prefer the record-level functions above. */
 export async function writeInstalledMods(
   mods: InstalledModRecord[]
 ): Promise<void> {
@@ -53,35 +139,361 @@ export async function listModDirFileNames(dirPath: string): Promise<string[]> {
   return invoke("list_mod_dir_file_names", { dirPath });
 }
 
-/** Download URL to path; returns final path (may differ if collision). */
+/** Payload of the Rust `download-progress` event. */
+export interface DownloadProgress {
+  downloadId: string;
+  received: number;
+  /** From Content-Length; null if the server did not send one. */
+  total: number | null;
+  bytesPerSec: number;
+  etaSecs: number | null;
+  done: boolean;
+  /** 1 for the first attempt, counting up with every retry. */
+  attempt: number;
+  maxAttempts: number;
+  /** Set while waiting to retry after lastError. */
+  retryInSecs: number | null;
+  lastError: string | null;
+}
+
+/** Published hashes and length a download must match before it is kept. */
+export interface ExpectedFile {
+  sha1?: string | null;
+  md5?: string | null;
+  size?: number | null;
+}
+
+export function expectedFileOf(file: ModFile): ExpectedFile {
+  return { sha1: file.sha1, md5: file.md5, size: file.fileLength };
+}
+
+/** Value for InstalledModRecord.fileHash once a download matched `expected`. */
+export function verifiedFileHash(expected: ExpectedFile): string | null {
+  if (expected.sha1) return `sha1:${expected.sha1.toLowerCase()}`;
+  if (expected.md5) return `md5:${expected.md5.toLowerCase()}`;
+  return null;
+}
+
+/** True for the SIZE_MISMATCH / CHECKSUM_MISMATCH errors of a download that failed verification. */
+export function isIntegrityError(e: unknown): boolean {
+  const msg = e instanceof Error ? e.message : String(e);
+  return msg.startsWith("SIZE_MISMATCH") || msg.startsWith("CHECKSUM_MISMATCH");
+}
+
+/** True if the server sent something other than a ZIP/JAR (e.g. an error page with a 200). */
+export function isNotArchiveError(e: unknown): boolean {
+  const msg = e instanceof Error ? e.message : String(e);
+  return msg.startsWith("NOT_A_MOD_ARCHIVE");
+}
+
+const HOST_NOT_ALLOWED = "HOST_NOT_ALLOWED: ";
+
+/** Host of a download refused because it (or a redirect) left the allowlist, else null. */
+export function blockedHostOf(e: unknown): string | null {
+  const msg = e instanceof Error ? e.message : String(e);
+  if (!msg.startsWith(HOST_NOT_ALLOWED)) return null;
+  return msg.slice(HOST_NOT_ALLOWED.length).split(" ")[0] || null;
+}
+
+/**
+ * Ask the user (in a native dialog) to allow downloads from a host outside the allowlist.
+ * Resolves true if the host is allowed now; it is remembered in settings.
+ */
+export async function trustDownloadHost(host: string): Promise<boolean> {
+  return invoke("trust_download_host", { url: `https://${host}/` });
+}
+
+function expectedArgs(expected: ExpectedFile = {}) {
+  return {
+    expectedSha1: expected.sha1 ?? null,
+    expectedMd5: expected.md5 ?? null,
+    expectedSize: expected.size ?? null,
+  };
+}
+
+/** Download cache key of a provider file, or undefined if the file ids are missing. */
+export function cacheKeyOf(
+  provider: "curseforge" | "orbis",
+  file: { fileId?: number | null; versionId?: string | null; fileIndex?: number | null }
+): string | undefined {
+  if (provider === "curseforge" && file.fileId != null) return `curseforge:${file.fileId}`;
+  if (provider === "orbis" && file.versionId != null && file.fileIndex != null)
+    return `orbis:${file.versionId}:${file.fileIndex}`;
+  return undefined;
+}
+
+export interface DownloadOptions {
+  /** Id used to tag progress events; generated when onProgress is set and no id is given. */
+  downloadId?: string;
+  onProgress?: (progress: DownloadProgress) => void;
+  /** Verified before the file is renamed into place; a mismatch deletes it. */
+  expected?: ExpectedFile;
+  /** See cacheKeyOf; lets the download be served from the local cache. */
+  cacheKey?: string;
+  /**
+   * Name the file as the server does (Content-Disposition or the final URL), in the directory of
+   * destPath. destPath's file name is used when the server does not send one.
+   */
+  useServerFileName?: boolean;
+}
+
+/** Where a download ended up (Rust `download::DownloadedFile`). */
+export interface DownloadedFile {
+  path: string;
+  fileName: string;
+}
+
+/**
+ * Download URL to path; returns the final path and file name (they may differ from destPath on a
+ * collision, or with useServerFileName). If the download is
+ * interrupted, calling again with the same URL and path resumes where it stopped. A host
+ * outside the allowlist is only downloaded from after the user confirms it.
+ */
 export async function downloadFileToPath(
   url: string,
-  destPath: string
-): Promise<string> {
-  return invoke("download_file_to_path", { url, destPath });
+  destPath: string,
+  options: DownloadOptions = {}
+): Promise<DownloadedFile> {
+  const { onProgress } = options;
+  const downloadId =
+    options.downloadId ?? (onProgress ? crypto.randomUUID() : undefined);
+  const unlisten = onProgress
+    ? await listen<DownloadProgress>("download-progress", (event) => {
+        if (event.payload.downloadId === downloadId) onProgress(event.payload);
+      })
+    : null;
+  const request = {
+    url,
+    destPath,
+    downloadId: downloadId ?? null,
+    ...expectedArgs(options.expected),
+    cacheKey: options.cacheKey ?? null,
+    useServerFileName: options.useServerFileName ?? false,
+  };
+  try {
+    try {
+      return await invoke<DownloadedFile>("download_file_to_path", { request });
+    } catch (e) {
+      const host = blockedHostOf(e);
+      if (host == null || !(await trustDownloadHost(host))) throw e;
+      return await invoke<DownloadedFile>("download_file_to_path", { request });
+    }
+  } finally {
+    unlisten?.();
+  }
 }
 
-/** Safe update: backup old file to Mods.backup, move new temp file into place. Returns new filename. */
+export interface DownloadItem {
+  url: string;
+  destPath: string;
+  /** Item id within the batch; defaults to the item's index. Also tags its progress events. */
+  downloadId?: string;
+  expected?: ExpectedFile;
+  cacheKey?: string;
+  /** See DownloadOptions.useServerFileName. */
+  useServerFileName?: boolean;
+}
+
+/** State of one batch item (Rust `download_manager::ItemStatus`). */
+export type DownloadItemStatus =
+  | { state: "queued" }
+  | { state: "running" }
+  | { state: "paused" }
+  | ({ state: "done" } & DownloadedFile)
+  | { state: "failed"; error: string }
+  | { state: "cancelled" };
+
+/** Payload of the Rust `download-status` event. */
+export interface DownloadItemUpdate {
+  batchId: string;
+  itemId: string;
+  status: DownloadItemStatus;
+}
+
+export interface DownloadBatchSummary {
+  batchId: string;
+  /** Same order as the items passed in. */
+  items: DownloadItemUpdate[];
+}
+
+export interface DownloadBatchOptions {
+  /** Parallel downloads; the backend defaults to 3 and caps at 8. */
+  concurrency?: number;
+  onProgress?: (progress: DownloadProgress) => void;
+  onStatus?: (update: DownloadItemUpdate) => void;
+}
+
+/**
+ * Download several files, a few at a time. Resolves once every item is done, failed or
+ * cancelled; items can be paused, resumed or cancelled meanwhile with the functions below.
+ */
+export async function runDownloadBatch(
+  batchId: string,
+  items: DownloadItem[],
+  options: DownloadBatchOptions = {}
+): Promise<DownloadBatchSummary> {
+  const { onProgress, onStatus } = options;
+  const ids = new Set(items.map((item, i) => item.downloadId ?? String(i)));
+  const unlisteners = await Promise.all([
+    onProgress
+      ? listen<DownloadProgress>("download-progress", (event) => {
+          if (ids.has(event.payload.downloadId)) onProgress(event.payload);
+        })
+      : null,
+    onStatus
+      ? listen<DownloadItemUpdate>("download-status", (event) => {
+          if (event.payload.batchId === batchId) onStatus(event.payload);
+        })
+      : null,
+  ]);
+  try {
+    return await invoke<DownloadBatchSummary>("run_download_batch", {
+      batchId,
+      items: items.map((item) => ({
+        url: item.url,
+        destPath: item.destPath,
+        downloadId: item.downloadId ?? null,
+        ...expectedArgs(item.expected),
+        cacheKey: item.cacheKey ?? null,
+        useServerFileName: item.useServerFileName ?? false,
+      })),
+      concurrency: options.concurrency ?? null,
+    });
+  } finally {
+    unlisteners.forEach((unlisten) => unlisten?.());
+  }
+}
+
+/** Pause one item, or the whole batch when itemId is omitted. Partial files are kept. */
+export async function pauseDownload(batchId: string, itemId?: string): Promise<DownloadItemUpdate[]> {
+  return invoke("pause_download", { batchId, itemId: itemId ?? null });
+}
+
+export async function resumeDownload(batchId: string, itemId?: string): Promise<DownloadItemUpdate[]> {
+  return invoke("resume_download", { batchId, itemId: itemId ?? null });
+}
+
+/** Cancel one item, or the whole batch when itemId is omitted. Partial files are deleted. */
+export async function cancelDownload(batchId: string, itemId?: string): Promise<DownloadItemUpdate[]> {
+  return invoke("cancel_download", { batchId, itemId: itemId ?? null });
+}
+
+export interface DownloadCacheUsage {
+  path: string;
+  files: number;
+  totalBytes: number;
+  limitBytes: number;
+}
+
+export async function getDownloadCacheUsage(): Promise<DownloadCacheUsage> {
+  return invoke("get_download_cache_usage");
+}
+
+/** Delete every cached download. Returns the new (empty) usage. */
+export async function clearDownloadCache(): Promise<DownloadCacheUsage> {
+  return invoke("clear_download_cache");
+}
+
+/** Temp file or resume metadata of an unfinished download (see Rust `download::is_partial_download`). */
+export function isPartialDownload(fileName: string): boolean {
+  return fileName.endsWith(".tmp") || fileName.endsWith(".tmp.meta");
+}
+
+export function formatBytes(n: number): string {
+  if (n < 1024) return `${n} B`;
+  if (n < 1024 * 1024) return `${(n / 1024).toFixed(1)} KB`;
+  if (n < 1024 * 1024 * 1024) return `${(n / (1024 * 1024)).toFixed(1)} MB`;
+  return `${(n / (1024 * 1024 * 1024)).toFixed(2)} GB`;
+}
+
+/** e.g. "3.2 MB / 10.0 MB · 1.1 MB/s · 7s left" */
+export function formatDownloadProgress(p: DownloadProgress): string {
+  if (p.retryInSecs != null) {
+    return `Attempt ${p.attempt}/${p.maxAttempts} failed, retrying in ${Math.ceil(p.retryInSecs)}s`;
+  }
+  const parts = [
+    p.total != null
+      ? `${formatBytes(p.received)} / ${formatBytes(p.total)}`
+      : formatBytes(p.received),
+  ];
+  if (p.bytesPerSec > 0) parts.push(`${formatBytes(Math.round(p.bytesPerSec))}/s`);
+  if (p.etaSecs != null && !p.done) parts.push(`${Math.ceil(p.etaSecs)}s left`);
+  if (p.attempt > 1) parts.push(`attempt ${p.attempt}/${p.maxAttempts}`);
+  return parts.join(" · ");
+}
+
+/** The mod applyModUpdate installs a new file for, saved with the file move. */
+export interface ModUpdateRecord {
+  modId: number;
+  installedFileId?: number | string | null;
+  /** Hash the new file was verified against; null clears the old file's hash. */
+  fileHash?: string | null;
+}
+
+/**
+ * Safe update: backup old file to Mods.backup, move new temp file into place. With `update`, the
+ * replaced file is recorded in the backup index (for rollbackMod) and the mod's record is saved in
+ * the same transaction. Returns the new filename and the saved record.
+ */
 export async function applyModUpdate(
   oldPath: string,
   newTempPath: string,
   finalDir: string,
-  newFilename: string
-): Promise<string> {
+  newFilename: string,
+  update?: ModUpdateRecord
+): Promise<{ fileName: string; record: InstalledModRecord | null }> {
   return invoke("apply_mod_update", {
     oldPath,
     newTempPath,
     finalDir,
     newFilename,
+    update: update ?? null,
   });
 }
 
-/** Generate next id for a new record (max existing + 1). */
-export function nextId(mods: InstalledModRecord[]): number {
-  const ids = mods
-    .map((m) => m.id)
-    .filter((id): id is number => typeof id === "number");
-  return ids.length > 0 ? Math.max(...ids) + 1 : 1;
+/** A replaced mod file kept in Mods.backup, and the mod's record while it was installed. */
+export interface ModBackup {
+  id: number;
+  modId: number;
+  /** File name in Mods.backup. */
+  fileName: string;
+  createdAt: string;
+  record: InstalledModRecord;
+}
+
+/** Backups of a mod, newest first (only those whose file still exists). */
+export async function listModBackups(modId: number): Promise<ModBackup[]> {
+  return invoke<ModBackup[]>("list_mod_backups", { modId });
+}
+
+/**
+ * Put a backed-up version back: the installed file becomes a new backup and the record is
+ * restored from the backup (enabled and pinned are kept). Returns the restored record.
+ */
+export async function rollbackMod(
+  modId: number,
+  backupId: number
+): Promise<InstalledModRecord> {
+  return invoke<InstalledModRecord>("rollback_mod", { modId, backupId });
+}
+
+/** File type filter of a file dialog. */
+export interface FileFilter {
+  name: string;
+  extensions: string[];
+}
+
+/**
+ * Ask where to save a file in a native dialog. Only paths chosen this way (or inside the app
+ * data and Mods folders) can be written with writeTextFile. Null if cancelled.
+ */
+export async function pickSavePath(defaultName?: string, filter?: FileFilter): Promise<string | null> {
+  return invoke("pick_save_path", { defaultName: defaultName ?? null, filter: filter ?? null });
+}
+
+/** Ask for a file to open in a native dialog, so it can be read with readTextFile. */
+export async function pickOpenPath(filter?: FileFilter): Promise<string | null> {
+  return invoke("pick_open_path", { filter: filter ?? null });
 }
 
 /** Write UTF-8 text to a file (e.g. export profile JSON). */
@@ -4,6 +4,7 @@
  */
 
 import { invoke } from "@tauri-apps/api/core";
+import type { InstalledModRecord, LoadResult } from "./modsDb";
 
 export interface ProfileRecord {
   id: number;
@@ -18,67 +19,90 @@ export interface ProfilesData {
   profiles: ProfileRecord[];
 }
 
+/** What the Rust validator repaired when reading or writing profiles. */
+export interface ProfilesValidation {
+  repairs: string[];
+  /** Mod ids removed from profiles because the mod is no longer installed. */
+  danglingModIds: Array<{ profileId: number; modId: number }>;
+}
+
+export type ProfilesLoadResult = LoadResult<ProfilesData> & {
+  validation: ProfilesValidation;
+};
+
+export async function loadProfiles(): Promise<ProfilesLoadResult> {
+  return invoke<ProfilesLoadResult>("read_profiles");
+}
+
 export async function readProfiles(): Promise<ProfilesData> {
-  return invoke<ProfilesData>("read_profiles");
+  return (await loadProfiles()).data;
 }
 
-export async function writeProfiles(data: ProfilesData): Promise<void> {
-  return invoke("write_profiles", { data });
+/** Replace all profiles. Rejects duplicate ids or an unknown active profile. */
+export async function writeProfiles(
+  data: ProfilesData
+): Promise<ProfilesValidation> {
+  return invoke<ProfilesValidation>("write_profiles", { data });
 }
 
-export function createProfile(
-  data: ProfilesData,
+/** Create a profile (id assigned in Rust); optionally make it active. */
+export async function createProfile(
   name: string,
-  enabledModIds: number[]
-): ProfileRecord {
-  const id = data.nextId;
-  return {
-    id,
+  enabledModIds: number[],
+  setActive: boolean
+): Promise<ProfileRecord> {
+  return invoke<ProfileRecord>("create_profile", {
     name,
-    createdAt: new Date().toISOString(),
-    enabledModIds: [...enabledModIds],
-  };
+    enabledModIds,
+    setActive,
+  });
 }
 
-export function nextProfileId(data: ProfilesData): number {
-  return data.nextId;
+export async function renameProfile(
+  profileId: number,
+  name: string
+): Promise<void> {
+  return invoke("rename_profile", { profileId, name });
 }
 
-/** Returns new ProfilesData with the new profile added and optionally set as active. */
-export function addProfile(
-  data: ProfilesData,
-  profile: ProfileRecord,
-  setActive: boolean
-): ProfilesData {
-  const profiles = [...data.profiles, profile];
-  return {
-    nextId: data.nextId + 1,
-    activeProfileId: setActive ? profile.id : data.activeProfileId,
-    profiles,
-  };
+/** Delete a profile. If it was active, the first remaining profile becomes active. */
+export async function deleteProfile(profileId: number): Promise<void> {
+  return invoke("delete_profile", { profileId });
 }
 
-/** Returns new ProfilesData with the profile removed. Clears active if it was this profile. */
-export function deleteProfile(
-  data: ProfilesData,
-  profileId: number
-): ProfilesData {
-  const profiles = data.profiles.filter((p) => p.id !== profileId);
-  const activeProfileId =
-    data.activeProfileId === profileId
-      ? profiles[0]?.id ?? null
-      : data.activeProfileId;
-  return { ...data, profiles, activeProfileId };
+export async function setActiveProfile(profileId: number | null): Promise<void> {
+  return invoke("set_active_profile", { profileId });
 }
 
-/** Returns new ProfilesData with the profile renamed. */
-export function renameProfile(
-  data: ProfilesData,
+/** A mod the profile wants switched that could not be (left as it was). */
+export interface MissingMod {
+  modId: number;
+  /** null if no mod with this id is installed. */
+  name: string | null;
+  reason: string;
+}
+
+/** What applyProfile changed; `enabled` / `disabled` are the updated records. */
+export interface ApplyProfileSummary {
+  profileId: number;
+  enabled: InstalledModRecord[];
+  disabled: InstalledModRecord[];
+  missing: MissingMod[];
+}
+
+/**
+ * Switch to a profile in one step: moves the mods whose state differs between Mods and
+ * Mods.disabled, updates their records and makes the profile active. Nothing changes if a
+ * move fails (error starts with TRANSACTION_FAILED).
+ */
+export async function applyProfile(profileId: number): Promise<ApplyProfileSummary> {
+  return invoke<ApplyProfileSummary>("apply_profile", { profileId });
+}
+
+/** Replace the list of mods enabled by a profile. */
+export async function setProfileMods(
   profileId: number,
-  name: string
-): ProfilesData {
-  const profiles = data.profiles.map((p) =>
-    p.id === profileId ? { ...p, name } : p
-  );
-  return { ...data, profiles };
+  modIds: number[]
+): Promise<void> {
+  return invoke("set_profile_mods", { profileId, modIds });
 }
@@ -1,9 +1,14 @@
 /**
- * Settings stored in localStorage (persists in the Tauri webview app data).
- * For a production app you could switch to Tauri plugin store or JSON in app data dir.
+ * Settings stored in settings.json in the app data dir (read and validated by Rust).
+ * Older builds kept them in localStorage; those values are migrated on first run.
  */
 
-const STORAGE_KEY = "hyghertales-settings";
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import type { LoadResult } from "./modsDb";
+
+/** localStorage key used before settings moved to Rust. Only read for the one-time migration. */
+const LEGACY_STORAGE_KEY = "hyghertales-settings";
 
 const defaultProxyBaseUrl =
   typeof import.meta.env.VITE_PROXY_BASE_URL === "string" &&
@@ -12,55 +17,123 @@ const defaultProxyBaseUrl =
     : "http://localhost:8787";
 
 export interface Settings {
+  /** HygherTales proxy. Only changed through setProxyBaseUrl; updateSettings keeps the saved value. */
   proxyBaseUrl: string;
   hytaleUserDataPath: string | null;
+  /** Only changed through pickModsDir or setModsDir; updateSettings keeps the saved value. */
   modsDirPath: string | null;
   gameExePath: string | null;
+  /** Size limit of the download cache in MB; 0 turns it off. */
+  downloadCacheLimitMb: number;
+  /** Retries of a download after a connection error, 429 or 5xx response (at most 10). */
+  downloadMaxRetries: number;
+  /** Seconds; 0 means no limit. */
+  httpConnectTimeoutSecs: number;
+  /** Longest a download may stall without data, in seconds; 0 means no limit. */
+  httpReadTimeoutSecs: number;
+  /**
+   * http://, https://, socks5:// or socks5h:// proxy for downloads; null for a direct connection.
+   * Only changed through setHttpProxyUrl; updateSettings keeps the saved value.
+   */
+  httpProxyUrl: string | null;
+  /** PEM or DER files with extra root certificates to trust. Only added through addCaCert. */
+  extraCaCerts: string[];
+  /**
+   * Download hosts the user allowed besides the mod CDNs. Hosts are only added through
+   * trustDownloadHost (which asks in a native dialog); saving settings can only remove them.
+   */
+  trustedDownloadHosts: string[];
 }
 
-const defaults: Settings = {
+export const defaultSettings: Settings = {
   proxyBaseUrl: defaultProxyBaseUrl,
   hytaleUserDataPath: null,
   modsDirPath: null,
   gameExePath: null,
+  downloadCacheLimitMb: 1024,
+  downloadMaxRetries: 3,
+  httpConnectTimeoutSecs: 15,
+  httpReadTimeoutSecs: 60,
+  httpProxyUrl: null,
+  extraCaCerts: [],
+  trustedDownloadHosts: [],
 };
 
-export function loadSettings(): Settings {
+function readLegacySettings(): Partial<Settings> | null {
   try {
-    const raw = localStorage.getItem(STORAGE_KEY);
-    if (!raw) return { ...defaults };
-    const parsed = JSON.parse(raw) as Partial<Settings>;
-    return {
-      proxyBaseUrl:
-        typeof parsed.proxyBaseUrl === "string" && parsed.proxyBaseUrl.trim()
-          ? parsed.proxyBaseUrl.trim()
-          : defaults.proxyBaseUrl,
-      hytaleUserDataPath:
-        typeof parsed.hytaleUserDataPath === "string" &&
-        parsed.hytaleUserDataPath.trim()
-          ? parsed.hytaleUserDataPath.trim()
-          : null,
-      modsDirPath:
-        typeof parsed.modsDirPath === "string" && parsed.modsDirPath.trim()
-          ? parsed.modsDirPath.trim()
-          : null,
-      gameExePath:
-        typeof parsed.gameExePath === "string" && parsed.gameExePath.trim()
-          ? parsed.gameExePath.trim()
-          : null,
-    };
+    const raw = localStorage.getItem(LEGACY_STORAGE_KEY);
+    return raw ? (JSON.parse(raw) as Partial<Settings>) : null;
   } catch {
-    return { ...defaults };
+    return null;
+  }
+}
+
+/**
+ * Load settings from Rust. On first run (no settings.json yet) the old localStorage values,
+ * or the defaults, are saved as the initial settings; after that the legacy key is removed.
+ */
+export async function loadSettings(): Promise<LoadResult<Settings>> {
+  const legacy = readLegacySettings();
+  const result = await invoke<LoadResult<Settings>>("get_settings", {
+    initial: { ...defaultSettings, ...legacy },
+  });
+  if (legacy) {
+    try {
+      localStorage.removeItem(LEGACY_STORAGE_KEY);
+    } catch {
+      /* ignore */
+    }
   }
+  return result;
+}
+
+/** Validate and save. Rejects with a message if a path or the proxy URL is unusable. */
+export async function updateSettings(settings: Settings): Promise<Settings> {
+  return invoke<Settings>("update_settings", { settings });
+}
+
+/**
+ * Send downloads through a proxy after the user confirms it in a native dialog; null turns it
+ * off. Resolves to the saved settings, or null if the user cancelled.
+ */
+export async function setHttpProxyUrl(url: string | null): Promise<Settings | null> {
+  return invoke<Settings | null>("set_http_proxy_url", { url });
 }
 
-export function saveSettings(settings: Settings): void {
-  localStorage.setItem(STORAGE_KEY, JSON.stringify(settings));
+/**
+ * Use another HygherTales proxy. One that is not on this machine is confirmed in a native dialog
+ * (which also allows downloads from it). Resolves to the saved settings, or null if cancelled.
+ */
+export async function setProxyBaseUrl(url: string): Promise<Settings | null> {
+  return invoke<Settings | null>("set_proxy_base_url", { url });
+}
+
+/** Pick the Mods folder in a native dialog and save it. Null if the user cancelled. */
+export async function pickModsDir(): Promise<Settings | null> {
+  return invoke<Settings | null>("pick_mods_dir");
+}
+
+/**
+ * Use a typed or auto-detected Mods folder after the user confirms it in a native dialog; null
+ * unsets it. Resolves to the saved settings, or null if cancelled.
+ */
+export async function setModsDir(path: string | null): Promise<Settings | null> {
+  return invoke<Settings | null>("set_mods_dir", { path });
+}
+
+/** Pick a root certificate to trust in a native dialog. Null if the user cancelled. */
+export async function addCaCert(): Promise<Settings | null> {
+  return invoke<Settings | null>("add_ca_cert");
 }
 
 /** Last selected mod source on Browse (CurseForge vs Orbis). */
 const BROWSE_SOURCE_KEY = "hyghertales-browse-source";
 
+/** Called with the new settings when Rust saves them on its own (e.g. after trusting a host). */
+export function onSettingsChanged(handler: (settings: Settings) => void): Promise<UnlistenFn> {
+  return listen<Settings>("settings-changed", (event) => handler(event.payload));
+}
+
 export type BrowseModSource = "curseforge" | "orbis";
 
 export function loadBrowseSource(): BrowseModSource {
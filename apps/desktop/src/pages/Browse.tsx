@@ -13,11 +13,16 @@ import {
 } from "../lib/api";
 import { marked } from "marked";
 import {
-  readInstalledMods,
-  writeInstalledMods,
+  addInstalledMod,
   downloadFileToPath,
-  nextId,
-  type InstalledModRecord,
+  expectedFileOf,
+  cacheKeyOf,
+  verifiedFileHash,
+  isIntegrityError,
+  blockedHostOf,
+  isNotArchiveError,
+  formatDownloadProgress,
+  type DownloadProgress,
 } from "../lib/modsDb";
 import { loadBrowseSource, saveBrowseSource } from "../lib/settings";
 import { openExternalUrl } from "../lib/shell";
@@ -59,6 +64,19 @@ interface BrowseProps {
   modsDirPath: string | null;
 }
 
+function describeInstallError(e: unknown): string {
+  if (e instanceof ApiError) return e.body?.message ?? e.message;
+  const host = blockedHostOf(e);
+  if (host) return `Downloads from ${host} are not allowed. It is not a known mod host.`;
+  if (isNotArchiveError(e)) {
+    return "The server did not send a mod archive (maybe an error page or an expired link). Nothing was installed.";
+  }
+  if (isIntegrityError(e)) {
+    return "The downloaded file did not match the size or checksum published for it and was deleted. Try again.";
+  }
+  return "Download failed";
+}
+
 export function Browse({ proxyBaseUrl, modsDirPath }: BrowseProps) {
   const [source, setSource] = useState<ModSource>(loadBrowseSource);
   const [query, setQuery] = useState("");
@@ -79,6 +97,7 @@ export function Browse({ proxyBaseUrl, modsDirPath }: BrowseProps) {
   const [detailLoading, setDetailLoading] = useState(false);
   const [detailError, setDetailError] = useState<string | null>(null);
   const [downloadingFile, setDownloadingFile] = useState<string | null>(null);
+  const [downloadProgress, setDownloadProgress] = useState<DownloadProgress | null>(null);
 
   const loadFeatured = useCallback(async () => {
     setError(null);
@@ -244,11 +263,16 @@ export function Browse({ proxyBaseUrl, modsDirPath }: BrowseProps) {
           const baseDir = modsDirPath.trim().replace(/\\/g, "/").replace(/\/$/, "");
           const fileName = file.fileName || file.displayName || "mod.jar";
           const destPath = `${baseDir}/${fileName}`;
-          const finalPath = await downloadFileToPath(url, destPath);
-          const installedFilename = finalPath.replace(/^.*[/\\]/, "");
-          const existing = await readInstalledMods();
-          const newRecord: InstalledModRecord = {
-            id: nextId(existing),
+          const expected = expectedFileOf(file);
+          const downloaded = await downloadFileToPath(url, destPath, {
+            downloadId: key,
+            onProgress: setDownloadProgress,
+            expected,
+            cacheKey: cacheKeyOf(detail.provider, file),
+            useServerFileName: !file.fileName,
+          });
+          const installedFilename = downloaded.fileName;
+          await addInstalledMod({
             provider: detail.provider,
             projectId: detail.provider === "curseforge" ? detail.projectId : null,
             resourceId: detail.provider === "orbis" ? detail.resourceId : null,
@@ -267,18 +291,17 @@ export function Browse({ proxyBaseUrl, modsDirPath }: BrowseProps) {
                 ? `https://www.orbis.place/mod/${detail.slug}`
                 : `https://www.curseforge.com/hytale/mods/${detail.slug}`,
             enabled: true,
-          };
-          await writeInstalledMods([...existing, newRecord]);
+            fileHash: verifiedFileHash(expected),
+          });
           setDetailError(null);
         } else {
           await openExternalUrl(url);
         }
       } catch (e) {
-        setDetailError(
-          e instanceof ApiError ? e.body?.message ?? e.message : "Download failed"
-        );
+        setDetailError(describeInstallError(e));
       } finally {
         setDownloadingFile(null);
+        setDownloadProgress(null);
       }
     },
     [proxyBaseUrl, selectedMod, detail, modsDirPath]
@@ -586,6 +609,11 @@ export function Browse({ proxyBaseUrl, modsDirPath }: BrowseProps) {
                             {file.releaseType && `${file.releaseType} · `}
                             {file.fileDate ? new Date(file.fileDate).toLocaleDateString() : ""}
                           </div>
+                          {isDownloading && downloadProgress && (
+                            <div className="text-xs text-[var(--color-text-muted)]" aria-live="polite">
+                              {formatDownloadProgress(downloadProgress)}
+                            </div>
+                          )}
                         </div>
                         <Button
                           size="sm"
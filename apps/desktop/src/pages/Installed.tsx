@@ -1,30 +1,53 @@
 import { useState, useCallback, useEffect } from "react";
 import {
   readInstalledMods,
-  writeInstalledMods,
+  loadInstalledMods,
+  describeRecovery,
+  addInstalledMod,
+  updateInstalledMod,
+  removeInstalledMod,
+  setModsEnabled,
+  setModsEnabledWithFiles,
   ensureModsDisabledDir,
-  moveModFile,
   moveFileToTrash,
   listModDirFileNames,
   downloadFileToPath,
+  runDownloadBatch,
+  expectedFileOf,
+  cacheKeyOf,
+  verifiedFileHash,
+  isIntegrityError,
+  blockedHostOf,
+  isNotArchiveError,
+  type ExpectedFile,
+  pauseDownload,
+  resumeDownload,
+  cancelDownload,
   applyModUpdate,
-  nextId,
+  listModBackups,
+  rollbackMod,
+  type ModBackup,
   writeTextFile,
   readTextFile,
+  pickSavePath,
+  pickOpenPath,
+  formatDownloadProgress,
+  isPartialDownload,
+  type DownloadProgress,
   type InstalledModRecord,
 } from "../lib/modsDb";
 import {
-  readProfiles,
-  writeProfiles,
+  loadProfiles as loadProfilesData,
   createProfile,
-  addProfile,
-  deleteProfile as deleteProfileFromData,
-  renameProfile as renameProfileInData,
+  deleteProfile,
+  renameProfile,
+  setActiveProfile,
+  setProfileMods,
+  applyProfile,
   type ProfileRecord,
   type ProfilesData,
 } from "../lib/profilesDb";
 import { openPath } from "../lib/shell";
-import { open as openFileDialog, save as saveFileDialog } from "@tauri-apps/plugin-dialog";
 import { getModFiles, getDownloadUrlCurseForge, getDownloadUrlOrbis, ApiError } from "../lib/api";
 import { getLatestFile, isUpdateAvailable } from "../lib/updates";
 import type { ModFile } from "@hyghertales/shared";
@@ -54,6 +77,23 @@ function updateKey(mod: InstalledModRecord): string {
   return mod.id != null ? String(mod.id) : `${mod.provider}-${mod.slug}`;
 }
 
+/** User-facing message for a failed URL lookup or download. */
+function describeDownloadError(e: unknown): string {
+  if (e instanceof ApiError && e.status === 503) {
+    return "This mod's download is restricted by CurseForge distribution settings. You may need to download it manually from the website.";
+  }
+  if (e instanceof ApiError) return e.body?.message ?? e.message;
+  const msg = e instanceof Error ? e.message : String(e);
+  const host = blockedHostOf(e);
+  if (host) return `Downloads from ${host} are not allowed. It is not a known mod host.`;
+  if (isNotArchiveError(e)) {
+    return "The server did not send a mod archive (maybe an error page or an expired link). Nothing was installed.";
+  }
+  return isIntegrityError(e)
+    ? `Downloaded file failed verification and was deleted (${msg}).`
+    : msg;
+}
+
 /** Export format: list of mod identifiers for import. */
 interface ExportedProfileMod {
   provider: "curseforge" | "orbis";
@@ -82,9 +122,17 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     inDisabled: string[];
   } | null>(null);
   const [removeConfirm, setRemoveConfirm] = useState<InstalledModRecord | null>(null);
+  const [backupsModal, setBackupsModal] = useState<{
+    mod: InstalledModRecord;
+    backups: ModBackup[];
+  } | null>(null);
+  const [rollingBack, setRollingBack] = useState(false);
   const [updateMap, setUpdateMap] = useState<Record<string, ModFile>>({});
   const [checkingUpdates, setCheckingUpdates] = useState(false);
   const [updatingIds, setUpdatingIds] = useState<Set<number>>(new Set());
+  const [updateProgress, setUpdateProgress] = useState<Record<number, DownloadProgress>>({});
+  /** Batch currently run by "Update all" or profile import. */
+  const [downloadBatch, setDownloadBatch] = useState<{ id: string; paused: boolean } | null>(null);
   const [updateError, setUpdateError] = useState<string | null>(null);
   const [updateSuccess, setUpdateSuccess] = useState<string | null>(null);
   const [profilesData, setProfilesData] = useState<ProfilesData | null>(null);
@@ -95,7 +143,6 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     profile: ProfileRecord;
     toEnable: InstalledModRecord[];
     toDisable: InstalledModRecord[];
-    progress?: { done: number; total: number };
   } | null>(null);
   const [applyingProfile, setApplyingProfile] = useState(false);
   const [exportImportError, setExportImportError] = useState<string | null>(null);
@@ -109,8 +156,9 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     setLoading(true);
     setError(null);
     try {
-      const list = await readInstalledMods();
-      
+      const { data: list, recovered } = await loadInstalledMods(modsDirPath);
+      if (recovered) setActionError(describeRecovery(recovered));
+
       // Verify enabled state matches actual file location
       if (modsDirPath) {
         const disabledDirPath = getDisabledDir(modsDirPath);
@@ -121,7 +169,8 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         const modsSet = new Set(modsFiles);
         const disabledSet = new Set(disabledFiles);
         
-        let needsUpdate = false;
+        const nowEnabled: number[] = [];
+        const nowDisabled: number[] = [];
         const verified = list.map((mod) => {
           const inMods = modsSet.has(mod.installedFilename);
           const inDisabled = disabledSet.has(mod.installedFilename);
@@ -129,18 +178,17 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
           
           // Sync DB state with actual file location
           if (mod.enabled !== actualEnabled && (inMods || inDisabled)) {
-            needsUpdate = true;
+            if (mod.id != null) {
+              (actualEnabled ? nowEnabled : nowDisabled).push(mod.id);
+            }
             return { ...mod, enabled: actualEnabled };
           }
           return mod;
         });
         
-        if (needsUpdate) {
-          await writeInstalledMods(verified);
-          setMods(verified);
-        } else {
-          setMods(list);
-        }
+        if (nowEnabled.length > 0) await setModsEnabled(nowEnabled, true);
+        if (nowDisabled.length > 0) await setModsEnabled(nowDisabled, false);
+        setMods(verified);
       } else {
         setMods(list);
       }
@@ -154,7 +202,14 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
 
   const loadProfiles = useCallback(async () => {
     try {
-      const data = await readProfiles();
+      const { data, recovered, validation } = await loadProfilesData();
+      if (recovered) setActionError(describeRecovery(recovered));
+      if (validation.danglingModIds.length > 0) {
+        console.warn(
+          "Removed uninstalled mods from profiles:",
+          validation.danglingModIds
+        );
+      }
       setProfilesData(data);
     } catch {
       setProfilesData({ nextId: 1, activeProfileId: null, profiles: [] });
@@ -198,22 +253,9 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
       setActionError(null);
       try {
         await ensureModsDisabledDir(modsDirPath);
-        const fromPath = getFilePath(mod);
-        const toDir = mod.enabled ? disabledDir : modsDirPath;
-        const toPath = `${toDir.replace(/\\/g, "/").replace(/\/$/, "")}/${mod.installedFilename}`;
-        const finalPath = await moveModFile(fromPath, toPath);
-        const newFilename = finalPath.replace(/^.*[/\\]/, "");
-        const updated = mods.map((m) =>
-          m.id === mod.id
-            ? {
-                ...m,
-                enabled: !m.enabled,
-                installedFilename: newFilename,
-              }
-            : m
-        );
-        await writeInstalledMods(updated);
-        setMods(updated);
+        const [saved] = await setModsEnabledWithFiles([mod.id!], !mod.enabled);
+        const updated = mods.map((m) => (m.id === mod.id ? saved : m));
+        setMods((prev) => prev.map((m) => (m.id === mod.id ? saved : m)));
         if (profilesData?.activeProfileId != null && mod.id != null) {
           const active = profilesData.profiles.find(
             (p) => p.id === profilesData.activeProfileId
@@ -222,19 +264,18 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
             const enabledIds = updated
               .filter((m) => m.enabled && m.id != null)
               .map((m) => m.id!);
+            await setProfileMods(active.id, enabledIds);
             const next = profilesData.profiles.map((p) =>
               p.id === active.id ? { ...p, enabledModIds: enabledIds } : p
             );
-            const nextData = { ...profilesData, profiles: next };
-            await writeProfiles(nextData);
-            setProfilesData(nextData);
+            setProfilesData({ ...profilesData, profiles: next });
           }
         }
       } catch (e) {
         setActionError(String(e));
       }
     },
-    [modsDirPath, disabledDir, mods, getFilePath, profilesData]
+    [modsDirPath, mods, profilesData]
   );
 
   const handleRemove = useCallback(
@@ -244,9 +285,8 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
       setActionError(null);
       try {
         await moveFileToTrash(path);
-        const updated = mods.filter((m) => m.id !== mod.id);
-        await writeInstalledMods(updated);
-        setMods(updated);
+        if (mod.id != null) await removeInstalledMod(mod.id);
+        setMods((prev) => prev.filter((m) => m.id !== mod.id));
       } catch (e) {
         setActionError(String(e));
       }
@@ -264,8 +304,11 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         listModDirFileNames(disabledDir),
       ]);
       const trackedMods = new Set(mods.map((m) => m.installedFilename));
-      const untrackedInMods = inMods.filter((f) => !trackedMods.has(f));
-      const untrackedInDisabled = inDisabled.filter((f) => !trackedMods.has(f));
+      // Skip hidden files and unfinished downloads (kept so they can be resumed).
+      const isUntrackedMod = (f: string) =>
+        !trackedMods.has(f) && !f.startsWith(".") && !isPartialDownload(f);
+      const untrackedInMods = inMods.filter(isUntrackedMod);
+      const untrackedInDisabled = inDisabled.filter(isUntrackedMod);
       if (untrackedInMods.length === 0 && untrackedInDisabled.length === 0) {
         setActionError(null);
         return;
@@ -278,8 +321,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
 
   const addUntracked = useCallback(
     async (filename: string, inModsFolder: boolean) => {
-      const newMod: InstalledModRecord = {
-        id: nextId(mods),
+      const newMod = await addInstalledMod({
         provider: "orbis",
         slug: "__untracked__",
         name: filename,
@@ -287,10 +329,8 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         installedAt: new Date().toISOString(),
         enabled: inModsFolder,
         pinned: false,
-      };
-      const updated = [...mods, newMod];
-      await writeInstalledMods(updated);
-      setMods(updated);
+      });
+      setMods((prev) => [...prev, newMod]);
       setRescanModal((prev) => {
         if (!prev) return null;
         if (inModsFolder) {
@@ -307,6 +347,34 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     [mods]
   );
 
+  const openBackups = useCallback(async (mod: InstalledModRecord) => {
+    if (mod.id == null) return;
+    setActionError(null);
+    try {
+      setBackupsModal({ mod, backups: await listModBackups(mod.id) });
+    } catch (e) {
+      setActionError(String(e));
+    }
+  }, []);
+
+  const handleRollback = useCallback(
+    async (backup: ModBackup) => {
+      if (!backupsModal) return;
+      setRollingBack(true);
+      setActionError(null);
+      try {
+        const restored = await rollbackMod(backup.modId, backup.id);
+        setMods((prev) => prev.map((m) => (m.id === restored.id ? restored : m)));
+        setBackupsModal(null);
+      } catch (e) {
+        setActionError(String(e));
+      } finally {
+        setRollingBack(false);
+      }
+    },
+    [backupsModal]
+  );
+
   const ignoreUntracked = useCallback(() => {
     setRescanModal(null);
   }, []);
@@ -338,10 +406,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         setProfilesData((prev) =>
           prev ? { ...prev, activeProfileId: profile.id } : null
         );
-        writeProfiles({
-          ...profilesData!,
-          activeProfileId: profile.id,
-        }).then(() => loadProfiles()).catch(() => {});
+        setActiveProfile(profile.id).then(() => loadProfiles()).catch(() => {});
         return;
       }
       setSwitchDryRun({ profile, toEnable, toDisable });
@@ -359,64 +424,30 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     setActionError(null);
     setExportImportError(null);
     try {
-      await ensureModsDisabledDir(modsDirPath);
-      const baseMods = modsDirPath.replace(/\\/g, "/").replace(/\/$/, "");
-      const baseDisabled = disabledDir.replace(/\\/g, "/").replace(/\/$/, "");
-      let updatedMods = [...mods];
-      const total = switchDryRun.toEnable.length + switchDryRun.toDisable.length;
-      let done = 0;
-      for (const mod of switchDryRun.toDisable) {
-        const fromPath = getFilePath(mod);
-        const toPath = `${baseDisabled}/${mod.installedFilename}`;
-        const finalPath = await moveModFile(fromPath, toPath);
-        const newFilename = finalPath.replace(/^.*[/\\]/, "");
-        updatedMods = updatedMods.map((m) =>
-          m.id === mod.id
-            ? { ...m, enabled: false, installedFilename: newFilename }
-            : m
-        );
-        done++;
-        setSwitchDryRun((prev) =>
-          prev ? { ...prev, progress: { done, total } } : null
-        );
-      }
-      for (const mod of switchDryRun.toEnable) {
-        const fromPath = getFilePath({ ...mod, enabled: false });
-        const toPath = `${baseMods}/${mod.installedFilename}`;
-        const finalPath = await moveModFile(fromPath, toPath);
-        const newFilename = finalPath.replace(/^.*[/\\]/, "");
-        updatedMods = updatedMods.map((m) =>
-          m.id === mod.id
-            ? { ...m, enabled: true, installedFilename: newFilename }
-            : m
-        );
-        done++;
-        setSwitchDryRun((prev) =>
-          prev ? { ...prev, progress: { done, total } } : null
-        );
-      }
-      await writeInstalledMods(updatedMods);
-      setMods(updatedMods);
-      const nextData = {
+      const summary = await applyProfile(switchDryRun.profile.id);
+      const changed = new Map(
+        [...summary.enabled, ...summary.disabled].map((m) => [m.id, m])
+      );
+      setMods((prev) => prev.map((m) => changed.get(m.id) ?? m));
+      setProfilesData({
         ...profilesData,
-        activeProfileId: switchDryRun.profile.id,
-      };
-      await writeProfiles(nextData);
-      setProfilesData(nextData);
+        activeProfileId: summary.profileId,
+      });
       setSwitchDryRun(null);
+      if (summary.missing.length > 0) {
+        const names = summary.missing
+          .map((m) => `${m.name ?? `mod #${m.modId}`} (${m.reason})`)
+          .join(", ");
+        setActionError(
+          `Switched to "${switchDryRun.profile.name}", but some mods were left as they were: ${names}`
+        );
+      }
     } catch (e) {
       setActionError(String(e));
     } finally {
       setApplyingProfile(false);
     }
-  }, [
-    switchDryRun,
-    modsDirPath,
-    profilesData,
-    mods,
-    disabledDir,
-    getFilePath,
-  ]);
+  }, [switchDryRun, modsDirPath, profilesData]);
 
   const handleCreateProfile = useCallback(
     async (name: string, fromCurrent: boolean) => {
@@ -424,35 +455,31 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
       const enabledIds = fromCurrent
         ? mods.filter((m) => m.enabled && m.id != null).map((m) => m.id!)
         : [];
-      const newProfile = createProfile(profilesData, name.trim(), enabledIds);
-      const nextData = addProfile(profilesData, newProfile, true);
-      await writeProfiles(nextData);
-      setProfilesData(nextData);
+      await createProfile(name.trim(), enabledIds, true);
+      await loadProfiles();
       setProfileModal(null);
     },
-    [profilesData, mods]
+    [profilesData, mods, loadProfiles]
   );
 
   const handleRenameProfile = useCallback(
     async (profileId: number, name: string) => {
       if (!profilesData || name.trim() === "") return;
-      const nextData = renameProfileInData(profilesData, profileId, name.trim());
-      await writeProfiles(nextData);
-      setProfilesData(nextData);
+      await renameProfile(profileId, name.trim());
+      await loadProfiles();
       setProfileModal(null);
     },
-    [profilesData]
+    [profilesData, loadProfiles]
   );
 
   const handleDeleteProfile = useCallback(
     async (profile: ProfileRecord) => {
       if (!profilesData) return;
-      const nextData = deleteProfileFromData(profilesData, profile.id);
-      await writeProfiles(nextData);
-      setProfilesData(nextData);
+      await deleteProfile(profile.id);
+      await loadProfiles();
       setProfileModal(null);
     },
-    [profilesData]
+    [profilesData, loadProfiles]
   );
 
   const handleExportProfile = useCallback(
@@ -492,10 +519,10 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         }),
       };
       try {
-        const path = await saveFileDialog({
-          filters: [{ name: "JSON", extensions: ["json"] }],
-          defaultPath: `hyghertales-profile-${profile.name.replace(/[^a-z0-9-_]/gi, "-")}.json`,
-        });
+        const path = await pickSavePath(
+          `hyghertales-profile-${profile.name.replace(/[^a-z0-9-_]/gi, "-")}.json`,
+          { name: "JSON", extensions: ["json"] }
+        );
         if (path) {
           await writeTextFile(path, JSON.stringify(exported, null, 2));
         }
@@ -510,18 +537,22 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     if (!modsDirPath?.trim() || !profilesData) return;
     setExportImportError(null);
     try {
-      const path = await openFileDialog({
-        filters: [{ name: "JSON", extensions: ["json"] }],
-      });
-      if (!path || typeof path !== "string") return;
+      const path = await pickOpenPath({ name: "JSON", extensions: ["json"] });
+      if (!path) return;
       const raw = await readTextFile(path);
       const parsed = JSON.parse(raw) as ExportedProfile;
       if (!parsed.name || !Array.isArray(parsed.mods)) {
         throw new Error("Invalid profile file format.");
       }
-      let currentMods = await readInstalledMods();
+      let currentMods = await readInstalledMods(modsDirPath);
       const enabledIds: number[] = [];
       const importSkipped: Array<{ name: string; reason: string }> = [];
+      const pending: Array<{
+        entry: ExportedProfileMod;
+        entryName: string;
+        url: string;
+        expected: ExpectedFile;
+      }> = [];
       for (const entry of parsed.mods as ExportedProfileMod[]) {
         const entryName = entry.name || entry.slug || "unknown mod";
         const match = currentMods.find((m) => {
@@ -536,6 +567,8 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
           enabledIds.push(match.id);
           continue;
         }
+        // Only known when the latest file is looked up below.
+        let expected: ExpectedFile = {};
         try {
           if (entry.provider === "curseforge" && entry.fileId == null && entry.projectId != null) {
             const res = await getModFiles(proxyBaseUrl, "curseforge", String(entry.projectId));
@@ -545,6 +578,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
               continue;
             }
             entry.fileId = latest.fileId;
+            expected = expectedFileOf(latest);
           }
           if (entry.provider === "orbis" && (entry.versionId == null || entry.fileIndex == null) && entry.resourceId != null) {
             const res = await getModFiles(proxyBaseUrl, "orbis", entry.resourceId);
@@ -555,6 +589,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
             }
             entry.versionId = latest.versionId;
             entry.fileIndex = latest.fileIndex;
+            expected = expectedFileOf(latest);
           }
           let url: string;
           if (entry.provider === "curseforge" && entry.projectId != null && entry.fileId != null) {
@@ -577,36 +612,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
             importSkipped.push({ name: entryName, reason: "missing identifier to resolve download URL" });
             continue;
           }
-          const baseDir = modsDirPath.replace(/\\/g, "/").replace(/\/$/, "");
-          const fileName = `${entry.slug || "mod"}.jar`;
-          const destPath = `${baseDir}/${fileName}`;
-          const finalPath = await downloadFileToPath(url, destPath);
-          const installedFilename = finalPath.replace(/^.*[/\\]/, "");
-          const newId = nextId(currentMods);
-          const newRecord: InstalledModRecord = {
-            id: newId,
-            provider: entry.provider,
-            projectId: entry.provider === "curseforge" ? entry.projectId : null,
-            resourceId: entry.provider === "orbis" ? entry.resourceId : null,
-            slug: entry.slug,
-            name: entry.name,
-            installedFileId:
-              entry.provider === "curseforge"
-                ? entry.fileId ?? null
-                : entry.versionId != null && entry.fileIndex != null
-                  ? `${entry.versionId}:${entry.fileIndex}`
-                  : null,
-            installedFilename,
-            installedAt: new Date().toISOString(),
-            sourceUrl:
-              entry.provider === "orbis"
-                ? `https://www.orbis.place/mod/${entry.slug}`
-                : undefined,
-            enabled: true,
-          };
-          currentMods = [...currentMods, newRecord];
-          await writeInstalledMods(currentMods);
-          enabledIds.push(newId);
+          pending.push({ entry, entryName, url, expected });
         } catch (e) {
           const reason = e instanceof ApiError && e.status === 503
             ? "download restricted by CurseForge distribution settings"
@@ -614,56 +620,82 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
           importSkipped.push({ name: entryName, reason });
         }
       }
-      setMods(currentMods);
-      const newProfile = createProfile(
-        profilesData,
-        `Imported: ${parsed.name}`,
-        enabledIds
+
+      // Download everything that was resolved in one batch. Files get the name the server
+      // gives them; the slug is only the fallback.
+      const baseDir = modsDirPath.replace(/\\/g, "/").replace(/\/$/, "");
+      const usedNames = new Set<string>();
+      const items = pending.map(({ entry, url, expected }, i) => {
+        let fileName = `${entry.slug || "mod"}.jar`;
+        if (usedNames.has(fileName)) fileName = `${entry.slug || "mod"}-${i}.jar`;
+        usedNames.add(fileName);
+        return {
+          url,
+          destPath: `${baseDir}/${fileName}`,
+          downloadId: `import-${i}`,
+          expected,
+          cacheKey: cacheKeyOf(entry.provider, entry),
+          useServerFileName: true,
+        };
+      });
+      const batchId = `import-${Date.now()}`;
+      setDownloadBatch({ id: batchId, paused: false });
+      const summary = await runDownloadBatch(batchId, items).finally(() =>
+        setDownloadBatch(null)
       );
-      const nextData = addProfile(profilesData, newProfile, true);
-      await writeProfiles(nextData);
-      setProfilesData(nextData);
-      await ensureModsDisabledDir(modsDirPath);
-      const baseMods = modsDirPath.replace(/\\/g, "/").replace(/\/$/, "");
-      const baseDisabled = getDisabledDir(modsDirPath).replace(/\\/g, "/").replace(/\/$/, "");
-      const enabledSet = new Set(enabledIds);
-      let finalMods = currentMods.map((m) => ({
-        ...m,
-        enabled: m.id != null && enabledSet.has(m.id),
-      }));
-      for (const mod of currentMods) {
-        const shouldEnable = mod.id != null && enabledSet.has(mod.id);
-        if (shouldEnable && !mod.enabled) {
-          const fromPath = `${baseDisabled}/${mod.installedFilename}`;
-          const toPath = `${baseMods}/${mod.installedFilename}`;
-          try {
-            const finalPath = await moveModFile(fromPath, toPath);
-            const newFilename = finalPath.replace(/^.*[/\\]/, "");
-            finalMods = finalMods.map((m) =>
-              m.id === mod.id ? { ...m, enabled: true, installedFilename: newFilename } : m
-            );
-          } catch {
-            /* file may already be in Mods */
-          }
-        } else if (!shouldEnable && mod.enabled) {
-          const fromPath = `${baseMods}/${mod.installedFilename}`;
-          const toPath = `${baseDisabled}/${mod.installedFilename}`;
-          try {
-            const finalPath = await moveModFile(fromPath, toPath);
-            const newFilename = finalPath.replace(/^.*[/\\]/, "");
-            finalMods = finalMods.map((m) =>
-              m.id === mod.id ? { ...m, enabled: false, installedFilename: newFilename } : m
-            );
-          } catch {
-            /* file may already be in Mods.disabled */
-          }
+      for (const [i, { entry, entryName, expected }] of pending.entries()) {
+        const status = summary.items[i].status;
+        if (status.state !== "done") {
+          importSkipped.push({
+            name: entryName,
+            reason: status.state === "failed" ? status.error : "download cancelled",
+          });
+          continue;
         }
+        const installedFilename = status.fileName;
+        const newRecord = await addInstalledMod({
+          provider: entry.provider,
+          projectId: entry.provider === "curseforge" ? entry.projectId : null,
+          resourceId: entry.provider === "orbis" ? entry.resourceId : null,
+          slug: entry.slug,
+          name: entry.name,
+          installedFileId:
+            entry.provider === "curseforge"
+              ? entry.fileId ?? null
+              : entry.versionId != null && entry.fileIndex != null
+                ? `${entry.versionId}:${entry.fileIndex}`
+                : null,
+          installedFilename,
+          installedAt: new Date().toISOString(),
+          sourceUrl:
+            entry.provider === "orbis"
+              ? `https://www.orbis.place/mod/${entry.slug}`
+              : undefined,
+          enabled: true,
+          fileHash: verifiedFileHash(expected),
+        });
+        currentMods = [...currentMods, newRecord];
+        enabledIds.push(newRecord.id!);
       }
-      await writeInstalledMods(finalMods);
-      setMods(finalMods);
-      if (importSkipped.length > 0) {
+      setMods(currentMods);
+      const profile = await createProfile(`Imported: ${parsed.name}`, enabledIds, true);
+      await ensureModsDisabledDir(modsDirPath);
+      // Moves the files and saves the records and active profile in one transaction.
+      const applied = await applyProfile(profile.id);
+      await loadProfiles();
+      const changed = new Map(
+        [...applied.enabled, ...applied.disabled].map((m) => [m.id, m])
+      );
+      setMods(currentMods.map((m) => changed.get(m.id) ?? m));
+      const problems = [
+        ...importSkipped.map((s) => `• ${s.name}: could not be installed (${s.reason})`),
+        ...applied.missing.map(
+          (m) => `• ${m.name ?? `mod #${m.modId}`}: left as it was (${m.reason})`
+        ),
+      ];
+      if (problems.length > 0) {
         setExportImportError(
-          `Import complete. ${importSkipped.length} mod(s) could not be downloaded:\n${importSkipped.map((s) => `• ${s.name}: ${s.reason}`).join("\n")}`
+          `Import complete. ${problems.length} mod(s) need attention:\n${problems.join("\n")}`
         );
       }
     } catch (e) {
@@ -673,6 +705,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     modsDirPath,
     profilesData,
     proxyBaseUrl,
+    loadProfiles,
   ]);
 
   const canCheckUpdate = (mod: InstalledModRecord): boolean => {
@@ -740,11 +773,11 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
   const togglePinned = useCallback(
     async (mod: InstalledModRecord) => {
       setActionError(null);
-      const updated = mods.map((m) =>
-        m.id === mod.id ? { ...m, pinned: !(m.pinned ?? false) } : m
-      );
-      await writeInstalledMods(updated);
-      setMods(updated);
+      if (mod.id == null) return;
+      const saved = await updateInstalledMod(mod.id, {
+        pinned: !(mod.pinned ?? false),
+      });
+      setMods((prev) => prev.map((m) => (m.id === mod.id ? saved : m)));
       if (mod.pinned) return;
       setUpdateMap((prev) => {
         const key = updateKey(mod);
@@ -753,106 +786,128 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         return next;
       });
     },
-    [mods]
+    []
   );
 
+  /** Resolve the download URL of an update through the proxy. */
+  const resolveUpdateUrl = useCallback(
+    async (mod: InstalledModRecord, latestFile: ModFile): Promise<string> => {
+      if (mod.provider === "curseforge" && mod.projectId != null && latestFile.fileId != null) {
+        const res = await getDownloadUrlCurseForge(
+          proxyBaseUrl,
+          mod.projectId,
+          latestFile.fileId
+        );
+        return res.url;
+      }
+      if (
+        mod.provider === "orbis" &&
+        mod.resourceId != null &&
+        latestFile.versionId != null &&
+        latestFile.fileIndex != null
+      ) {
+        const res = await getDownloadUrlOrbis(
+          proxyBaseUrl,
+          mod.resourceId,
+          latestFile.versionId,
+          latestFile.fileIndex
+        );
+        return res.url;
+      }
+      if (latestFile.downloadUrl) return latestFile.downloadUrl;
+      throw new Error("Cannot get download URL for this file.");
+    },
+    [proxyBaseUrl]
+  );
+
+  /** Where an update is downloaded before it replaces the installed file. */
+  const updateTarget = useCallback(
+    (mod: InstalledModRecord, latestFile: ModFile) => {
+      const finalDir = mod.enabled ? modsDirPath! : disabledDir;
+      const baseDir = finalDir.replace(/\\/g, "/").replace(/\/$/, "");
+      const newFilename = latestFile.fileName || latestFile.displayName || "mod.jar";
+      // Stable per mod and file, so a retry resumes an interrupted download. The `.ht-update-`
+      // prefix lets the backend clear leftovers at startup.
+      const tempPath = `${baseDir}/.ht-update-${mod.id}-${newFilename}`;
+      return { finalDir, newFilename, tempPath };
+    },
+    [modsDirPath, disabledDir]
+  );
+
+  /** Swap the downloaded update into place and record the new file. */
+  const finishUpdate = useCallback(
+    async (mod: InstalledModRecord, latestFile: ModFile, downloadedPath: string) => {
+      const { finalDir, newFilename } = updateTarget(mod, latestFile);
+      const { record: saved } = await applyModUpdate(
+        getFilePath(mod),
+        downloadedPath,
+        finalDir,
+        newFilename,
+        {
+          modId: mod.id!,
+          installedFileId:
+            mod.provider === "curseforge"
+              ? latestFile.fileId ?? mod.installedFileId
+              : latestFile.versionId != null && latestFile.fileIndex != null
+                ? `${latestFile.versionId}:${latestFile.fileIndex}`
+                : mod.installedFileId,
+          // null clears the hash of the replaced file.
+          fileHash: verifiedFileHash(expectedFileOf(latestFile)),
+        }
+      );
+      if (saved) setMods((prev) => prev.map((m) => (m.id === mod.id ? saved : m)));
+      setUpdateMap((prev) => {
+        const next = { ...prev };
+        delete next[updateKey(mod)];
+        return next;
+      });
+    },
+    [updateTarget, getFilePath]
+  );
+
+  const clearUpdating = useCallback((modIds: number[]) => {
+    setUpdatingIds((s) => {
+      const next = new Set(s);
+      modIds.forEach((id) => next.delete(id));
+      return next;
+    });
+    setUpdateProgress((prev) => {
+      const next = { ...prev };
+      modIds.forEach((id) => delete next[id]);
+      return next;
+    });
+  }, []);
+
   const updateOne = useCallback(
     async (mod: InstalledModRecord) => {
-      const key = updateKey(mod);
-      const latestFile = updateMap[key];
+      const latestFile = updateMap[updateKey(mod)];
       if (!latestFile || !modsDirPath || mod.id == null) return;
       setUpdateError(null);
       setUpdatingIds((s) => new Set(s).add(mod.id!));
-      const finalDir = mod.enabled ? modsDirPath : disabledDir;
-      const baseDir = finalDir.replace(/\\/g, "/").replace(/\/$/, "");
-      const oldPath = getFilePath(mod);
-      const newFilename = latestFile.fileName || latestFile.displayName || "mod.jar";
-      const tempPath = `${baseDir}/.ht-update-${Date.now()}-${newFilename}`;
       try {
-        let url: string;
-        if (mod.provider === "curseforge" && mod.projectId != null && latestFile.fileId != null) {
-          const res = await getDownloadUrlCurseForge(
-            proxyBaseUrl,
-            mod.projectId,
-            latestFile.fileId
-          );
-          url = res.url;
-        } else if (
-          mod.provider === "orbis" &&
-          mod.resourceId != null &&
-          latestFile.versionId != null &&
-          latestFile.fileIndex != null
-        ) {
-          const res = await getDownloadUrlOrbis(
-            proxyBaseUrl,
-            mod.resourceId,
-            latestFile.versionId,
-            latestFile.fileIndex
-          );
-          url = res.url;
-        } else if (latestFile.downloadUrl) {
-          url = latestFile.downloadUrl;
-        } else {
-          throw new Error("Cannot get download URL for this file.");
-        }
-        const downloadedPath = await downloadFileToPath(url, tempPath);
-        const finalFilename = await applyModUpdate(
-          oldPath,
-          downloadedPath,
-          finalDir,
-          newFilename
-        );
-        const updated = mods.map((m) =>
-          m.id === mod.id
-            ? {
-                ...m,
-                installedFilename: finalFilename,
-                installedAt: new Date().toISOString(),
-                installedFileId:
-                  mod.provider === "curseforge"
-                    ? latestFile.fileId ?? m.installedFileId
-                    : latestFile.versionId != null && latestFile.fileIndex != null
-                      ? `${latestFile.versionId}:${latestFile.fileIndex}`
-                      : m.installedFileId,
-              }
-            : m
-        );
-        await writeInstalledMods(updated);
-        setMods(updated);
-        setUpdateError(null);
-        setUpdateMap((prev) => {
-          const next = { ...prev };
-          delete next[key];
-          return next;
+        const url = await resolveUpdateUrl(mod, latestFile);
+        const modId = mod.id!;
+        const downloaded = await downloadFileToPath(url, updateTarget(mod, latestFile).tempPath, {
+          downloadId: `update-${modId}`,
+          onProgress: (p) => setUpdateProgress((prev) => ({ ...prev, [modId]: p })),
+          expected: expectedFileOf(latestFile),
+          cacheKey: cacheKeyOf(mod.provider, latestFile),
         });
+        await finishUpdate(mod, latestFile, downloaded.path);
+        setUpdateError(null);
       } catch (e) {
-        const msg =
-          e instanceof ApiError && e.status === 503
-            ? "This mod's download is restricted by CurseForge distribution settings. You may need to download it manually from the website."
-            : e instanceof ApiError
-              ? e.body?.message ?? e.message
-              : String(e);
+        const msg = describeDownloadError(e);
         setUpdateError(msg);
         throw new Error(msg);
       } finally {
-        setUpdatingIds((s) => {
-          const next = new Set(s);
-          next.delete(mod.id!);
-          return next;
-        });
+        clearUpdating([mod.id!]);
       }
     },
-    [
-      updateMap,
-      modsDirPath,
-      disabledDir,
-      mods,
-      getFilePath,
-      proxyBaseUrl,
-    ]
+    [updateMap, modsDirPath, resolveUpdateUrl, updateTarget, finishUpdate, clearUpdating]
   );
 
   const updateAll = useCallback(async () => {
+    if (!modsDirPath) return;
     const toUpdate = mods.filter(
       (m) => m.id != null && !m.pinned && updateMap[updateKey(m)] != null
     );
@@ -860,29 +915,97 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
     setUpdateSuccess(null);
     const succeeded: string[] = [];
     const failed: Array<{ name: string; reason: string }> = [];
-    for (const mod of toUpdate) {
-      try {
-        await updateOne(mod);
-        succeeded.push(mod.name);
-      } catch (e) {
-        failed.push({ name: mod.name, reason: e instanceof Error ? e.message : String(e) });
+    let cancelled = 0;
+    const modIds = toUpdate.map((m) => m.id!);
+    setUpdatingIds((s) => new Set([...s, ...modIds]));
+    try {
+      // Resolve URLs first, then download everything in one batch.
+      const pending: Array<{ mod: InstalledModRecord; latestFile: ModFile; url: string }> = [];
+      for (const mod of toUpdate) {
+        const latestFile = updateMap[updateKey(mod)];
+        try {
+          pending.push({ mod, latestFile, url: await resolveUpdateUrl(mod, latestFile) });
+        } catch (e) {
+          failed.push({ name: mod.name, reason: describeDownloadError(e) });
+        }
+      }
+      const batchId = `update-all-${Date.now()}`;
+      const modIdByItem = new Map(pending.map(({ mod }) => [`update-${mod.id}`, mod.id!]));
+      setDownloadBatch({ id: batchId, paused: false });
+      const summary = await runDownloadBatch(
+        batchId,
+        pending.map(({ mod, latestFile, url }) => ({
+          url,
+          destPath: updateTarget(mod, latestFile).tempPath,
+          downloadId: `update-${mod.id}`,
+          expected: expectedFileOf(latestFile),
+          cacheKey: cacheKeyOf(mod.provider, latestFile),
+        })),
+        {
+          onProgress: (p) => {
+            const modId = modIdByItem.get(p.downloadId);
+            if (modId != null) setUpdateProgress((prev) => ({ ...prev, [modId]: p }));
+          },
+        }
+      );
+      setDownloadBatch(null);
+      for (const [i, { mod, latestFile }] of pending.entries()) {
+        const status = summary.items[i].status;
+        if (status.state === "done") {
+          try {
+            await finishUpdate(mod, latestFile, status.path);
+            succeeded.push(mod.name);
+          } catch (e) {
+            failed.push({ name: mod.name, reason: describeDownloadError(e) });
+          }
+        } else if (status.state === "failed") {
+          failed.push({ name: mod.name, reason: describeDownloadError(status.error) });
+        } else {
+          cancelled++;
+        }
       }
+    } catch (e) {
+      setDownloadBatch(null);
+      setUpdateError(describeDownloadError(e));
+      return;
+    } finally {
+      clearUpdating(modIds);
     }
+    const cancelledNote = cancelled > 0 ? ` ${cancelled} cancelled.` : "";
     if (failed.length === 0) {
       setUpdateError(null);
-      setUpdateSuccess(`Updated ${succeeded.length} mod(s) successfully.`);
+      setUpdateSuccess(`Updated ${succeeded.length} mod(s) successfully.${cancelledNote}`);
     } else if (succeeded.length === 0) {
       setUpdateSuccess(null);
       setUpdateError(
-        `All ${failed.length} update(s) failed:\n${failed.map((f) => `• ${f.name}: ${f.reason}`).join("\n")}`
+        `All ${failed.length} update(s) failed:${cancelledNote}\n${failed.map((f) => `• ${f.name}: ${f.reason}`).join("\n")}`
       );
     } else {
-      setUpdateSuccess(`Updated ${succeeded.length} mod(s) successfully.`);
+      setUpdateSuccess(`Updated ${succeeded.length} mod(s) successfully.${cancelledNote}`);
       setUpdateError(
         `${failed.length} update(s) failed:\n${failed.map((f) => `• ${f.name}: ${f.reason}`).join("\n")}`
       );
     }
-  }, [mods, updateMap, updateOne]);
+  }, [mods, modsDirPath, updateMap, resolveUpdateUrl, updateTarget, finishUpdate, clearUpdating]);
+
+  const toggleBatchPaused = useCallback(async () => {
+    if (!downloadBatch) return;
+    try {
+      if (downloadBatch.paused) await resumeDownload(downloadBatch.id);
+      else await pauseDownload(downloadBatch.id);
+      setDownloadBatch({ ...downloadBatch, paused: !downloadBatch.paused });
+    } catch {
+      /* batch already finished */
+    }
+  }, [downloadBatch]);
+
+  const cancelBatch = useCallback(
+    async (itemId?: string) => {
+      if (!downloadBatch) return;
+      await cancelDownload(downloadBatch.id, itemId).catch(() => {});
+    },
+    [downloadBatch]
+  );
 
   const hasAnyUpdate = Object.keys(updateMap).length > 0;
 
@@ -916,10 +1039,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
                   setProfilesData((prev) =>
                     prev ? { ...prev, activeProfileId: null } : null
                   );
-                  writeProfiles({
-                    ...profilesData!,
-                    activeProfileId: null,
-                  }).then(() => loadProfiles()).catch(() => {});
+                  setActiveProfile(null).then(() => loadProfiles()).catch(() => {});
                 }
                 return;
               }
@@ -975,7 +1095,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
           >
             Export
           </Button>
-          <Button size="sm" onClick={handleImportProfile}>
+          <Button size="sm" onClick={handleImportProfile} disabled={downloadBatch != null}>
             Import
           </Button>
         </div>
@@ -1005,6 +1125,16 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
             Update all
           </Button>
         )}
+        {downloadBatch && (
+          <>
+            <Button size="sm" onClick={toggleBatchPaused}>
+              {downloadBatch.paused ? "Resume downloads" : "Pause downloads"}
+            </Button>
+            <Button size="sm" variant="danger" onClick={() => cancelBatch()}>
+              Cancel downloads
+            </Button>
+          </>
+        )}
       </div>
 
       {/* Error messages */}
@@ -1089,6 +1219,20 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
                     {mod.enabled ? "Enabled" : "Disabled"}
                     {mod.pinned && " · Pinned"}
                   </div>
+                  {isUpdating && updateProgress[mod.id!] && (
+                    <div className="text-xs text-[var(--color-text-muted)]" aria-live="polite">
+                      Downloading update: {formatDownloadProgress(updateProgress[mod.id!])}
+                      {downloadBatch?.id.startsWith("update-all-") && (
+                        <button
+                          type="button"
+                          className="ml-2 underline hover:text-white"
+                          onClick={() => cancelBatch(`update-${mod.id}`)}
+                        >
+                          Cancel
+                        </button>
+                      )}
+                    </div>
+                  )}
                 </div>
                 <div className="flex flex-wrap gap-2">
                   {!isUntracked(mod) && (
@@ -1123,6 +1267,9 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
                   <Button size="sm" onClick={() => openInFolder(mod)}>
                     Open in folder
                   </Button>
+                  <Button size="sm" onClick={() => openBackups(mod)}>
+                    Versions
+                  </Button>
                   <Button
                     size="sm"
                     variant="danger"
@@ -1160,6 +1307,41 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         </p>
       </Modal>
 
+      {/* Backups modal */}
+      <Modal
+        isOpen={backupsModal !== null}
+        onClose={() => (rollingBack ? undefined : setBackupsModal(null))}
+        title={backupsModal ? `Earlier versions of ${backupsModal.mod.name}` : ""}
+        size="wide"
+        footer={<Button onClick={() => setBackupsModal(null)}>Close</Button>}
+      >
+        {backupsModal &&
+          (backupsModal.backups.length === 0 ? (
+            <p className="text-[var(--color-text)]">
+              No earlier versions are kept in Mods.backup for this mod.
+            </p>
+          ) : (
+            <ul className="space-y-2">
+              {backupsModal.backups.map((b) => (
+                <li key={b.id} className="flex items-center justify-between gap-3">
+                  <span className="text-sm text-[var(--color-text)]">
+                    {b.record.installedFilename} — replaced{" "}
+                    {new Date(b.createdAt).toLocaleString()}
+                  </span>
+                  <Button
+                    size="sm"
+                    onClick={() => handleRollback(b)}
+                    disabled={rollingBack}
+                    isLoading={rollingBack}
+                  >
+                    Restore
+                  </Button>
+                </li>
+              ))}
+            </ul>
+          ))}
+      </Modal>
+
       {/* Rescan modal */}
       <Modal
         isOpen={rescanModal !== null}
@@ -1376,7 +1558,7 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
         title={switchDryRun ? `Switch to "${switchDryRun.profile.name}"` : ""}
         size="wide"
         footer={
-          !switchDryRun?.progress && (
+          !applyingProfile && (
             <>
               <Button onClick={cancelSwitchDryRun}>Cancel</Button>
               <Button
@@ -1393,12 +1575,13 @@ export function Installed({ modsDirPath, proxyBaseUrl }: InstalledProps) {
       >
         {switchDryRun && (
           <div className="space-y-4">
-            {switchDryRun.progress ? (
+            {applyingProfile ? (
               <div className="flex items-center gap-3">
                 <Spinner />
                 <p className="text-[var(--color-text)]">
-                  Applying… {switchDryRun.progress.done} /{" "}
-                  {switchDryRun.progress.total} files moved.
+                  Switching{" "}
+                  {switchDryRun.toEnable.length + switchDryRun.toDisable.length}{" "}
+                  mods…
                 </p>
               </div>
             ) : (
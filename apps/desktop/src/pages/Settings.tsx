@@ -2,25 +2,82 @@ import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { open } from "@tauri-apps/plugin-dialog";
 import type { Settings } from "../lib/settings";
-import { loadSettings, saveSettings } from "../lib/settings";
+import {
+  addCaCert,
+  pickModsDir,
+  setHttpProxyUrl,
+  setModsDir,
+  setProxyBaseUrl,
+  updateSettings,
+} from "../lib/settings";
+import {
+  clearDownloadCache,
+  formatBytes,
+  getDownloadCacheUsage,
+  type DownloadCacheUsage,
+} from "../lib/modsDb";
 import { PageContainer } from "../components/layout/PageContainer";
 import { Button, Input, Label } from "../components/ui";
 
 interface SettingsPageProps {
+  /** Settings as currently saved; edits start from these. */
+  settings: Settings;
   onSettingsChange?: (settings: Settings) => void;
 }
 
 type PathStatus = "idle" | "ok" | "missing" | "not-dir" | "not-writable" | "created" | "error";
 
-export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
-  const [settings, setSettings] = useState<Settings>(() => loadSettings());
+/** Delay before edits are validated and saved, so typing a path does not save every keystroke. */
+const SAVE_DELAY_MS = 400;
+
+export function SettingsPage({
+  settings: savedSettings,
+  onSettingsChange,
+}: SettingsPageProps) {
+  const [settings, setSettings] = useState<Settings>(savedSettings);
+  const [saveError, setSaveError] = useState<string | null>(null);
+  const [modsDirDraft, setModsDirDraft] = useState(savedSettings.modsDirPath ?? "");
+  const [proxyBaseDraft, setProxyBaseDraft] = useState(savedSettings.proxyBaseUrl);
+  const [httpProxyDraft, setHttpProxyDraft] = useState(savedSettings.httpProxyUrl ?? "");
   const [candidates, setCandidates] = useState<string[]>([]);
   const [pathStatus, setPathStatus] = useState<PathStatus>("idle");
   const [pathMessage, setPathMessage] = useState<string>("");
+  const [cacheUsage, setCacheUsage] = useState<DownloadCacheUsage | null>(null);
+  const [cacheError, setCacheError] = useState<string | null>(null);
+
+  // Reload after saves, since a lower limit evicts files.
+  useEffect(() => {
+    getDownloadCacheUsage()
+      .then(setCacheUsage)
+      .catch((e) => setCacheError(String(e)));
+  }, [savedSettings.downloadCacheLimitMb]);
+
+  async function handleClearCache() {
+    setCacheError(null);
+    try {
+      setCacheUsage(await clearDownloadCache());
+    } catch (e) {
+      setCacheError(String(e));
+    }
+  }
 
   useEffect(() => {
-    saveSettings(settings);
-    onSettingsChange?.(settings);
+    let cancelled = false;
+    const timer = setTimeout(() => {
+      updateSettings(settings)
+        .then((saved) => {
+          if (cancelled) return;
+          setSaveError(null);
+          onSettingsChange?.(saved);
+        })
+        .catch((e) => {
+          if (!cancelled) setSaveError(String(e));
+        });
+    }, SAVE_DELAY_MS);
+    return () => {
+      cancelled = true;
+      clearTimeout(timer);
+    };
   }, [settings, onSettingsChange]);
 
   async function handleAutoDetect() {
@@ -38,25 +95,34 @@ export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
     }
   }
 
-  function selectCandidate(path: string) {
-    setSettings((s) => ({ ...s, modsDirPath: path }));
-    setCandidates([]);
+  /** Ask Rust to use a typed or auto-detected folder; it confirms in a native dialog. */
+  async function applyModsDir(path: string) {
     setPathStatus("idle");
     setPathMessage("");
+    try {
+      const saved = await setModsDir(path.trim() || null);
+      if (saved) {
+        applyConfirmed(saved);
+        setModsDirDraft(saved.modsDirPath ?? "");
+        setCandidates([]);
+      }
+    } catch (e) {
+      setPathStatus("error");
+      setPathMessage(String(e));
+    }
   }
 
   async function handleBrowse() {
     setPathStatus("idle");
     setPathMessage("");
     try {
-      const selected = await open({
-        directory: true,
-        multiple: false,
-      });
-      if (selected) {
-        setSettings((s) => ({ ...s, modsDirPath: selected }));
+      const saved = await pickModsDir();
+      if (saved) {
+        applyConfirmed(saved);
+        setModsDirDraft(saved.modsDirPath ?? "");
       }
     } catch (e) {
+      setPathStatus("error");
       setPathMessage(String(e));
     }
   }
@@ -76,8 +142,68 @@ export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
     }
   }
 
+  /** Take over settings Rust saved after a native dialog. */
+  function applyConfirmed(saved: Settings) {
+    setSettings((s) => ({
+      ...s,
+      modsDirPath: saved.modsDirPath,
+      proxyBaseUrl: saved.proxyBaseUrl,
+      trustedDownloadHosts: saved.trustedDownloadHosts,
+      httpProxyUrl: saved.httpProxyUrl,
+      extraCaCerts: saved.extraCaCerts,
+    }));
+    onSettingsChange?.(saved);
+  }
+
+  async function handleApplyProxyBaseUrl() {
+    setSaveError(null);
+    try {
+      const saved = await setProxyBaseUrl(proxyBaseDraft);
+      if (saved) {
+        applyConfirmed(saved);
+        setProxyBaseDraft(saved.proxyBaseUrl);
+      }
+    } catch (e) {
+      setSaveError(String(e));
+    }
+  }
+
+  async function handleApplyHttpProxy() {
+    setSaveError(null);
+    try {
+      const saved = await setHttpProxyUrl(httpProxyDraft.trim() || null);
+      if (saved) {
+        applyConfirmed(saved);
+        setHttpProxyDraft(saved.httpProxyUrl ?? "");
+      }
+    } catch (e) {
+      setSaveError(String(e));
+    }
+  }
+
+  async function handleAddCaCert() {
+    setSaveError(null);
+    try {
+      const saved = await addCaCert();
+      if (saved) applyConfirmed(saved);
+    } catch (e) {
+      setSaveError(String(e));
+    }
+  }
+
+  function removeTrustedHost(host: string) {
+    setSettings((s) => ({
+      ...s,
+      trustedDownloadHosts: s.trustedDownloadHosts.filter((h) => h !== host),
+    }));
+  }
+
+  function removeCaCert(path: string) {
+    setSettings((s) => ({ ...s, extraCaCerts: s.extraCaCerts.filter((p) => p !== path) }));
+  }
+
   async function handleValidate() {
-    const path = settings.modsDirPath?.trim();
+    const path = modsDirDraft.trim();
     setPathMessage("");
     if (!path) {
       setPathStatus("missing");
@@ -114,7 +240,6 @@ export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
       }
       setPathStatus("ok");
       setPathMessage("Path is valid and writable.");
-      saveSettings(settings);
     } catch (e) {
       setPathStatus("error");
       setPathMessage(String(e));
@@ -143,15 +268,26 @@ export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
         {/* Proxy URL */}
         <div>
           <Label htmlFor="proxy-url">Proxy base URL</Label>
-          <Input
-            id="proxy-url"
-            type="url"
-            value={settings.proxyBaseUrl}
-            onChange={(e) =>
-              setSettings((s) => ({ ...s, proxyBaseUrl: e.target.value }))
-            }
-            placeholder="http://localhost:8787"
-          />
+          <div className="flex gap-2">
+            <Input
+              id="proxy-url"
+              type="url"
+              value={proxyBaseDraft}
+              onChange={(e) => setProxyBaseDraft(e.target.value)}
+              placeholder="http://localhost:8787"
+              className="flex-1"
+            />
+            <Button
+              size="sm"
+              onClick={handleApplyProxyBaseUrl}
+              disabled={proxyBaseDraft.trim() === settings.proxyBaseUrl}
+            >
+              Apply
+            </Button>
+          </div>
+          <p className="mt-1 text-sm text-[var(--color-text-muted)]">
+            A proxy that is not on this computer has to be confirmed before it is used.
+          </p>
         </div>
 
         {/* Hytale user data path */}
@@ -178,17 +314,21 @@ export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
             <Input
               id="mods-dir"
               type="text"
-              value={settings.modsDirPath ?? ""}
+              value={modsDirDraft}
               onChange={(e) => {
-                setSettings((s) => ({
-                  ...s,
-                  modsDirPath: e.target.value.trim() || null,
-                }));
+                setModsDirDraft(e.target.value);
                 setPathStatus("idle");
               }}
               placeholder="Path where mods are stored"
               className="flex-1"
             />
+            <Button
+              size="sm"
+              onClick={() => applyModsDir(modsDirDraft)}
+              disabled={modsDirDraft.trim() === (settings.modsDirPath ?? "")}
+            >
+              Apply
+            </Button>
           </div>
           <div className="flex gap-2 mt-2">
             <Button size="sm" onClick={handleAutoDetect}>
@@ -210,7 +350,7 @@ export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
                     <button
                       type="button"
                       className="text-sm text-[#7eb8ff] hover:text-[#a8d4ff] underline focus-visible:outline-none focus-visible:ring-2 focus-visible:ring-[rgba(100,160,100,0.6)]"
-                      onClick={() => selectCandidate(p)}
+                      onClick={() => applyModsDir(p)}
                     >
                       {p}
                     </button>
@@ -252,6 +392,165 @@ export function SettingsPage({ onSettingsChange }: SettingsPageProps) {
             </Button>
           </div>
         </div>
+
+        {/* Network */}
+        <div>
+          <Label htmlFor="http-proxy">Download proxy (optional)</Label>
+          <div className="flex gap-2">
+            <Input
+              id="http-proxy"
+              type="url"
+              value={httpProxyDraft}
+              onChange={(e) => setHttpProxyDraft(e.target.value)}
+              placeholder="http://proxy:3128 or socks5h://proxy:1080"
+              className="flex-1"
+            />
+            <Button
+              size="sm"
+              onClick={handleApplyHttpProxy}
+              disabled={httpProxyDraft.trim() === (settings.httpProxyUrl ?? "")}
+            >
+              Apply
+            </Button>
+          </div>
+          <p className="mt-1 text-sm text-[var(--color-text-muted)]">
+            You are asked to confirm a new proxy before it is used.
+          </p>
+        </div>
+
+        <div className="flex gap-4">
+          <div className="flex-1">
+            <Label htmlFor="connect-timeout">Connect timeout (s, 0 for none)</Label>
+            <Input
+              id="connect-timeout"
+              type="number"
+              min={0}
+              value={settings.httpConnectTimeoutSecs}
+              onChange={(e) =>
+                setSettings((s) => ({
+                  ...s,
+                  httpConnectTimeoutSecs: Math.max(0, Math.floor(Number(e.target.value) || 0)),
+                }))
+              }
+            />
+          </div>
+          <div className="flex-1">
+            <Label htmlFor="read-timeout">Stalled download timeout (s, 0 for none)</Label>
+            <Input
+              id="read-timeout"
+              type="number"
+              min={0}
+              value={settings.httpReadTimeoutSecs}
+              onChange={(e) =>
+                setSettings((s) => ({
+                  ...s,
+                  httpReadTimeoutSecs: Math.max(0, Math.floor(Number(e.target.value) || 0)),
+                }))
+              }
+            />
+          </div>
+        </div>
+
+        <div>
+          <Label>Extra root certificates (optional)</Label>
+          {settings.extraCaCerts.length > 0 && (
+            <ul className="mb-2 space-y-1">
+              {settings.extraCaCerts.map((p) => (
+                <li key={p} className="flex items-center gap-2 text-sm text-[var(--color-text)]">
+                  <span className="flex-1 truncate" title={p}>
+                    {p}
+                  </span>
+                  <Button size="sm" onClick={() => removeCaCert(p)}>
+                    Remove
+                  </Button>
+                </li>
+              ))}
+            </ul>
+          )}
+          <Button size="sm" onClick={handleAddCaCert}>
+            Add certificate…
+          </Button>
+        </div>
+
+        {settings.trustedDownloadHosts.length > 0 && (
+          <div>
+            <Label>Allowed download hosts</Label>
+            <p className="mb-2 text-sm text-[var(--color-text-muted)]">
+              Hosts you allowed besides CurseForge and Orbis. You are asked again after removing one.
+            </p>
+            <ul className="space-y-1">
+              {settings.trustedDownloadHosts.map((h) => (
+                <li key={h} className="flex items-center gap-2 text-sm text-[var(--color-text)]">
+                  <span className="flex-1 truncate">{h}</span>
+                  <Button size="sm" onClick={() => removeTrustedHost(h)}>
+                    Remove
+                  </Button>
+                </li>
+              ))}
+            </ul>
+          </div>
+        )}
+
+        {/* Download retries */}
+        <div>
+          <Label htmlFor="max-retries">Download retries (0–10)</Label>
+          <Input
+            id="max-retries"
+            type="number"
+            min={0}
+            max={10}
+            value={settings.downloadMaxRetries}
+            onChange={(e) =>
+              setSettings((s) => ({
+                ...s,
+                downloadMaxRetries: Math.min(10, Math.max(0, Math.floor(Number(e.target.value) || 0))),
+              }))
+            }
+          />
+          <p className="mt-1 text-sm text-[var(--color-text-muted)]">
+            Connection errors, timeouts and busy servers (429, 5xx) are retried with increasing
+            delays. Other errors fail right away.
+          </p>
+        </div>
+
+        {/* Download cache */}
+        <div>
+          <Label htmlFor="cache-limit">Download cache size limit (MB, 0 to disable)</Label>
+          <div className="flex gap-2">
+            <Input
+              id="cache-limit"
+              type="number"
+              min={0}
+              value={settings.downloadCacheLimitMb}
+              onChange={(e) =>
+                setSettings((s) => ({
+                  ...s,
+                  downloadCacheLimitMb: Math.max(0, Math.floor(Number(e.target.value) || 0)),
+                }))
+              }
+              className="flex-1"
+            />
+            <Button size="sm" onClick={handleClearCache} disabled={!cacheUsage?.files}>
+              Clear cache
+            </Button>
+          </div>
+          {cacheUsage && (
+            <p className="mt-2 text-sm text-[var(--color-text-muted)]" role="status">
+              {cacheUsage.files} file(s), {formatBytes(cacheUsage.totalBytes)} in {cacheUsage.path}
+            </p>
+          )}
+          {cacheError && (
+            <p className="mt-2 text-sm text-[#ffb3b3]" role="alert">
+              {cacheError}
+            </p>
+          )}
+        </div>
+
+        {saveError && (
+          <p className="text-sm text-[#ffb3b3]" role="alert">
+            Settings not saved: {saveError}
+          </p>
+        )}
       </div>
     </PageContainer>
   );
@@ -70,6 +70,22 @@ describe("mapCfFileToModFile", () => {
     });
   });
 
+  test("maps hashes and length", () => {
+    const cf: CfFile = {
+      id: 1,
+      fileName: "a.jar",
+      fileLength: 1234,
+      hashes: [
+        { value: "AAF4C61DDCC5E8A2DABEDE0F3B482CD9AEA9434D", algo: 1 },
+        { value: "5d41402abc4b2a76b9719d911017c592", algo: 2 },
+      ],
+    };
+    const file = mapCfFileToModFile(cf, 1);
+    expect(file.sha1).toBe("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d");
+    expect(file.md5).toBe("5d41402abc4b2a76b9719d911017c592");
+    expect(file.fileLength).toBe(1234);
+  });
+
   test("maps beta release type", () => {
     const cf: CfFile = { id: 1, fileName: "a.jar", releaseType: 2 };
     expect(mapCfFileToModFile(cf, 1).releaseType).toBe("beta");
@@ -18,6 +18,8 @@ export interface CfFile {
   fileDate?: string;
   releaseType?: number;
   fileLength?: number;
+  /** algo 1 = SHA-1, 2 = MD5. */
+  hashes?: Array<{ value?: string | null; algo?: number }> | null;
 }
 
 /** CurseForge API returns download URL in a wrapper. */
@@ -72,6 +74,11 @@ function releaseTypeLabel(releaseType?: number): string | null {
   }
 }
 
+function fileHash(cf: CfFile, algo: number): string | undefined {
+  const value = cf.hashes?.find((h) => h.algo === algo)?.value?.trim().toLowerCase();
+  return value || undefined;
+}
+
 /** Map upstream file to ModFile. */
 export function mapCfFileToModFile(cf: CfFile, _projectId: number): ModFile {
   return {
@@ -81,5 +88,8 @@ export function mapCfFileToModFile(cf: CfFile, _projectId: number): ModFile {
     releaseType: releaseTypeLabel(cf.releaseType) ?? null,
     fileDate: cf.fileDate ?? new Date(0).toISOString(),
     downloadUrl: null, // Filled by getFileDownloadUrl when needed
+    sha1: fileHash(cf, 1),
+    md5: fileHash(cf, 2),
+    fileLength: cf.fileLength,
   };
 }
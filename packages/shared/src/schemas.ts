@@ -101,6 +101,9 @@ export const modFileSchema = z.object({
   releaseType: z.string().nullable().optional(),
   fileDate: z.string(), // ISO timestamp
   downloadUrl: z.string().url().nullable().optional(),
+  sha1: z.string().nullable().optional(), // hex, when the provider publishes it
+  md5: z.string().nullable().optional(),
+  fileLength: z.number().int().min(0).nullable().optional(), // bytes
 });
 
 export type ModFile = z.infer<typeof modFileSchema>;
@@ -160,6 +163,7 @@ export const installedModSchema = z.object({
   installedAt: z.string(), // ISO timestamp
   sourceUrl: z.string().url().optional(),
   enabled: z.boolean(),
+  fileHash: z.string().nullable().optional(), // "sha1:<hex>" or "md5:<hex>", verified at download
 });
 
 export type InstalledMod = z.infer<typeof installedModSchema>;